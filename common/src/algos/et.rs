@@ -1,95 +1,122 @@
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::{
-    collections::{BTreeMap, HashSet},
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashSet},
     time::Instant,
 };
 use tracing::info;
 
 use crate::runners::OutboundLocalTrust;
+use getset::CopyGetters;
 
 /// The trust weight given to the seed trust vector in the trust matrix calculation.
-const PRE_TRUST_WEIGHT: f32 = 0.5;
+pub const PRE_TRUST_WEIGHT: f32 = 0.5;
 
 /// The threshold value used for convergence check in the trust matrix calculation.
 ///
 /// If the absolute difference between the current score and the next score is
 /// less than `DELTA`, the score has converged.
-const DELTA: f32 = 0.01;
+pub const DELTA: f32 = 0.01;
 
-fn find_reachable_peers(
-    lt: &BTreeMap<u64, OutboundLocalTrust>,
-    seed: &BTreeMap<u64, f32>,
-) -> HashSet<u64> {
-    let mut to_visit: Vec<&u64> = seed.keys().collect();
-    let mut visited = HashSet::new();
-    while let Some(i) = to_visit.pop() {
-        if visited.contains(i) {
-            continue;
-        }
-        visited.insert(*i);
-        for (j, v) in lt.get(i).unwrap().outbound_trust_scores() {
-            if !visited.contains(j) && *v > 0.0 {
-                to_visit.push(j);
-            }
-        }
-    }
-    visited
+/// Local trust matrix and seed trust values as seen by the runs:
+///
+/// - If the seed is empty (or sums up to zero), every peer gets the same seed trust.
+/// - Peers without outbound trust distribute it to the seed peers, based on their seed trust.
+struct RunInputs<'a> {
+    lt: &'a BTreeMap<u64, OutboundLocalTrust>,
+    seed: Cow<'a, BTreeMap<u64, f32>>,
+    dangling: OutboundLocalTrust,
 }
 
-/// Pre-processes a mutable local trust matrix `lt` by modifying it in-place:
-///
-/// - Removes self-trust (diagonal entries), as prohibited by EigenTrust.
-fn pre_process(
-    lt: &mut BTreeMap<u64, OutboundLocalTrust>,
-    seed: &mut BTreeMap<u64, f32>,
-    count: u64,
-) {
-    // Calculate the sum of all seed trust values.
-    let sum: f32 = seed.par_iter().map(|(_, v)| v).sum();
+impl<'a> RunInputs<'a> {
+    fn new(
+        lt: &'a BTreeMap<u64, OutboundLocalTrust>,
+        seed: &'a BTreeMap<u64, f32>,
+        count: u64,
+    ) -> Self {
+        let sum: f32 = seed.par_iter().map(|(_, v)| v).sum();
+        let seed = if sum == 0.0 {
+            Cow::Owned((0..count).map(|i| (i, 1.0)).collect())
+        } else {
+            Cow::Borrowed(seed)
+        };
+        let dangling = OutboundLocalTrust::from_score_map(&seed);
+        Self { lt, seed, dangling }
+    }
 
-    if sum == 0.0 {
-        for i in 0..count {
-            seed.insert(i, 1.0);
+    /// Returns the outbound trust of the peer.
+    fn outbound(&self, i: &u64) -> &OutboundLocalTrust {
+        match self.lt.get(i) {
+            Some(from_map) if *from_map.outbound_sum() != 0.0 => from_map,
+            _ => &self.dangling,
         }
     }
 
-    for from in 0..count {
-        let sum = lt.get(&from).map(|lt| lt.outbound_sum()).unwrap_or(&0.0);
-        // If peer does not have outbound trust,
-        // his trust will be distributed to seed peers based on their seed/pre-trust
-        if *sum == 0.0 {
-            let single_lt = OutboundLocalTrust::from_score_map(seed);
-            lt.insert(from, single_lt);
+    /// Returns the peers reachable from the seed.
+    fn reachable(&self) -> HashSet<u64> {
+        let mut to_visit: Vec<&u64> = self.seed.keys().collect();
+        let mut reachable = HashSet::new();
+        while let Some(i) = to_visit.pop() {
+            if !reachable.insert(*i) {
+                continue;
+            }
+            for (j, v) in self.outbound(i).outbound_trust_scores() {
+                if !reachable.contains(j) && *v > 0.0 {
+                    to_visit.push(j);
+                }
+            }
         }
+        reachable
     }
+}
 
-    let reachable = find_reachable_peers(lt, seed);
-    lt.retain(|from, _| reachable.contains(from));
+/// Pre-processes the local trust matrix `lt` and the seed trust values `seed`, see
+/// [RunInputs], and returns their normalised versions, leaving the inputs untouched. Only the
+/// peers reachable from the seed are kept in the matrix.
+///
+/// The normalised matrix is the only copy of the data made, so peak memory stays close to
+/// the size of the matrix.
+fn normalised_inputs(
+    lt: &BTreeMap<u64, OutboundLocalTrust>,
+    seed: &BTreeMap<u64, f32>,
+    count: u64,
+) -> (BTreeMap<u64, OutboundLocalTrust>, BTreeMap<u64, f32>) {
+    let inputs = RunInputs::new(lt, seed, count);
+    let lt_norm = inputs
+        .reachable()
+        .par_iter()
+        .map(|i| (*i, inputs.outbound(i).norm()))
+        .collect();
+    (lt_norm, normalise_scores(&inputs.seed))
 }
 
-/// Normalizes the `lt` matrix by dividing each element by the sum of its row.
-fn normalise_lt(lt: &BTreeMap<u64, OutboundLocalTrust>) -> BTreeMap<u64, OutboundLocalTrust> {
-    lt.par_iter()
-        .fold(BTreeMap::new, |mut lt_norm, (from, from_map)| {
-            let from_map_norm = from_map.norm();
-            lt_norm.insert(*from, from_map_norm);
-            lt_norm
-        })
-        .reduce(BTreeMap::new, |mut acc, lt_norm| {
-            acc.extend(lt_norm);
-            acc
-        })
+/// Returns the set of peers that end up with a score after running [positive_run] on the
+/// given local trust matrix (`lt`) and seed trust values (`seed`): the outbound entries of the
+/// peers reachable from the seed.
+pub fn scored_peers(
+    lt: &BTreeMap<u64, OutboundLocalTrust>,
+    seed: &BTreeMap<u64, f32>,
+    count: u64,
+) -> BTreeSet<u64> {
+    let inputs = RunInputs::new(lt, seed, count);
+    inputs
+        .reachable()
+        .iter()
+        .flat_map(|i| inputs.outbound(i).outbound_trust_scores().keys().copied())
+        .collect()
 }
 
 /// Normalizes the scores, to eliminate the rounding error
+///
+/// The sum is accumulated sequentially in `f64`, so that the normalised scores sum up to 1
+/// within the `f32` precision whatever the number of peers, and don't depend on the threads.
 fn normalise_scores(scores: &BTreeMap<u64, f32>) -> BTreeMap<u64, f32> {
-    // Calculate the sum of all seed trust values.
-    let sum: f32 = scores.par_iter().map(|(_, v)| v).sum();
+    let sum: f64 = scores.values().map(|v| *v as f64).sum();
 
     scores
         .par_iter()
         .fold(BTreeMap::new, |mut scores, (i, value)| {
-            scores.insert(*i, *value / sum);
+            scores.insert(*i, (*value as f64 / sum) as f32);
             scores
         })
         .reduce(BTreeMap::new, |mut acc, scores| {
@@ -98,30 +125,38 @@ fn normalise_scores(scores: &BTreeMap<u64, f32>) -> BTreeMap<u64, f32> {
         })
 }
 
+#[derive(Clone, Copy, Debug, CopyGetters)]
+#[getset(get_copy = "pub")]
+/// How a [positive_run] converged.
+pub struct Convergence {
+    /// Iterations computed, including the one that confirmed the convergence.
+    iterations: u32,
+    /// Total score change of the last iteration.
+    delta: f32,
+}
+
 /// Performs the positive EigenTrust algorithm on the given local trust matrix (`lt`) and seed trust values (`seed`).
 /// The algorithm iteratively updates the scores of each node until convergence.
-/// It returns a vector of tuples containing the node ID and the final score.
+/// It returns a vector of tuples containing the node ID and the final score, and how the run
+/// converged.
 pub fn positive_run(
-    mut lt: BTreeMap<u64, OutboundLocalTrust>,
-    mut seed: BTreeMap<u64, f32>,
+    lt: &BTreeMap<u64, OutboundLocalTrust>,
+    seed: &BTreeMap<u64, f32>,
     count: u64,
-) -> Vec<(u64, f32)> {
+) -> (Vec<(u64, f32)>, Convergence) {
     let start = Instant::now();
     info!(
         "PRE_PROCESS_START, LT_SIZE: {}, SEED_SIZE: {}",
         lt.len(),
         seed.len()
     );
-    pre_process(&mut lt, &mut seed, count);
+    let (lt, seed) = normalised_inputs(lt, seed, count);
     info!(
         "PRE_PROCESS_FINISH: {:?}, LT_SIZE: {}, SEED_SIZE: {}",
         start.elapsed(),
         lt.len(),
         seed.len()
     );
-    info!("NORMALISE_LT_SEED");
-    seed = normalise_scores(&seed);
-    lt = normalise_lt(&lt);
 
     // Initialize the scores of each node to the seed trust values.
     let mut scores = seed.clone();
@@ -130,7 +165,7 @@ pub fn positive_run(
     info!("COMPUTE_START");
     let start = Instant::now();
     let mut i = 0;
-    loop {
+    let convergence = loop {
         // Calculate the n+1 scores of each node.
         let n_plus_1_scores = iteration(&lt, &seed, &scores);
         // Normalise n+1 scores.
@@ -142,23 +177,27 @@ pub fn positive_run(
         // Check for convergence.
         let (is_converged, delta) = is_converged(&n_plus_1_scores, &n_plus_2_scores);
         info!("ITER: {}, CONVERGED: {}, DELTA: {}", i, is_converged, delta);
+        // Every step computes two iterations.
+        i += 1;
         if is_converged {
             // Return previous iteration, since the scores are converged.
             scores = n_plus_1_scores;
-            break;
+            break Convergence {
+                iterations: 2 * i,
+                delta,
+            };
         } else {
             // Update the scores with the latest scores.
             scores = n_plus_2_scores;
         }
-        i += 1;
-    }
+    };
     info!(
         "COMPUTE_END: {:?}, NUM_SCORES: {}, NUM_ITER: {}",
         start.elapsed(),
         scores.len(),
-        i
+        convergence.iterations
     );
-    scores.into_iter().collect()
+    (scores.into_iter().collect(), convergence)
 }
 
 /// Given the previous scores (`scores`) and the next scores (`next_scores`), checks if the scores have converged.
@@ -183,8 +222,8 @@ pub fn is_converged(scores: &BTreeMap<u64, f32>, next_scores: &BTreeMap<u64, f32
 /// seed trust values (`seed`), and previous scores (`scores`).
 /// It returns `true` if the scores have converged and `false` otherwise.
 pub fn convergence_check(
-    mut lt: BTreeMap<u64, OutboundLocalTrust>,
-    mut seed: BTreeMap<u64, f32>,
+    lt: &BTreeMap<u64, OutboundLocalTrust>,
+    seed: &BTreeMap<u64, f32>,
     scores: &BTreeMap<u64, f32>,
     count: u64,
 ) -> bool {
@@ -193,15 +232,12 @@ pub fn convergence_check(
         lt.len(),
         seed.len()
     );
-    pre_process(&mut lt, &mut seed, count);
+    let (lt, seed) = normalised_inputs(lt, seed, count);
     info!(
         "PRE_PROCESS_END. LT_SIZE: {}, SEED_SIZE: {}",
         lt.len(),
         seed.len()
     );
-    info!("NORMALISE_LT_SEED");
-    seed = normalise_scores(&seed);
-    lt = normalise_lt(&lt);
 
     info!("CONVERGENCE_START");
     let start = Instant::now();
@@ -239,15 +275,12 @@ fn iteration(
             }
             partial
         })
-        .reduce(
-            || BTreeMap::new(),
-            |mut acc, partial| {
-                for (k, v) in partial {
-                    *acc.entry(k).or_insert(0.0) += v;
-                }
-                acc
-            },
-        );
+        .reduce(BTreeMap::new, |mut acc, partial| {
+            for (k, v) in partial {
+                *acc.entry(k).or_insert(0.0) += v;
+            }
+            acc
+        });
 
     // Step 4: Apply pre-trust weighted normalization
     for (i, v) in &mut next_scores {
@@ -257,3 +290,55 @@ fn iteration(
 
     next_scores
 }
+
+#[cfg(test)]
+mod test {
+    use crate::algos::et::{normalise_scores, positive_run, scored_peers, DELTA};
+    use crate::runners::OutboundLocalTrust;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn should_match_scored_peers_with_positive_run() {
+        // 0 -> 1 -> 2, 3 is dangling, 4 -> 5 is unreachable from the seed.
+        let mut lt = BTreeMap::new();
+        lt.insert(
+            0,
+            OutboundLocalTrust::from_score_map(&BTreeMap::from([(1, 1.0)])),
+        );
+        lt.insert(
+            1,
+            OutboundLocalTrust::from_score_map(&BTreeMap::from([(2, 1.0), (3, 0.5)])),
+        );
+        lt.insert(
+            4,
+            OutboundLocalTrust::from_score_map(&BTreeMap::from([(5, 1.0)])),
+        );
+        let seed = BTreeMap::from([(0, 1.0)]);
+
+        let expected = scored_peers(&lt, &seed, 6);
+        let (scores, convergence) = positive_run(&lt, &seed, 6);
+        assert!(convergence.iterations() >= 2 && convergence.iterations() % 2 == 0);
+        assert!(convergence.delta() <= DELTA);
+        let scores: BTreeSet<u64> = scores.into_iter().map(|(i, _)| i).collect();
+
+        assert_eq!(expected, scores);
+        assert_eq!(expected, BTreeSet::from([0, 1, 2, 3]));
+
+        // Without a seed, every peer is part of the default seed.
+        let expected = scored_peers(&lt, &BTreeMap::new(), 6);
+        let scores: BTreeSet<u64> = positive_run(&lt, &BTreeMap::new(), 6)
+            .0
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(expected, scores);
+        assert_eq!(expected, BTreeSet::from([0, 1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn should_normalise_many_scores() {
+        let scores: BTreeMap<u64, f32> = (0..1_000_000).map(|i| (i, 0.1 + i as f32)).collect();
+        let sum: f64 = normalise_scores(&scores).values().map(|v| *v as f64).sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+    }
+}
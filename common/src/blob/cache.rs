@@ -0,0 +1,427 @@
+//! Local, content-addressed cache of downloaded datasets.
+//!
+//! Objects are stored decompressed under `<root>/<prefix>/<id>`. Since ids are content hashes,
+//! a cached object never goes stale. When the total size goes over the limit, the least
+//! recently used objects are evicted.
+//!
+//! The computer and challenger on the same machine can share the same root. Partial writes are
+//! only removed once stale, objects evicted by the other process are downloaded again, and
+//! opened files stay readable after an eviction. The size limit is accounted per process.
+
+use crate::blob::{verify_content, BlobKey, BlobKind, BlobStore, Error};
+use crate::config::Settings;
+use crate::io::compression::DecompressWriter;
+use crate::merkle::Hash;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// Root directory used when none is configured.
+pub const DEFAULT_CACHE_ROOT: &str = "./cache";
+/// Size limit used when none is configured: 10 GiB.
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+const TMP_SUFFIX: &str = ".tmp";
+/// Age after which a partial write is left over by a stopped process, rather than being written
+/// by a running one.
+const STALE_TMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug, PartialEq)]
+/// Location and size limit of the [`DatasetCache`].
+pub struct CacheConfig {
+    root: PathBuf,
+    max_bytes: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_ROOT.into(), DEFAULT_CACHE_MAX_BYTES)
+    }
+}
+
+impl CacheConfig {
+    pub fn new(root: PathBuf, max_bytes: u64) -> Self {
+        Self { root, max_bytes }
+    }
+
+    /// Reads the config from the environment only, see [Self::from_settings].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Reads the config from the `DATA_CACHE_ROOT` and `DATA_CACHE_MAX_BYTES` settings, falling
+    /// back to the defaults.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        let root = settings
+            .get("DATA_CACHE_ROOT")
+            .unwrap_or(DEFAULT_CACHE_ROOT.to_string());
+        let max_bytes = settings
+            .parse::<u64>("DATA_CACHE_MAX_BYTES")
+            .map_err(|e| Error::Config(e.to_string()))?
+            .unwrap_or(DEFAULT_CACHE_MAX_BYTES);
+        Ok(Self::new(root.into(), max_bytes))
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// Content-addressed cache of datasets, in front of a [`BlobStore`].
+pub struct DatasetCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<BlobKey, CacheEntry>>,
+    tmp_counter: AtomicU64,
+}
+
+impl DatasetCache {
+    /// Opens the cache, indexing the objects left by previous runs and removing stale partial
+    /// writes.
+    pub fn new(config: CacheConfig) -> Result<Self, Error> {
+        let mut entries = HashMap::new();
+        for kind in [BlobKind::Trust, BlobKind::Seed, BlobKind::Scores] {
+            let dir = config.root.join(kind.prefix());
+            fs::create_dir_all(&dir).map_err(Error::Io)?;
+            for dir_entry in fs::read_dir(&dir).map_err(Error::Io)? {
+                let dir_entry = dir_entry.map_err(Error::Io)?;
+                let name = dir_entry.file_name().to_string_lossy().to_string();
+                let metadata = match dir_entry.metadata() {
+                    Ok(metadata) => metadata,
+                    // Renamed or removed by another process sharing the root.
+                    Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                    Err(e) => return Err(Error::Io(e)),
+                };
+                if name.ends_with(TMP_SUFFIX) {
+                    if is_stale(&metadata) {
+                        remove_if_exists(&dir_entry.path()).map_err(Error::Io)?;
+                    }
+                    continue;
+                }
+                if !metadata.is_file() || !is_valid_id(&name) {
+                    continue;
+                }
+                let entry = CacheEntry {
+                    size: metadata.len(),
+                    last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                };
+                entries.insert(BlobKey::new(kind, name), entry);
+            }
+        }
+        let cache = Self {
+            config,
+            entries: Mutex::new(entries),
+            tmp_counter: AtomicU64::new(0),
+        };
+        cache.evict(None);
+        Ok(cache)
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Path of the cached object with the key.
+    pub fn path(&self, key: &BlobKey) -> PathBuf {
+        self.config.root.join(key.kind().prefix()).join(key.id())
+    }
+
+    /// Total size of the cached objects, in bytes.
+    pub fn size(&self) -> u64 {
+        self.entries.lock().unwrap().values().map(|x| x.size).sum()
+    }
+
+    /// Checks whether the object is cached.
+    pub fn contains(&self, key: &BlobKey) -> bool {
+        self.entries.lock().unwrap().contains_key(key)
+    }
+
+    /// Opens the cached object, downloading it from the store first if it's not cached.
+    /// The returned file stays readable even if the object is evicted afterwards.
+    pub async fn open(&self, store: &dyn BlobStore, key: &BlobKey) -> Result<File, Error> {
+        if !is_valid_id(key.id()) {
+            return Err(Error::InvalidKey(key.clone()));
+        }
+        if let Some(file) = self.open_cached(key)? {
+            debug!("Cache hit: {}", key);
+            return Ok(file);
+        }
+
+        debug!("Cache miss: {}", key);
+        let path = self.path(key);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).map_err(Error::Io)?;
+        // Write to a unique temporary file first, so readers never see a partial object,
+        // even when several downloads of the same object race.
+        let tmp_path = dir.join(format!(
+            ".{}.{}.{}{}",
+            key.id(),
+            std::process::id(),
+            self.tmp_counter.fetch_add(1, Ordering::Relaxed),
+            TMP_SUFFIX
+        ));
+        let res = download(store, key, &tmp_path).await;
+        let size = match res {
+            Ok(size) => size,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+        // Opened before the rename, so that it stays readable if another process sharing the
+        // root evicts the object right away.
+        let file = File::open(&tmp_path).map_err(Error::Io)?;
+        fs::rename(&tmp_path, &path).map_err(Error::Io)?;
+
+        let entry = CacheEntry {
+            size,
+            last_used: SystemTime::now(),
+        };
+        self.entries.lock().unwrap().insert(key.clone(), entry);
+        self.evict(Some(key));
+        Ok(file)
+    }
+
+    /// Checks the object against its content id, removing it from the cache if it doesn't match,
+    /// so that the next attempt downloads it again.
+    pub fn verify(&self, key: &BlobKey, actual: &Hash) -> Result<(), Error> {
+        verify_content(key, actual).inspect_err(|_| {
+            if let Err(e) = self.remove(key) {
+                warn!("Failed to remove {} from the cache: {}", key, e);
+            }
+        })
+    }
+
+    /// Removes the object from the cache.
+    pub fn remove(&self, key: &BlobKey) -> Result<(), Error> {
+        self.entries.lock().unwrap().remove(key);
+        remove_if_exists(&self.path(key)).map_err(Error::Io)
+    }
+
+    fn open_cached(&self, key: &BlobKey) -> Result<Option<File>, Error> {
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(key) {
+            return Ok(None);
+        }
+        match File::open(self.path(key)) {
+            Ok(file) => {
+                let now = SystemTime::now();
+                entries.get_mut(key).unwrap().last_used = now;
+                // Persist the recency, so the eviction order survives restarts.
+                if let Err(e) = file.set_modified(now) {
+                    debug!("Failed to update access time of {}: {}", key, e);
+                }
+                Ok(Some(file))
+            }
+            // Removed behind our back, e.g. by another process sharing the root.
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                entries.remove(key);
+                Ok(None)
+            }
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+
+    /// Evicts the least recently used objects until the cache fits in its size limit.
+    /// The `keep` object is never evicted, even if it's larger than the limit by itself.
+    fn evict(&self, keep: Option<&BlobKey>) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut total: u64 = entries.values().map(|x| x.size).sum();
+        if total <= self.config.max_bytes {
+            return;
+        }
+        let mut candidates: Vec<(BlobKey, SystemTime, u64)> = entries
+            .iter()
+            .filter(|(key, _)| Some(*key) != keep)
+            .map(|(key, entry)| (key.clone(), entry.last_used, entry.size))
+            .collect();
+        candidates.sort_by_key(|(_, last_used, _)| *last_used);
+        for (key, _, size) in candidates {
+            if total <= self.config.max_bytes {
+                break;
+            }
+            debug!("Evicting {} ({} bytes)", key, size);
+            if let Err(e) = remove_if_exists(&self.path(&key)) {
+                warn!("Failed to evict {}: {}", key, e);
+                continue;
+            }
+            entries.remove(&key);
+            total -= size;
+        }
+    }
+}
+
+/// Ids are hex-encoded hashes. Anything else could escape the cache root.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Removes the file, if it wasn't already removed, e.g. by another process sharing the root.
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Checks whether a partial write wasn't modified for [STALE_TMP_AGE].
+fn is_stale(metadata: &fs::Metadata) -> bool {
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    modified.elapsed().is_ok_and(|age| age > STALE_TMP_AGE)
+}
+
+/// Downloads and decompresses the object into the file, returning the decompressed size.
+async fn download(store: &dyn BlobStore, key: &BlobKey, path: &Path) -> Result<u64, Error> {
+    let file = File::create(path).map_err(Error::Io)?;
+    let mut writer = DecompressWriter::new(file);
+    store.get_into(key, &mut writer).await?;
+    let file = writer.finish().map_err(Error::Decompress)?;
+    file.sync_all().map_err(Error::Io)?;
+    Ok(file.metadata().map_err(Error::Io)?.len())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blob::cache::{CacheConfig, DatasetCache, STALE_TMP_AGE};
+    use crate::blob::{BlobKey, BlobStore, Error, MemoryBlobStore};
+    use crate::io::compression::{compress, Compression};
+    use crate::merkle::Hash;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    fn temp_root(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("openrank-cache-{}-{}", name, std::process::id()))
+    }
+
+    fn read(mut file: std::fs::File) -> Vec<u8> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn should_reuse_cached_objects() {
+        let root = temp_root("reuse");
+        let cache = DatasetCache::new(CacheConfig::new(root.clone(), 1024)).unwrap();
+        let store = MemoryBlobStore::new();
+        let data = b"i,j,v\na,b,1\n".to_vec();
+        let key = BlobKey::trust("aa");
+        store
+            .put(&key, compress(&data, Compression::Zstd).unwrap())
+            .await
+            .unwrap();
+
+        // Stored decompressed.
+        assert_eq!(read(cache.open(&store, &key).await.unwrap()), data);
+        assert!(cache.contains(&key));
+        assert_eq!(cache.size(), data.len() as u64);
+
+        // Served from the cache, without touching the store.
+        let empty = MemoryBlobStore::new();
+        assert_eq!(read(cache.open(&empty, &key).await.unwrap()), data);
+
+        // Indexed again after a restart.
+        let cache = DatasetCache::new(CacheConfig::new(root.clone(), 1024)).unwrap();
+        assert_eq!(read(cache.open(&empty, &key).await.unwrap()), data);
+
+        // Dropped when it doesn't match its id.
+        let res = cache.verify(&key, &Hash::default());
+        assert!(matches!(res, Err(Error::ContentHashMismatch { .. })));
+        assert!(!cache.contains(&key));
+
+        let res = cache.open(&empty, &BlobKey::seed("bb")).await;
+        assert!(matches!(res, Err(Error::NotFound(_))));
+        assert!(!cache.contains(&BlobKey::seed("bb")));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_evict_least_recently_used() {
+        let root = temp_root("evict");
+        let cache = DatasetCache::new(CacheConfig::new(root.clone(), 10)).unwrap();
+        let store = MemoryBlobStore::new();
+        for id in ["a1", "b2", "c3"] {
+            store.put(&BlobKey::seed(id), vec![0; 4]).await.unwrap();
+        }
+
+        cache.open(&store, &BlobKey::seed("a1")).await.unwrap();
+        cache.open(&store, &BlobKey::seed("b2")).await.unwrap();
+        // Touch "a1", so "b2" becomes the least recently used.
+        cache.open(&store, &BlobKey::seed("a1")).await.unwrap();
+        cache.open(&store, &BlobKey::seed("c3")).await.unwrap();
+
+        assert!(cache.contains(&BlobKey::seed("a1")));
+        assert!(!cache.contains(&BlobKey::seed("b2")));
+        assert!(!cache.path(&BlobKey::seed("b2")).exists());
+        assert!(cache.contains(&BlobKey::seed("c3")));
+        assert_eq!(cache.size(), 8);
+
+        // An object larger than the limit is still kept, on its own.
+        store
+            .put(&BlobKey::scores("dd"), vec![0; 16])
+            .await
+            .unwrap();
+        let file = cache.open(&store, &BlobKey::scores("dd")).await.unwrap();
+        assert_eq!(read(file).len(), 16);
+        assert_eq!(cache.size(), 16);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_ids_and_clean_partial_writes() {
+        let root = temp_root("invalid");
+        std::fs::create_dir_all(root.join("trust")).unwrap();
+        let stale = root.join("trust").join(".aa.1.0.tmp");
+        std::fs::write(&stale, b"partial").unwrap();
+        let old = SystemTime::now() - STALE_TMP_AGE * 2;
+        std::fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        // Possibly still written by another process sharing the root.
+        let fresh = root.join("trust").join(".bb.2.0.tmp");
+        std::fs::write(&fresh, b"partial").unwrap();
+
+        let cache = DatasetCache::new(CacheConfig::new(root.clone(), 1024)).unwrap();
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+        assert_eq!(cache.size(), 0);
+
+        let store = MemoryBlobStore::new();
+        let res = cache.open(&store, &BlobKey::trust("../aa")).await;
+        assert!(matches!(res, Err(Error::InvalidKey(_))));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_share_the_root_between_processes() {
+        let root = temp_root("shared");
+        let computer = DatasetCache::new(CacheConfig::new(root.clone(), 1024)).unwrap();
+        let store = MemoryBlobStore::new();
+        let key = BlobKey::trust("aa");
+        store.put(&key, b"data".to_vec()).await.unwrap();
+        let file = computer.open(&store, &key).await.unwrap();
+
+        let challenger = DatasetCache::new(CacheConfig::new(root.clone(), 1024)).unwrap();
+        assert!(challenger.contains(&key));
+        // Removed by the computer, while the challenger still has it indexed.
+        computer.remove(&key).unwrap();
+        assert_eq!(read(file), b"data");
+        assert_eq!(read(challenger.open(&store, &key).await.unwrap()), b"data");
+        assert!(challenger.path(&key).exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
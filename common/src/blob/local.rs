@@ -0,0 +1,88 @@
+use crate::blob::{BlobKey, BlobStore, Error};
+use async_trait::async_trait;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+/// Store that keeps objects as files under a root directory, one directory per key prefix.
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Path of the object with the key.
+    pub fn path(&self, key: &BlobKey) -> PathBuf {
+        self.root.join(key.kind().prefix()).join(key.id())
+    }
+
+    fn open(&self, key: &BlobKey) -> Result<File, Error> {
+        File::open(self.path(key)).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::NotFound(key.clone()),
+            _ => Error::Io(e),
+        })
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &BlobKey, data: Vec<u8>) -> Result<(), Error> {
+        let path = self.path(key);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).map_err(Error::Io)?;
+        // Write to a temporary file first, so readers never see a partial object.
+        let tmp_path = dir.join(format!(".{}.tmp", key.id()));
+        fs::write(&tmp_path, data).map_err(Error::Io)?;
+        fs::rename(&tmp_path, &path).map_err(Error::Io)
+    }
+
+    async fn get(&self, key: &BlobKey) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        std::io::copy(&mut self.open(key)?, &mut data).map_err(Error::Io)?;
+        Ok(data)
+    }
+
+    async fn exists(&self, key: &BlobKey) -> Result<bool, Error> {
+        Ok(self.path(key).is_file())
+    }
+
+    async fn get_into(&self, key: &BlobKey, writer: &mut (dyn Write + Send)) -> Result<u64, Error> {
+        std::io::copy(&mut self.open(key)?, writer).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blob::{BlobKey, BlobStore, Error, LocalBlobStore};
+
+    #[tokio::test]
+    async fn should_store_objects_under_prefixes() {
+        let root = std::env::temp_dir().join(format!("openrank-blob-{}", std::process::id()));
+        let store = LocalBlobStore::new(root.clone());
+
+        store
+            .put(&BlobKey::scores("abc"), vec![1, 2])
+            .await
+            .unwrap();
+        store.put(&BlobKey::scores("abc"), vec![3]).await.unwrap();
+
+        assert!(root.join("scores").join("abc").is_file());
+        assert!(store.exists(&BlobKey::scores("abc")).await.unwrap());
+        assert_eq!(store.get(&BlobKey::scores("abc")).await.unwrap(), vec![3]);
+
+        let mut out = Vec::new();
+        store
+            .get_into(&BlobKey::scores("abc"), &mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, vec![3]);
+
+        let res = store.get(&BlobKey::trust("abc")).await;
+        assert!(matches!(res, Err(Error::NotFound(_))));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use crate::blob::{BlobKey, BlobStore, Error};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+#[derive(Default)]
+/// Store that keeps objects in memory. Used in tests and for local runs.
+pub struct MemoryBlobStore {
+    objects: RwLock<HashMap<BlobKey, Vec<u8>>>,
+}
+
+impl MemoryBlobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored objects.
+    pub fn len(&self) -> usize {
+        self.objects.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl BlobStore for MemoryBlobStore {
+    async fn put(&self, key: &BlobKey, data: Vec<u8>) -> Result<(), Error> {
+        self.objects.write().unwrap().insert(key.clone(), data);
+        Ok(())
+    }
+
+    async fn get(&self, key: &BlobKey) -> Result<Vec<u8>, Error> {
+        let objects = self.objects.read().unwrap();
+        objects
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(key.clone()))
+    }
+
+    async fn exists(&self, key: &BlobKey) -> Result<bool, Error> {
+        Ok(self.objects.read().unwrap().contains_key(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blob::{BlobKey, BlobStore, Error, MemoryBlobStore};
+
+    #[tokio::test]
+    async fn should_store_objects_by_kind() {
+        let store = MemoryBlobStore::new();
+        store.put(&BlobKey::trust("a"), vec![1]).await.unwrap();
+        store.put(&BlobKey::seed("a"), vec![2]).await.unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(&BlobKey::trust("a")).await.unwrap(), vec![1]);
+        assert_eq!(store.get(&BlobKey::seed("a")).await.unwrap(), vec![2]);
+        assert!(!store.exists(&BlobKey::scores("a")).await.unwrap());
+
+        let mut out = Vec::new();
+        let size = store.get_into(&BlobKey::seed("a"), &mut out).await.unwrap();
+        assert_eq!((size, out), (1, vec![2]));
+
+        let res = store.get(&BlobKey::meta("a")).await;
+        assert!(matches!(res, Err(Error::NotFound(key)) if key.to_string() == "meta/a"));
+    }
+}
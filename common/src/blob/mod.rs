@@ -0,0 +1,253 @@
+//! Storage of datasets and job metadata, behind a common [`BlobStore`] interface.
+
+use crate::config::Settings;
+use crate::merkle::{hash_leaf, Hash};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha3::Keccak256;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub mod cache;
+pub mod local;
+pub mod memory;
+pub mod s3;
+
+pub use cache::{CacheConfig, DatasetCache};
+pub use local::LocalBlobStore;
+pub use memory::MemoryBlobStore;
+pub use s3::S3BlobStore;
+
+/// Bucket used when none is configured.
+pub const DEFAULT_BUCKET: &str = "openrank-data-dev";
+/// Region used when none is configured.
+pub const DEFAULT_REGION: &str = "us-west-2";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Kind of the stored object, which determines its key prefix.
+pub enum BlobKind {
+    Trust,
+    Seed,
+    Scores,
+    Meta,
+}
+
+impl BlobKind {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Trust => "trust",
+            Self::Seed => "seed",
+            Self::Scores => "scores",
+            Self::Meta => "meta",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// Key of a stored object, made of its kind and its content id.
+pub struct BlobKey {
+    kind: BlobKind,
+    id: String,
+}
+
+impl BlobKey {
+    pub fn new(kind: BlobKind, id: impl Into<String>) -> Self {
+        Self {
+            kind,
+            id: id.into(),
+        }
+    }
+
+    pub fn trust(id: impl Into<String>) -> Self {
+        Self::new(BlobKind::Trust, id)
+    }
+
+    pub fn seed(id: impl Into<String>) -> Self {
+        Self::new(BlobKind::Seed, id)
+    }
+
+    pub fn scores(id: impl Into<String>) -> Self {
+        Self::new(BlobKind::Scores, id)
+    }
+
+    pub fn meta(id: impl Into<String>) -> Self {
+        Self::new(BlobKind::Meta, id)
+    }
+
+    pub fn kind(&self) -> BlobKind {
+        self.kind
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Display for BlobKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}/{}", self.kind.prefix(), self.id)
+    }
+}
+
+#[async_trait]
+/// Storage for datasets and job metadata.
+pub trait BlobStore: Send + Sync {
+    /// Stores the object, replacing any previous one with the same key.
+    async fn put(&self, key: &BlobKey, data: Vec<u8>) -> Result<(), Error>;
+
+    /// Fetches the whole object.
+    async fn get(&self, key: &BlobKey) -> Result<Vec<u8>, Error>;
+
+    /// Checks whether the object exists.
+    async fn exists(&self, key: &BlobKey) -> Result<bool, Error>;
+
+    /// Writes the object into the writer, returning the number of bytes written.
+    /// Stores that can stream the object override this to avoid buffering it in memory.
+    async fn get_into(&self, key: &BlobKey, writer: &mut (dyn Write + Send)) -> Result<u64, Error> {
+        let data = self.get(key).await?;
+        writer.write_all(&data).map_err(Error::Io)?;
+        Ok(data.len() as u64)
+    }
+}
+
+/// Checks that the content id in the key matches the hash of the content.
+/// Meta objects are hashed with [`content_hash`], datasets with their canonical encoding.
+pub fn verify_content(key: &BlobKey, actual: &Hash) -> Result<(), Error> {
+    let actual = actual.clone().to_hex();
+    if !key.id().eq_ignore_ascii_case(&actual) {
+        return Err(Error::ContentHashMismatch {
+            key: key.clone(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Keccak hash of the raw bytes, which is the content id of meta objects.
+pub fn content_hash(data: &[u8]) -> Hash {
+    hash_leaf::<Keccak256>(data.to_vec())
+}
+
+/// Fetches a meta object and checks it against its content id.
+pub async fn get_verified(store: &dyn BlobStore, key: &BlobKey) -> Result<Vec<u8>, Error> {
+    let data = store.get(key).await?;
+    verify_content(key, &content_hash(&data))?;
+    Ok(data)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// Selects and configures the [`BlobStore`] implementation.
+pub enum BlobStoreConfig {
+    S3 { bucket: String, region: String },
+    Local { root: PathBuf },
+    Memory,
+}
+
+impl Default for BlobStoreConfig {
+    fn default() -> Self {
+        Self::S3 {
+            bucket: DEFAULT_BUCKET.to_string(),
+            region: DEFAULT_REGION.to_string(),
+        }
+    }
+}
+
+impl BlobStoreConfig {
+    /// Reads the config from the environment only, see [Self::from_settings].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Reads the config from the `BLOB_STORE` (`s3`, `local` or `memory`), `S3_BUCKET`,
+    /// `S3_REGION` and `BLOB_STORE_ROOT` settings. Defaults to the S3 bucket.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        let var = |name: &str| settings.get(name);
+        match var("BLOB_STORE").as_deref() {
+            None | Some("s3") => Ok(Self::S3 {
+                bucket: var("S3_BUCKET").unwrap_or(DEFAULT_BUCKET.to_string()),
+                region: var("S3_REGION").unwrap_or(DEFAULT_REGION.to_string()),
+            }),
+            Some("local") => {
+                let root = var("BLOB_STORE_ROOT").ok_or(Error::Config(
+                    "BLOB_STORE_ROOT must be set for the local store".to_string(),
+                ))?;
+                Ok(Self::Local { root: root.into() })
+            }
+            Some("memory") => Ok(Self::Memory),
+            Some(other) => Err(Error::Config(format!("Unknown blob store: '{}'", other))),
+        }
+    }
+
+    /// Creates the configured store.
+    pub async fn build(&self) -> Arc<dyn BlobStore> {
+        match self {
+            Self::S3 { bucket, region } => {
+                Arc::new(S3BlobStore::from_env(bucket.clone(), region.clone()).await)
+            }
+            Self::Local { root } => Arc::new(LocalBlobStore::new(root.clone())),
+            Self::Memory => Arc::new(MemoryBlobStore::new()),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+/// Errors that can arise while accessing a [`BlobStore`].
+pub enum Error {
+    /// There is no object with the key.
+    #[error("Blob not found: {0}")]
+    NotFound(BlobKey),
+    /// Local storage failed.
+    #[error("I/O error: {0}")]
+    Io(std::io::Error),
+    /// The key can't be used as a local path.
+    #[error("Invalid blob key: {0}")]
+    InvalidKey(BlobKey),
+    /// The downloaded object couldn't be decompressed.
+    #[error("Decompression error: {0}")]
+    Decompress(crate::io::Error),
+    /// The S3 request failed.
+    #[error("S3 error: {0}")]
+    S3(String),
+    /// The content doesn't hash to the id in its key.
+    #[error("Content hash mismatch for {key}: found {actual}")]
+    ContentHashMismatch { key: BlobKey, actual: String },
+    /// The store is not configured correctly.
+    #[error("Config error: {0}")]
+    Config(String),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blob::{
+        content_hash, get_verified, verify_content, BlobKey, BlobStore, Error, MemoryBlobStore,
+    };
+    use crate::tx::canonical::trust_id;
+    use crate::tx::trust::TrustEntry;
+
+    #[tokio::test]
+    async fn should_reject_tampered_meta() {
+        let store = MemoryBlobStore::new();
+        let data = b"[]".to_vec();
+        let key = BlobKey::meta(content_hash(&data).to_hex());
+        store.put(&key, data).await.unwrap();
+        assert_eq!(get_verified(&store, &key).await.unwrap(), b"[]".to_vec());
+
+        store.put(&key, b"[{}]".to_vec()).await.unwrap();
+        let res = get_verified(&store, &key).await;
+        assert!(matches!(res, Err(Error::ContentHashMismatch { .. })));
+    }
+
+    #[test]
+    fn should_verify_dataset_ids() {
+        let entries = vec![TrustEntry::new("a".to_string(), "b".to_string(), 1.0)];
+        let key = BlobKey::trust(trust_id(&entries).to_hex());
+        verify_content(&key, &trust_id(&entries)).unwrap();
+
+        let other = vec![TrustEntry::new("a".to_string(), "b".to_string(), 0.5)];
+        let res = verify_content(&key, &trust_id(&other));
+        assert!(matches!(res, Err(Error::ContentHashMismatch { key: k, .. }) if k == key));
+    }
+}
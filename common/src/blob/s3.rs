@@ -0,0 +1,100 @@
+use crate::blob::{BlobKey, BlobStore, Error};
+use crate::io::compression::Compression;
+use async_trait::async_trait;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use std::io::Write;
+
+/// Store backed by an S3 bucket.
+pub struct S3BlobStore {
+    client: Client,
+    bucket: String,
+}
+
+impl S3BlobStore {
+    pub fn new(client: Client, bucket: String) -> Self {
+        Self { client, bucket }
+    }
+
+    /// Creates the client with credentials from the environment.
+    pub async fn from_env(bucket: String, region: String) -> Self {
+        let config = aws_config::from_env()
+            .region(aws_config::Region::new(region))
+            .load()
+            .await;
+        Self::new(Client::new(&config), bucket)
+    }
+
+    async fn get_object(&self, key: &BlobKey) -> Result<GetObjectOutput, Error> {
+        self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key.to_string())
+            .send()
+            .await
+            .map_err(|e| match e.into_service_error() {
+                GetObjectError::NoSuchKey(_) => Error::NotFound(key.clone()),
+                e => Error::S3(e.to_string()),
+            })
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &BlobKey, data: Vec<u8>) -> Result<(), Error> {
+        let content_encoding = Compression::detect(&data).content_encoding();
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key.to_string())
+            .set_content_encoding(content_encoding.map(String::from))
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .map_err(|e| Error::S3(e.into_service_error().to_string()))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &BlobKey) -> Result<Vec<u8>, Error> {
+        let res = self.get_object(key).await?;
+        let data = res
+            .body
+            .collect()
+            .await
+            .map_err(|e| Error::S3(e.to_string()))?;
+        Ok(data.to_vec())
+    }
+
+    async fn exists(&self, key: &BlobKey) -> Result<bool, Error> {
+        let res = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key.to_string())
+            .send()
+            .await;
+        match res {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let e = e.into_service_error();
+                if e.is_not_found() {
+                    Ok(false)
+                } else {
+                    Err(Error::S3(e.to_string()))
+                }
+            }
+        }
+    }
+
+    async fn get_into(&self, key: &BlobKey, writer: &mut (dyn Write + Send)) -> Result<u64, Error> {
+        let mut res = self.get_object(key).await?;
+        let mut size = 0;
+        while let Some(bytes) = res.body.next().await {
+            let bytes = bytes.map_err(|e| Error::S3(e.to_string()))?;
+            writer.write_all(&bytes).map_err(Error::Io)?;
+            size += bytes.len() as u64;
+        }
+        Ok(size)
+    }
+}
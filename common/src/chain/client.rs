@@ -0,0 +1,566 @@
+//! Typed client of the `OpenRankManager` contract, shared by the SDK and the nodes.
+
+use crate::chain::contract::IOpenRankManager::{
+    IOpenRankManagerEvents, IOpenRankManagerInstance, MetaChallenge, MetaChallengeEvent,
+    MetaComputeRequest, MetaComputeRequestEvent, MetaComputeResult, MetaComputeResultEvent,
+};
+use crate::chain::{decode_log, Error, ManagerError, TxTracker};
+use crate::config::Settings;
+use crate::merkle::Hash;
+use crate::metrics;
+use alloy::contract::{CallBuilder, CallDecoder};
+use alloy::primitives::{Address, Bytes, FixedBytes, B256, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::{Filter, Log, TransactionReceipt};
+use alloy::sol_types::{SolEvent, SolEventInterface};
+use getset::Getters;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, info};
+
+/// How long to wait for a transaction to be included, used when none is configured.
+pub const DEFAULT_RECEIPT_TIMEOUT_SECS: u64 = 120;
+/// How long a transaction may stay pending before it is replaced, used when none is configured.
+pub const DEFAULT_REPLACE_AFTER_SECS: u64 = 60;
+/// Fee increase of a replacement, used when none is configured. Nodes usually require 10%.
+pub const DEFAULT_FEE_BUMP_PERCENT: u64 = 12;
+/// Interval at which the receipt of a pending transaction is requested.
+pub(crate) const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, PartialEq, Getters)]
+#[getset(get = "pub")]
+/// Gas and fee settings of the transactions, and how long to wait for them.
+pub struct TxConfig {
+    /// Gas limit, estimated by the node when not set.
+    gas_limit: Option<u64>,
+    /// Maximum fee per gas in wei, estimated by the node when not set.
+    max_fee_per_gas: Option<u128>,
+    /// Maximum priority fee per gas in wei, estimated by the node when not set.
+    max_priority_fee_per_gas: Option<u128>,
+    /// How long to wait for a transaction to be included.
+    receipt_timeout: Duration,
+    /// How long a transaction may stay pending before it is replaced.
+    replace_after: Duration,
+    /// Fee increase of a replacement, in percent.
+    fee_bump_percent: u64,
+    /// Directory of the pending transaction files.
+    pending_root: PathBuf,
+}
+
+impl Default for TxConfig {
+    fn default() -> Self {
+        Self::new(
+            None,
+            None,
+            None,
+            Duration::from_secs(DEFAULT_RECEIPT_TIMEOUT_SECS),
+        )
+    }
+}
+
+impl TxConfig {
+    pub fn new(
+        gas_limit: Option<u64>,
+        max_fee_per_gas: Option<u128>,
+        max_priority_fee_per_gas: Option<u128>,
+        receipt_timeout: Duration,
+    ) -> Self {
+        Self {
+            gas_limit,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            receipt_timeout,
+            replace_after: Duration::from_secs(DEFAULT_REPLACE_AFTER_SECS),
+            fee_bump_percent: DEFAULT_FEE_BUMP_PERCENT,
+            pending_root: PathBuf::from("./pending"),
+        }
+    }
+
+    /// Sets how long a transaction may stay pending, and by how much its fees are raised
+    /// when it is replaced.
+    pub fn with_replacement(mut self, replace_after: Duration, fee_bump_percent: u64) -> Self {
+        self.replace_after = replace_after;
+        self.fee_bump_percent = fee_bump_percent;
+        self
+    }
+
+    pub fn with_pending_root(mut self, pending_root: impl Into<PathBuf>) -> Self {
+        self.pending_root = pending_root.into();
+        self
+    }
+
+    /// Opens the tracker of the transactions sent by the named node role.
+    pub fn tracker(&self, name: &str, sender: Address) -> Result<TxTracker, Error> {
+        let path = self.pending_root.join(format!("{}.json", name));
+        TxTracker::open(path, sender, self)
+    }
+
+    /// Reads the config from the environment only, see [Self::from_settings].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Reads the config from the `TX_GAS_LIMIT`, `TX_MAX_FEE_PER_GAS`,
+    /// `TX_MAX_PRIORITY_FEE_PER_GAS`, `TX_RECEIPT_TIMEOUT_SECS`, `TX_REPLACE_AFTER_SECS`,
+    /// `TX_FEE_BUMP_PERCENT` and `TX_PENDING_ROOT` settings, falling back to the
+    /// defaults.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        fn number<T: FromStr>(settings: &Settings, name: &str) -> Result<Option<T>, Error>
+        where
+            T::Err: Display,
+        {
+            settings
+                .parse(name)
+                .map_err(|e| Error::Config(e.to_string()))
+        }
+        let receipt_timeout =
+            number(settings, "TX_RECEIPT_TIMEOUT_SECS")?.unwrap_or(DEFAULT_RECEIPT_TIMEOUT_SECS);
+        let replace_after =
+            number(settings, "TX_REPLACE_AFTER_SECS")?.unwrap_or(DEFAULT_REPLACE_AFTER_SECS);
+        let fee_bump_percent =
+            number(settings, "TX_FEE_BUMP_PERCENT")?.unwrap_or(DEFAULT_FEE_BUMP_PERCENT);
+        if fee_bump_percent < 10 {
+            return Err(Error::Config(
+                "TX_FEE_BUMP_PERCENT must be at least 10".to_string(),
+            ));
+        }
+        let mut config = Self::new(
+            number(settings, "TX_GAS_LIMIT")?,
+            number(settings, "TX_MAX_FEE_PER_GAS")?,
+            number(settings, "TX_MAX_PRIORITY_FEE_PER_GAS")?,
+            Duration::from_secs(receipt_timeout),
+        )
+        .with_replacement(Duration::from_secs(replace_after), fee_bump_percent);
+        if let Some(pending_root) = settings.get("TX_PENDING_ROOT") {
+            config = config.with_pending_root(pending_root);
+        }
+        Ok(config)
+    }
+}
+
+/// Turns a failed contract call into a typed revert, when the contract returned a known error.
+fn contract_error(e: alloy::contract::Error) -> Error {
+    match ManagerError::from_contract_error(&e) {
+        Some(revert) => Error::Revert(revert),
+        None => Error::Rpc(e.to_string()),
+    }
+}
+
+/// Decodes a log of the `OpenRankManager` contract into its event.
+pub fn decode_manager_event(log: &Log) -> Result<IOpenRankManagerEvents, Error> {
+    IOpenRankManagerEvents::decode_log(&log.inner, true)
+        .map(|x| x.data)
+        .map_err(|e| Error::Decode(e.to_string()))
+}
+
+/// Client of the `OpenRankManager` contract.
+///
+/// Transactions are simulated with `eth_call` before they are sent, when the sender is known,
+/// so that a call bound to revert fails with a typed [ManagerError] without spending gas.
+/// With a [TxTracker], nonces are assigned by the client and stuck transactions are replaced.
+#[derive(Clone)]
+pub struct OpenRankClient<P> {
+    contract: IOpenRankManagerInstance<(), P>,
+    sender: Option<Address>,
+    config: TxConfig,
+    tracker: Option<Arc<TxTracker>>,
+}
+
+impl<P: Provider> OpenRankClient<P> {
+    pub fn new(address: Address, provider: P, config: TxConfig) -> Self {
+        Self {
+            contract: IOpenRankManagerInstance::new(address, provider),
+            sender: None,
+            config,
+            tracker: None,
+        }
+    }
+
+    /// Sends the transactions through the tracker, from its account.
+    pub fn with_tracker(mut self, tracker: Arc<TxTracker>) -> Self {
+        self.sender = Some(tracker.sender());
+        self.tracker = Some(tracker);
+        self
+    }
+
+    /// Sets the account the transactions are sent from.
+    pub fn with_sender(mut self, sender: Address) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    pub fn address(&self) -> &Address {
+        self.contract.address()
+    }
+
+    pub fn provider(&self) -> &P {
+        self.contract.provider()
+    }
+
+    pub fn sender(&self) -> Option<Address> {
+        self.sender
+    }
+
+    pub fn config(&self) -> &TxConfig {
+        &self.config
+    }
+
+    /// Reconciles the transactions left pending by a previous run, if there is a tracker.
+    pub async fn reconcile_pending(&self) -> Result<(), Error> {
+        match &self.tracker {
+            Some(tracker) => tracker.reconcile(self.provider()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the transaction is tracked, and not included yet.
+    pub fn is_pending(&self, tx_hash: &B256) -> bool {
+        self.tracker.as_ref().is_some_and(|x| x.is_pending(tx_hash))
+    }
+
+    /// Returns the filter of every event of the contract.
+    pub fn event_filter(&self) -> Filter {
+        Filter::new().address(*self.address()).event_signature(vec![
+            MetaComputeRequestEvent::SIGNATURE_HASH,
+            MetaComputeResultEvent::SIGNATURE_HASH,
+            MetaChallengeEvent::SIGNATURE_HASH,
+        ])
+    }
+
+    /// Returns the challenge window, in seconds.
+    pub async fn challenge_window(&self) -> Result<u64, Error> {
+        let res = self
+            .contract
+            .CHALLENGE_WINDOW()
+            .call()
+            .await
+            .map_err(contract_error)?;
+        Ok(res._0)
+    }
+
+    /// Returns the compute id the next request will get.
+    pub async fn next_compute_id(&self) -> Result<U256, Error> {
+        let res = self
+            .contract
+            .idCounter()
+            .call()
+            .await
+            .map_err(contract_error)?;
+        Ok(res._0)
+    }
+
+    /// Returns `true` if the account may submit compute results.
+    pub async fn is_allowlisted_computer(&self, computer: Address) -> Result<bool, Error> {
+        let res = self
+            .contract
+            .isAllowlistedComputer(computer)
+            .call()
+            .await
+            .map_err(contract_error)?;
+        Ok(res._0)
+    }
+
+    /// Returns the compute request, if there is one with the id.
+    pub async fn meta_compute_request(
+        &self,
+        compute_id: U256,
+    ) -> Result<Option<MetaComputeRequest>, Error> {
+        let res = self
+            .contract
+            .metaComputeRequests(compute_id)
+            .call()
+            .await
+            .map_err(contract_error)?;
+        if res.id.is_zero() {
+            return Ok(None);
+        }
+        Ok(Some(MetaComputeRequest {
+            user: res.user,
+            id: res.id,
+            jobDescriptionId: res.jobDescriptionId,
+            timestamp: res.timestamp,
+        }))
+    }
+
+    /// Returns the results submitted for the request, if any.
+    pub async fn meta_compute_result(
+        &self,
+        compute_id: U256,
+    ) -> Result<Option<MetaComputeResult>, Error> {
+        let res = self
+            .contract
+            .metaComputeResults(compute_id)
+            .call()
+            .await
+            .map_err(contract_error)?;
+        if res.computeId.is_zero() {
+            return Ok(None);
+        }
+        Ok(Some(MetaComputeResult {
+            computer: res.computer,
+            computeId: res.computeId,
+            metaCommitment: res.metaCommitment,
+            resultsId: res.resultsId,
+            timestamp: res.timestamp,
+        }))
+    }
+
+    /// Returns the challenge of the request's results, if any.
+    pub async fn meta_challenge(&self, compute_id: U256) -> Result<Option<MetaChallenge>, Error> {
+        let res = self
+            .contract
+            .metaChallenges(compute_id)
+            .call()
+            .await
+            .map_err(contract_error)?;
+        if res.computeId.is_zero() {
+            return Ok(None);
+        }
+        Ok(Some(MetaChallenge {
+            challenger: res.challenger,
+            computeId: res.computeId,
+            subJobId: res.subJobId,
+            certificate: res.certificate,
+            timestamp: res.timestamp,
+            requestIndex: res.requestIndex,
+        }))
+    }
+
+    /// Sends a compute request for the job description, returning the transaction hash.
+    pub async fn send_meta_compute_request(
+        &self,
+        job_description_id: &Hash,
+    ) -> Result<B256, Error> {
+        let call = self
+            .contract
+            .submitMetaComputeRequest(FixedBytes::from_slice(job_description_id.inner()));
+        self.send("submitMetaComputeRequest", call).await
+    }
+
+    /// Sends a compute request, and waits for it to be included. Returns the compute id
+    /// assigned by the contract, along with the receipt.
+    pub async fn submit_meta_compute_request(
+        &self,
+        job_description_id: &Hash,
+    ) -> Result<(U256, TransactionReceipt), Error> {
+        let tx_hash = self.send_meta_compute_request(job_description_id).await?;
+        let receipt = self.wait_for_receipt(tx_hash).await?;
+        let compute_id = receipt
+            .inner
+            .logs()
+            .iter()
+            .filter(|x| x.address() == *self.address())
+            .find(|x| x.topic0() == Some(&MetaComputeRequestEvent::SIGNATURE_HASH))
+            .map(decode_log::<MetaComputeRequestEvent>)
+            .transpose()?
+            .ok_or(Error::Decode(
+                "No MetaComputeRequestEvent in the receipt".to_string(),
+            ))?
+            .computeId;
+        Ok((compute_id, receipt))
+    }
+
+    /// Sends the results of the request, returning the transaction hash.
+    pub async fn send_meta_compute_result(
+        &self,
+        compute_id: U256,
+        meta_commitment: &Hash,
+        results_id: &Hash,
+    ) -> Result<B256, Error> {
+        let call = self.contract.submitMetaComputeResult(
+            compute_id,
+            FixedBytes::from_slice(meta_commitment.inner()),
+            FixedBytes::from_slice(results_id.inner()),
+        );
+        self.send("submitMetaComputeResult", call).await
+    }
+
+    /// Sends a challenge of the sub-job's results, returning the transaction hash.
+    pub async fn send_meta_challenge(
+        &self,
+        compute_id: U256,
+        sub_job_id: u32,
+        certificate: Vec<u8>,
+    ) -> Result<B256, Error> {
+        let call =
+            self.contract
+                .submitMetaChallenge(compute_id, sub_job_id, Bytes::from(certificate));
+        self.send("submitMetaChallenge", call).await
+    }
+
+    /// Waits for the transaction to be included, until the configured timeout. Fails if the
+    /// transaction reverted, or if it was tracked and dropped.
+    pub async fn wait_for_receipt(&self, tx_hash: B256) -> Result<TransactionReceipt, Error> {
+        self.poll_receipt(tx_hash)
+            .await
+            .inspect_err(record_tx_failure)
+    }
+
+    async fn poll_receipt(&self, tx_hash: B256) -> Result<TransactionReceipt, Error> {
+        if let Some(tracker) = self.tracker.as_ref().filter(|x| x.is_pending(&tx_hash)) {
+            return tracker.wait(self.provider(), tx_hash).await;
+        }
+        let deadline = Instant::now() + self.config.receipt_timeout;
+        loop {
+            let receipt = self
+                .provider()
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(|e| Error::Rpc(e.to_string()))?;
+            if let Some(receipt) = receipt {
+                if !receipt.status() {
+                    return Err(Error::Reverted(tx_hash));
+                }
+                return Ok(receipt);
+            }
+            if Instant::now() + RECEIPT_POLL_INTERVAL > deadline {
+                return Err(Error::ReceiptTimeout(tx_hash));
+            }
+            sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Applies the config to the call, simulates it and sends it.
+    async fn send<D: CallDecoder + Unpin>(
+        &self,
+        name: &str,
+        call: CallBuilder<(), &P, D>,
+    ) -> Result<B256, Error> {
+        self.send_call(name, call)
+            .await
+            .inspect_err(record_tx_failure)
+    }
+
+    async fn send_call<D: CallDecoder + Unpin>(
+        &self,
+        name: &str,
+        mut call: CallBuilder<(), &P, D>,
+    ) -> Result<B256, Error> {
+        if let Some(gas_limit) = self.config.gas_limit {
+            call = call.gas(gas_limit);
+        }
+        if let Some(max_fee_per_gas) = self.config.max_fee_per_gas {
+            call = call.max_fee_per_gas(max_fee_per_gas);
+        }
+        if let Some(max_priority_fee_per_gas) = self.config.max_priority_fee_per_gas {
+            call = call.max_priority_fee_per_gas(max_priority_fee_per_gas);
+        }
+        if let Some(sender) = self.sender {
+            call = call.from(sender);
+            call.call().await.map_err(contract_error)?;
+            debug!("Simulated '{}'", name);
+        }
+        let tx_hash = match &self.tracker {
+            Some(tracker) => {
+                let request = call.into_transaction_request();
+                tracker.send(self.provider(), request).await?
+            }
+            None => *call.send().await.map_err(contract_error)?.tx_hash(),
+        };
+        info!("Sent '{}': Tx Hash({:#})", name, tx_hash);
+        Ok(tx_hash)
+    }
+}
+
+/// Counts the failed transaction, by the kind of error.
+fn record_tx_failure(e: &Error) {
+    let reason = match e {
+        Error::Revert(_) => "revert",
+        Error::Rpc(_) => "rpc",
+        Error::Reverted(_) => "reverted",
+        Error::Dropped(_) => "dropped",
+        Error::ReceiptTimeout(_) => "timeout",
+        _ => "other",
+    };
+    metrics::TX_FAILURES.with_label_values(&[reason]).inc();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::client::{OpenRankClient, TxConfig};
+    use crate::chain::contract::IOpenRankManager::ComputeResultAlreadySubmitted;
+    use crate::chain::{Error, ManagerError};
+    use crate::merkle::Hash;
+    use alloy::primitives::{Address, Bytes, B256, U256};
+    use alloy::providers::ProviderBuilder;
+    use alloy::rpc::json_rpc::ErrorPayload;
+    use alloy::rpc::types::TransactionReceipt;
+    use alloy::sol_types::{SolError, SolValue};
+    use alloy::transports::mock::Asserter;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn should_decode_getters_and_reverts() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new().on_mocked_client(asserter.clone());
+        let computer = Address::with_last_byte(7);
+        let client = OpenRankClient::new(Address::with_last_byte(1), provider, TxConfig::default())
+            .with_sender(computer);
+
+        asserter.push_success(&Bytes::from(true.abi_encode()));
+        assert!(client.is_allowlisted_computer(computer).await.unwrap());
+
+        let empty = (
+            Address::ZERO,
+            U256::ZERO,
+            B256::ZERO,
+            B256::ZERO,
+            U256::ZERO,
+        );
+        asserter.push_success(&Bytes::from(empty.abi_encode()));
+        assert_eq!(
+            client.meta_compute_result(U256::from(3)).await.unwrap(),
+            None
+        );
+
+        let result = (
+            computer,
+            U256::from(3),
+            B256::repeat_byte(1),
+            B256::repeat_byte(2),
+            U256::from(9),
+        );
+        asserter.push_success(&Bytes::from(result.abi_encode()));
+        let result = client
+            .meta_compute_result(U256::from(3))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.computer, computer);
+        assert_eq!(result.resultsId, B256::repeat_byte(2));
+
+        // The simulation reverts, so nothing is sent.
+        let data = Bytes::from(ComputeResultAlreadySubmitted {}.abi_encode());
+        asserter.push_failure(ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(serde_json::value::to_raw_value(&data).unwrap()),
+        });
+        let res = client
+            .send_meta_compute_result(U256::from(3), &Hash::default(), &Hash::default())
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::Revert(ManagerError::ComputeResultAlreadySubmitted))
+        ));
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_time_out_waiting_for_the_receipt() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new().on_mocked_client(asserter.clone());
+        let config = TxConfig::new(None, None, None, Duration::from_secs(5));
+        let client = OpenRankClient::new(Address::ZERO, provider, config);
+        for _ in 0..3 {
+            asserter.push_success(&Option::<TransactionReceipt>::None);
+        }
+        let res = client.wait_for_receipt(B256::repeat_byte(5)).await;
+        assert!(matches!(res, Err(Error::ReceiptTimeout(x)) if x == B256::repeat_byte(5)));
+        assert!(asserter.read_q().is_empty());
+    }
+}
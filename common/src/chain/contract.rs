@@ -0,0 +1,57 @@
+//! Bindings of the `IOpenRankManager` interface, along with the getters of the public storage of
+//! `OpenRankManager`.
+
+use alloy::sol;
+
+sol! {
+    #[sol(rpc)]
+    #[derive(Debug, PartialEq, Eq)]
+    interface IOpenRankManager {
+        error ComputeRequestNotFound();
+        error ComputeResultAlreadySubmitted();
+        error ComputeResultNotFound();
+        error ChallengeNotFound();
+        error ChallengePeriodExpired();
+        error CallerNotWhitelisted();
+        error InvalidReservationForImageId();
+
+        struct MetaComputeRequest {
+            address user;
+            uint256 id;
+            bytes32 jobDescriptionId;
+            uint256 timestamp;
+        }
+
+        struct MetaComputeResult {
+            address computer;
+            uint256 computeId;
+            bytes32 metaCommitment;
+            bytes32 resultsId;
+            uint256 timestamp;
+        }
+
+        struct MetaChallenge {
+            address challenger;
+            uint256 computeId;
+            uint32 subJobId;
+            bytes certificate;
+            uint256 timestamp;
+            uint256 requestIndex;
+        }
+
+        event MetaComputeRequestEvent(uint256 indexed computeId, bytes32 jobDescriptionId);
+        event MetaComputeResultEvent(uint256 indexed computeId, bytes32 commitment, bytes32 resultsId);
+        event MetaChallengeEvent(uint256 indexed computeId, uint32 subJobId, bytes certificate);
+
+        function submitMetaComputeRequest(bytes32 jobDescriptionId) external returns (uint256 computeId);
+        function submitMetaComputeResult(uint256 computeId, bytes32 metaCommitment, bytes32 resultsId) external returns (bool);
+        function submitMetaChallenge(uint256 computeId, uint32 subJobId, bytes memory certificate) external returns (bool);
+        function isAllowlistedComputer(address computer) external view returns (bool);
+
+        function CHALLENGE_WINDOW() external view returns (uint64);
+        function idCounter() external view returns (uint256);
+        function metaComputeRequests(uint256 computeId) external view returns (address user, uint256 id, bytes32 jobDescriptionId, uint256 timestamp);
+        function metaComputeResults(uint256 computeId) external view returns (address computer, uint256 computeId, bytes32 metaCommitment, bytes32 resultsId, uint256 timestamp);
+        function metaChallenges(uint256 computeId) external view returns (address challenger, uint256 computeId, uint32 subJobId, bytes memory certificate, uint256 timestamp, uint256 requestIndex);
+    }
+}
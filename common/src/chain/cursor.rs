@@ -0,0 +1,131 @@
+use crate::chain::Error;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Position of the last processed log.
+pub struct BlockCursor {
+    block: u64,
+    /// Index of the last processed log in the block, or `None` if the whole block is processed.
+    log_index: Option<u64>,
+}
+
+impl BlockCursor {
+    /// Cursor after every log of the block.
+    pub fn end_of_block(block: u64) -> Self {
+        Self {
+            block,
+            log_index: None,
+        }
+    }
+
+    /// Cursor at the log.
+    pub fn at_log(block: u64, log_index: u64) -> Self {
+        Self {
+            block,
+            log_index: Some(log_index),
+        }
+    }
+
+    /// First block that can still have logs to process.
+    pub fn next_block(&self) -> u64 {
+        match self.log_index {
+            Some(_) => self.block,
+            None => self.block + 1,
+        }
+    }
+
+    /// Returns `true` if the log at the position was already processed.
+    pub fn covers(&self, block: u64, log_index: u64) -> bool {
+        (block, log_index) <= self.position()
+    }
+
+    /// Returns the position of the last processed log, for comparisons.
+    pub fn position(&self) -> (u64, u64) {
+        (self.block, self.log_index.unwrap_or(u64::MAX))
+    }
+}
+
+/// Persists a [BlockCursor] in a JSON file.
+pub struct CursorStore {
+    path: PathBuf,
+}
+
+impl CursorStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns the name of the cursor, the stem of its file.
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Loads the cursor, if one was saved.
+    pub fn load(&self) -> Result<Option<BlockCursor>, Error> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(Error::Serde)
+    }
+
+    /// Saves the cursor, replacing the previous one atomically. The file and its directory are
+    /// synced, so that the cursor survives a crash of the machine.
+    pub fn save(&self, cursor: &BlockCursor) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(Error::Io)?;
+        }
+        let data = serde_json::to_vec(cursor).map_err(Error::Serde)?;
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp).map_err(Error::Io)?;
+        file.write_all(&data).map_err(Error::Io)?;
+        file.sync_all().map_err(Error::Io)?;
+        fs::rename(&tmp, &self.path).map_err(Error::Io)?;
+        // Directories can't be opened as files on every platform.
+        #[cfg(unix)]
+        if let Some(parent) = self.path.parent() {
+            let dir = if parent.as_os_str().is_empty() {
+                File::open(".")
+            } else {
+                File::open(parent)
+            };
+            dir.and_then(|x| x.sync_all()).map_err(Error::Io)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::cursor::{BlockCursor, CursorStore};
+
+    #[test]
+    fn should_persist_cursor() {
+        let path = std::env::temp_dir()
+            .join(format!("openrank-cursor-{}", std::process::id()))
+            .join("computer.json");
+        let store = CursorStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(&BlockCursor::at_log(10, 2)).unwrap();
+        let cursor = store.load().unwrap().unwrap();
+        assert_eq!(cursor, BlockCursor::at_log(10, 2));
+        assert_eq!(cursor.next_block(), 10);
+        assert!(cursor.covers(10, 2) && cursor.covers(9, 7));
+        assert!(!cursor.covers(10, 3));
+        assert!(BlockCursor::end_of_block(10).covers(10, 3));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,883 @@
+//! Ingestion of contract logs that survives restarts.
+//!
+//! On startup, the [EventIngestor] subscribes to new logs, then backfills the logs emitted
+//! since its persisted cursor with `eth_getLogs`, in bounded block ranges. Once it has caught
+//! up with the chain head, it switches to the subscription. Logs seen twice, e.g. both in the
+//! backfill and in the subscription, are dropped by their transaction hash and log index.
+//!
+//! Logs are held back until their block has the configured number of confirmations. A log
+//! removed by a reorg is dropped if it was still held back, and returned with `removed` set
+//! otherwise, so that the caller can undo its effects. The cursor is then moved back before
+//! its block, so that the logs of the new chain are read again.
+//!
+//! When the subscription closes, the ingestor reconnects through its [LogSource], and backfills
+//! the logs emitted meanwhile from the cursor.
+
+use crate::chain::{BlockCursor, CursorStore, Error};
+use crate::config::Settings;
+use crate::metrics;
+use alloy::primitives::B256;
+use alloy::providers::{Provider, RootProvider, WsConnect};
+use alloy::rpc::client::ClientBuilder;
+use alloy::rpc::types::{Filter, Log};
+use alloy::sol_types::SolEvent;
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use getset::Getters;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+/// Root directory of the cursor files used when none is configured.
+pub const DEFAULT_CURSOR_ROOT: &str = "./cursor";
+/// Largest block range of a single `eth_getLogs` request used when none is configured.
+pub const DEFAULT_MAX_BLOCK_RANGE: u64 = 1000;
+/// Number of blocks mined on top of a log's block before it is returned, used when none is
+/// configured.
+pub const DEFAULT_CONFIRMATIONS: u64 = 3;
+/// Interval at which the chain head is read while no logs arrive.
+const HEAD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Stream of live logs.
+pub type LogStream = Pin<Box<dyn Stream<Item = Log> + Send>>;
+
+#[async_trait]
+/// Chain node the logs are read from.
+pub trait LogSource: Send + Sync {
+    /// Returns the number of the latest block.
+    async fn block_number(&self) -> Result<u64, Error>;
+    /// Returns the logs matching the filter, which has a bounded block range.
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, Error>;
+    /// Subscribes to the logs matching the filter in new blocks.
+    async fn subscribe_logs(&self, filter: &Filter) -> Result<LogStream, Error>;
+    /// Prepares a new subscription after the previous one closed. By default, the connection
+    /// is reused.
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// [LogSource] backed by a provider. Subscriptions need a WebSocket or IPC transport.
+pub struct ProviderLogSource<P> {
+    provider: P,
+}
+
+impl<P> ProviderLogSource<P> {
+    pub fn new(provider: P) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> LogSource for ProviderLogSource<P> {
+    async fn block_number(&self) -> Result<u64, Error> {
+        self.provider
+            .get_block_number()
+            .await
+            .map_err(|e| Error::Rpc(e.to_string()))
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, Error> {
+        self.provider
+            .get_logs(filter)
+            .await
+            .map_err(|e| Error::Rpc(e.to_string()))
+    }
+
+    async fn subscribe_logs(&self, filter: &Filter) -> Result<LogStream, Error> {
+        let subscription = self
+            .provider
+            .subscribe_logs(filter)
+            .await
+            .map_err(|e| Error::Rpc(e.to_string()))?;
+        Ok(Box::pin(subscription.into_stream()))
+    }
+}
+
+/// [LogSource] over a WebSocket connection, which is opened again when the subscription
+/// closes.
+pub struct WsLogSource {
+    url: String,
+    inner: ProviderLogSource<RootProvider>,
+}
+
+impl WsLogSource {
+    /// Opens the connection.
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        let inner = Self::open(url).await?;
+        Ok(Self {
+            url: url.to_string(),
+            inner,
+        })
+    }
+
+    async fn open(url: &str) -> Result<ProviderLogSource<RootProvider>, Error> {
+        let client = ClientBuilder::default()
+            .ws(WsConnect::new(url))
+            .await
+            .map_err(|e| Error::Rpc(e.to_string()))?;
+        Ok(ProviderLogSource::new(RootProvider::new(client)))
+    }
+}
+
+#[async_trait]
+impl LogSource for WsLogSource {
+    async fn block_number(&self) -> Result<u64, Error> {
+        self.inner.block_number().await
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, Error> {
+        self.inner.get_logs(filter).await
+    }
+
+    async fn subscribe_logs(&self, filter: &Filter) -> Result<LogStream, Error> {
+        self.inner.subscribe_logs(filter).await
+    }
+
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        self.inner = Self::open(&self.url).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+/// Connection state and progress of an [EventIngestor], shared with the health checks.
+pub struct IngestStatus {
+    connected: AtomicBool,
+    /// Block up to which the logs were read: the cursor while backfilling, the chain head
+    /// after.
+    synced_block: AtomicU64,
+    /// When the subscription closed, or the status was created, while it is not open.
+    disconnected_at: Mutex<Option<Instant>>,
+}
+
+impl Default for IngestStatus {
+    fn default() -> Self {
+        Self {
+            connected: AtomicBool::new(false),
+            synced_block: AtomicU64::new(0),
+            disconnected_at: Mutex::new(Some(Instant::now())),
+        }
+    }
+}
+
+impl IngestStatus {
+    /// Returns `true` while the subscription is open.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Returns the block up to which the logs were read.
+    pub fn synced_block(&self) -> u64 {
+        self.synced_block.load(Ordering::Relaxed)
+    }
+
+    /// Returns how long the subscription has been closed, or not opened yet.
+    pub fn disconnected_for(&self) -> Option<Duration> {
+        self.disconnected_at.lock().unwrap().map(|x| x.elapsed())
+    }
+
+    fn set_connected(&self, connected: bool) {
+        let mut disconnected_at = self.disconnected_at.lock().unwrap();
+        if connected {
+            *disconnected_at = None;
+        } else if disconnected_at.is_none() {
+            *disconnected_at = Some(Instant::now());
+        }
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Getters)]
+#[getset(get = "pub")]
+/// Configuration of the [EventIngestor].
+pub struct IngestConfig {
+    /// Directory of the cursor files, one per node role.
+    cursor_root: PathBuf,
+    /// First block to process when there is no cursor yet. Defaults to the chain head.
+    start_block: Option<u64>,
+    /// Largest block range of a single `eth_getLogs` request.
+    max_block_range: u64,
+    /// Number of blocks mined on top of a log's block before it is returned.
+    confirmations: u64,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_CURSOR_ROOT.into(),
+            None,
+            DEFAULT_MAX_BLOCK_RANGE,
+            DEFAULT_CONFIRMATIONS,
+        )
+    }
+}
+
+impl IngestConfig {
+    pub fn new(
+        cursor_root: PathBuf,
+        start_block: Option<u64>,
+        max_block_range: u64,
+        confirmations: u64,
+    ) -> Self {
+        Self {
+            cursor_root,
+            start_block,
+            max_block_range: max_block_range.max(1),
+            confirmations,
+        }
+    }
+
+    /// Reads the config from the environment only, see [Self::from_settings].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Reads the config from the `EVENTS_CURSOR_ROOT`, `EVENTS_START_BLOCK`,
+    /// `EVENTS_MAX_BLOCK_RANGE` and `EVENTS_CONFIRMATIONS` settings, falling back
+    /// to the defaults.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        let number = |name: &str| {
+            settings
+                .parse::<u64>(name)
+                .map_err(|e| Error::Config(e.to_string()))
+        };
+        let cursor_root = settings
+            .get("EVENTS_CURSOR_ROOT")
+            .unwrap_or(DEFAULT_CURSOR_ROOT.to_string());
+        let start_block = number("EVENTS_START_BLOCK")?;
+        let max_block_range = number("EVENTS_MAX_BLOCK_RANGE")?.unwrap_or(DEFAULT_MAX_BLOCK_RANGE);
+        let confirmations = number("EVENTS_CONFIRMATIONS")?.unwrap_or(DEFAULT_CONFIRMATIONS);
+        Ok(Self::new(
+            cursor_root.into(),
+            start_block,
+            max_block_range,
+            confirmations,
+        ))
+    }
+
+    /// Returns the store of the cursor with the given name.
+    pub fn cursor_store(&self, name: &str) -> CursorStore {
+        CursorStore::new(self.cursor_root.join(format!("{}.json", name)))
+    }
+}
+
+/// Decodes the event of the log.
+pub fn decode_log<E: SolEvent>(log: &Log) -> Result<E, Error> {
+    log.log_decode::<E>()
+        .map(|x| x.inner.data)
+        .map_err(|e| Error::Decode(e.to_string()))
+}
+
+/// Returns the block number and log index of the log.
+fn position(log: &Log) -> Option<(u64, u64)> {
+    Some((log.block_number?, log.log_index?))
+}
+
+/// Returns the key identifying the log within its block.
+fn key(log: &Log) -> (B256, u64) {
+    (
+        log.transaction_hash.unwrap_or_default(),
+        log.log_index.unwrap_or_default(),
+    )
+}
+
+/// Item of the queue of logs ready to be returned.
+enum Backfilled {
+    Log(Log),
+    /// Every confirmed log up to the end of the block was queued.
+    EndOfBlock(u64),
+}
+
+/// Reads the logs matching a filter in chain order, resuming where the previous run stopped.
+///
+/// A log returned by [`EventIngestor::next`] counts as processed once `next` is called again.
+/// The cursor is only persisted once a block is fully processed, so after a restart the logs
+/// of a partly processed block are delivered again, including the ones already processed.
+/// Reorgs deeper than the confirmations that happen while the node is stopped are not
+/// detected.
+pub struct EventIngestor<S> {
+    source: S,
+    filter: Filter,
+    config: IngestConfig,
+    store: CursorStore,
+    /// Name of the cursor, used as the label of its metrics.
+    name: String,
+    cursor: BlockCursor,
+    /// Cursor persisted last, always at the end of a block.
+    saved: BlockCursor,
+    /// Furthest position of the cursor, which reorgs don't move back.
+    processed: BlockCursor,
+    /// Next block to backfill, until the backfill caught up with the chain head.
+    backfill_from: Option<u64>,
+    /// Last block known to be part of the chain.
+    head: u64,
+    backlog: VecDeque<Backfilled>,
+    /// Logs of the blocks without enough confirmations yet, by block.
+    unconfirmed: BTreeMap<u64, Vec<Log>>,
+    live: LogStream,
+    /// Keys of the logs returned in this session, by block.
+    seen: BTreeMap<u64, HashSet<(B256, u64)>>,
+    /// Position of the log returned last, not processed yet.
+    pending: Option<(u64, u64)>,
+    status: Arc<IngestStatus>,
+}
+
+impl<S: LogSource> EventIngestor<S> {
+    /// Subscribes to new logs, and plans the backfill from the persisted cursor.
+    pub async fn start(
+        source: S,
+        filter: Filter,
+        config: IngestConfig,
+        store: CursorStore,
+    ) -> Result<Self, Error> {
+        // Subscribing before reading the head, so that no block falls between the two.
+        let live = source.subscribe_logs(&filter).await?;
+        let head = source.block_number().await?;
+        let cursor = match store.load()? {
+            Some(cursor) => cursor,
+            None => {
+                let start = config.start_block.unwrap_or(head + 1);
+                let cursor = BlockCursor::end_of_block(start.saturating_sub(1));
+                store.save(&cursor)?;
+                cursor
+            }
+        };
+        let name = store.name();
+        metrics::LAST_BLOCK
+            .with_label_values(&[&name])
+            .set(*cursor.block() as i64);
+        metrics::CHAIN_HEAD
+            .with_label_values(&[&name])
+            .set(head as i64);
+        let from = cursor.next_block();
+        if from <= head {
+            info!("Backfilling logs: FromBlock({}), ToBlock({})", from, head);
+        }
+        let ingestor = Self {
+            source,
+            filter,
+            config,
+            store,
+            name,
+            cursor,
+            saved: cursor,
+            processed: cursor,
+            backfill_from: Some(from),
+            head,
+            backlog: VecDeque::new(),
+            unconfirmed: BTreeMap::new(),
+            live,
+            seen: BTreeMap::new(),
+            pending: None,
+            status: Arc::default(),
+        };
+        ingestor.status.set_connected(true);
+        ingestor.update_status();
+        Ok(ingestor)
+    }
+
+    /// Reports the progress to the given status, e.g. one shared with the health checks.
+    pub fn with_status(mut self, status: Arc<IngestStatus>) -> Self {
+        self.status = status;
+        self.status.set_connected(true);
+        self.update_status();
+        self
+    }
+
+    /// Returns the connection state and progress.
+    pub fn status(&self) -> &Arc<IngestStatus> {
+        &self.status
+    }
+
+    /// Returns the position of the last processed log.
+    pub fn cursor(&self) -> &BlockCursor {
+        &self.cursor
+    }
+
+    /// Returns `true` while logs of past blocks are being read.
+    pub fn is_backfilling(&self) -> bool {
+        self.backfill_from.is_some() || !self.backlog.is_empty()
+    }
+
+    /// Returns the next confirmed log, or a processed log removed by a reorg, marking the log
+    /// returned by the previous call as processed.
+    ///
+    /// After an error, the call can be retried without losing logs. A failed request to the
+    /// source is taken as a lost connection, so the next call reconnects first.
+    pub async fn next(&mut self) -> Result<Log, Error> {
+        let res = self.read_next().await;
+        if let Err(e @ Error::Rpc(_)) = &res {
+            warn!("Log source request failed, reconnecting: {}", e);
+            self.status.set_connected(false);
+        }
+        res
+    }
+
+    async fn read_next(&mut self) -> Result<Log, Error> {
+        if let Some((block, log_index)) = self.pending.take() {
+            self.advance(BlockCursor::at_log(block, log_index))?;
+        }
+        if !self.status.is_connected() {
+            self.reconnect().await?;
+        }
+        loop {
+            match self.backlog.pop_front() {
+                Some(Backfilled::Log(log)) => {
+                    if !log.removed {
+                        self.pending = position(&log);
+                    }
+                    return Ok(log);
+                }
+                Some(Backfilled::EndOfBlock(block)) => {
+                    self.advance(BlockCursor::end_of_block(block))?;
+                    continue;
+                }
+                None => {}
+            }
+            if let Some(from) = self.backfill_from {
+                self.backfill(from).await?;
+                continue;
+            }
+            // New blocks might have no matching logs, so the head is polled as well.
+            let log = match timeout(HEAD_POLL_INTERVAL, self.live.next()).await {
+                Ok(log) => log,
+                Err(_) => {
+                    let head = self.source.block_number().await?;
+                    self.update_head(head);
+                    continue;
+                }
+            };
+            let Some(log) = log else {
+                warn!("Log subscription closed, reconnecting");
+                self.status.set_connected(false);
+                self.reconnect().await?;
+                continue;
+            };
+            self.receive(log)?;
+        }
+    }
+
+    /// Queues the logs of the next block range. The head is read again once it is reached,
+    /// since the subscription might not have kept the logs of a long backfill.
+    async fn backfill(&mut self, from: u64) -> Result<(), Error> {
+        if from > self.head {
+            let head = self.source.block_number().await?;
+            if head <= self.head {
+                info!("Backfill completed: Cursor({:?})", self.cursor);
+                self.backfill_from = None;
+                self.update_status();
+                return Ok(());
+            }
+            self.update_head(head);
+        }
+        let to = self.head.min(from + self.config.max_block_range - 1);
+        let filter = self.filter.clone().from_block(from).to_block(to);
+        let mut logs = self.source.get_logs(&filter).await?;
+        debug!(
+            "Backfilled logs: FromBlock({}), ToBlock({}), Logs({})",
+            from,
+            to,
+            logs.len()
+        );
+        logs.sort_by_key(|x| (x.block_number, x.log_index));
+        for log in logs {
+            self.receive(log)?;
+        }
+        self.release();
+        let confirmed = self.head.saturating_sub(self.config.confirmations);
+        self.backlog
+            .push_back(Backfilled::EndOfBlock(to.min(confirmed)));
+        self.backfill_from = Some(to + 1);
+        Ok(())
+    }
+
+    /// Holds the log back until it is confirmed, unless it was processed already. Removed
+    /// logs are handed to [`EventIngestor::remove`].
+    fn receive(&mut self, log: Log) -> Result<(), Error> {
+        let Some((block, log_index)) = position(&log) else {
+            warn!("Dropping log without a position: {:?}", log);
+            return Ok(());
+        };
+        if log.removed {
+            return self.remove(log, block, log_index);
+        }
+        if self.cursor.covers(block, log_index) {
+            return Ok(());
+        }
+        if !self.seen.entry(block).or_default().insert(key(&log)) {
+            debug!(
+                "Dropping duplicate log: Block({}), LogIndex({})",
+                block, log_index
+            );
+            return Ok(());
+        }
+        self.unconfirmed.entry(block).or_default().push(log);
+        self.update_head(block);
+        Ok(())
+    }
+
+    /// Drops the removed log if it wasn't returned yet. Otherwise, queues it for the caller
+    /// and moves the cursor back before its block.
+    fn remove(&mut self, log: Log, block: u64, log_index: u64) -> Result<(), Error> {
+        let removed = key(&log);
+        let is_removed = |x: &Log| x.block_number == Some(block) && key(x) == removed;
+        // The log of the new chain might have the same key, e.g. if the transaction was
+        // included again.
+        if let Some(keys) = self.seen.get_mut(&block) {
+            keys.remove(&removed);
+        }
+        let mut dropped = false;
+        if let Some(logs) = self.unconfirmed.get_mut(&block) {
+            let len = logs.len();
+            logs.retain(|x| !is_removed(x));
+            dropped = logs.len() < len;
+            if logs.is_empty() {
+                self.unconfirmed.remove(&block);
+            }
+        }
+        if !dropped {
+            let len = self.backlog.len();
+            self.backlog
+                .retain(|x| !matches!(x, Backfilled::Log(x) if !x.removed && is_removed(x)));
+            dropped = self.backlog.len() < len;
+        }
+        if dropped {
+            debug!(
+                "Dropping removed log: Block({}), LogIndex({})",
+                block, log_index
+            );
+            return Ok(());
+        }
+        if !self.processed.covers(block, log_index) {
+            return Ok(());
+        }
+        warn!(
+            "Processed log removed by a reorg: Block({}), LogIndex({})",
+            block, log_index
+        );
+        if self.cursor.covers(block, log_index) {
+            self.rewind(block)?;
+        }
+        self.backlog.push_back(Backfilled::Log(log));
+        Ok(())
+    }
+
+    /// Raises the known chain head, and queues the logs it confirms.
+    fn update_head(&mut self, head: u64) {
+        self.head = self.head.max(head);
+        metrics::CHAIN_HEAD
+            .with_label_values(&[&self.name])
+            .set(self.head as i64);
+        self.update_status();
+        self.release();
+    }
+
+    fn update_status(&self) {
+        let synced_block = match self.backfill_from {
+            Some(_) => *self.cursor.block(),
+            None => self.head,
+        };
+        self.status
+            .synced_block
+            .store(synced_block, Ordering::Relaxed);
+    }
+
+    /// Subscribes again through the source. The logs held back for confirmations are dropped,
+    /// and read again by a backfill from the cursor along with the logs missed meanwhile.
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        self.source.reconnect().await?;
+        self.live = self.source.subscribe_logs(&self.filter).await?;
+        let head = self.source.block_number().await?;
+        for (block, logs) in std::mem::take(&mut self.unconfirmed) {
+            if let Some(keys) = self.seen.get_mut(&block) {
+                for log in &logs {
+                    keys.remove(&key(log));
+                }
+            }
+        }
+        let from = self.cursor.next_block();
+        info!(
+            "Reconnected, backfilling logs: FromBlock({}), ToBlock({})",
+            from, head
+        );
+        self.backfill_from = Some(from);
+        self.head = self.head.max(head);
+        self.status.set_connected(true);
+        self.update_status();
+        Ok(())
+    }
+
+    /// Queues the logs that have enough confirmations, in chain order.
+    fn release(&mut self) {
+        let confirmed = self.head.saturating_sub(self.config.confirmations);
+        let unconfirmed = self.unconfirmed.split_off(&(confirmed + 1));
+        let logs = std::mem::replace(&mut self.unconfirmed, unconfirmed);
+        self.backlog
+            .extend(logs.into_values().flatten().map(Backfilled::Log));
+    }
+
+    /// Moves the cursor forward, and saves it at block boundaries.
+    fn advance(&mut self, cursor: BlockCursor) -> Result<(), Error> {
+        if cursor.position() <= self.cursor.position() {
+            return Ok(());
+        }
+        // A log of a block means that the previous blocks are fully processed.
+        let completed = match cursor.log_index() {
+            None => Some(cursor),
+            Some(_) => cursor.block().checked_sub(1).map(BlockCursor::end_of_block),
+        };
+        if let Some(completed) = completed {
+            if completed.position() > self.saved.position() {
+                self.store.save(&completed)?;
+                self.saved = completed;
+            }
+        }
+        self.cursor = cursor;
+        metrics::LAST_BLOCK
+            .with_label_values(&[&self.name])
+            .set(*cursor.block() as i64);
+        self.update_status();
+        if cursor.position() > self.processed.position() {
+            self.processed = cursor;
+        }
+        // Older logs are dropped by the cursor already.
+        self.seen = self.seen.split_off(cursor.block());
+        Ok(())
+    }
+
+    /// Moves the cursor back before the reorged block and saves it, so that the logs of the
+    /// new chain are not dropped.
+    fn rewind(&mut self, block: u64) -> Result<(), Error> {
+        let cursor = BlockCursor::end_of_block(block.saturating_sub(1));
+        info!("Rewinding the cursor: Cursor({:?})", cursor);
+        self.store.save(&cursor)?;
+        self.saved = cursor;
+        self.cursor = cursor;
+        metrics::LAST_BLOCK
+            .with_label_values(&[&self.name])
+            .set(*cursor.block() as i64);
+        self.update_status();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::events::{EventIngestor, IngestConfig};
+    use crate::chain::mock::MockChain;
+    use crate::chain::{BlockCursor, Error};
+    use alloy::rpc::types::{Filter, Log};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    fn config(name: &str, start_block: Option<u64>) -> IngestConfig {
+        let root =
+            std::env::temp_dir().join(format!("openrank-events-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        IngestConfig::new(root, start_block, 2, 0)
+    }
+
+    fn positions(logs: &[Log]) -> Vec<(u64, u64)> {
+        logs.iter()
+            .map(|x| (x.block_number.unwrap(), x.log_index.unwrap()))
+            .collect()
+    }
+
+    async fn take(ingestor: &mut EventIngestor<MockChain>, n: usize) -> Result<Vec<Log>, Error> {
+        let mut logs = Vec::new();
+        for _ in 0..n {
+            logs.push(ingestor.next().await?);
+        }
+        Ok(logs)
+    }
+
+    fn cleanup(config: &IngestConfig) {
+        let root: &PathBuf = config.cursor_root();
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_backfill_then_follow_the_subscription() {
+        let chain = MockChain::new();
+        chain.mine(2);
+        chain.mine(0);
+        chain.mine(1);
+        chain.mine(0);
+        chain.mine(1);
+        let config = config("backfill", Some(1));
+        let store = config.cursor_store("computer");
+        let mut ingestor =
+            EventIngestor::start(chain.clone(), Filter::new(), config.clone(), store)
+                .await
+                .unwrap();
+
+        // Mined during the backfill, so delivered both by the backfill and the subscription.
+        chain.mine(1);
+        let logs = take(&mut ingestor, 5).await.unwrap();
+        assert_eq!(
+            positions(&logs),
+            vec![(1, 0), (1, 1), (3, 0), (5, 0), (6, 0)]
+        );
+        assert_eq!(
+            chain.get_logs_ranges(),
+            vec![(1, 2), (3, 4), (5, 5), (6, 6)]
+        );
+
+        chain.mine(2);
+        let logs = take(&mut ingestor, 2).await.unwrap();
+        assert_eq!(positions(&logs), vec![(7, 0), (7, 1)]);
+        assert_eq!(ingestor.cursor(), &BlockCursor::at_log(7, 0));
+        cleanup(&config);
+    }
+
+    #[tokio::test]
+    async fn should_resume_from_the_cursor() {
+        let chain = MockChain::new();
+        chain.mine(2);
+        chain.mine(2);
+        let config = config("resume", Some(1));
+        let mut ingestor = EventIngestor::start(
+            chain.clone(),
+            Filter::new(),
+            config.clone(),
+            config.cursor_store("challenger"),
+        )
+        .await
+        .unwrap();
+        let logs = take(&mut ingestor, 4).await.unwrap();
+        assert_eq!(positions(&logs), vec![(1, 0), (1, 1), (2, 0), (2, 1)]);
+        assert_eq!(ingestor.cursor(), &BlockCursor::at_log(2, 0));
+        drop(ingestor);
+
+        // Block 2 was not fully processed, so all its logs are delivered again.
+        chain.mine(1);
+        let mut ingestor = EventIngestor::start(
+            chain.clone(),
+            Filter::new(),
+            config.clone(),
+            config.cursor_store("challenger"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ingestor.cursor(), &BlockCursor::end_of_block(1));
+        let logs = take(&mut ingestor, 3).await.unwrap();
+        assert_eq!(positions(&logs), vec![(2, 0), (2, 1), (3, 0)]);
+
+        chain.close_subscriptions();
+        let res = ingestor.next().await;
+        assert!(matches!(res, Err(Error::SubscriptionClosed)));
+        cleanup(&config);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_wait_for_confirmations() {
+        let chain = MockChain::new();
+        let root = config("confirmations", None).cursor_root().clone();
+        let config = IngestConfig::new(root, None, 2, 2);
+        let mut ingestor = EventIngestor::start(
+            chain.clone(),
+            Filter::new(),
+            config.clone(),
+            config.cursor_store("computer"),
+        )
+        .await
+        .unwrap();
+        chain.mine(1);
+        chain.mine(1);
+        chain.mine(1);
+        let logs = take(&mut ingestor, 1).await.unwrap();
+        assert_eq!(positions(&logs), vec![(1, 0)]);
+
+        // Blocks without logs are seen by polling the head.
+        let miner = chain.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_secs(5)).await;
+            miner.mine(0);
+            miner.mine(0);
+        });
+        let logs = take(&mut ingestor, 2).await.unwrap();
+        assert_eq!(positions(&logs), vec![(2, 0), (3, 0)]);
+
+        // A log removed before it is confirmed is never returned.
+        chain.mine(2);
+        chain.reorg(1);
+        chain.mine(1);
+        chain.mine(0);
+        chain.mine(0);
+        let logs = take(&mut ingestor, 1).await.unwrap();
+        assert_eq!(positions(&logs), vec![(6, 0)]);
+        assert!(!logs[0].removed);
+        assert_eq!(ingestor.cursor(), &BlockCursor::at_log(3, 0));
+        cleanup(&config);
+    }
+
+    #[tokio::test]
+    async fn should_return_removed_logs() {
+        let chain = MockChain::new();
+        let config = config("reorg", None);
+        let mut ingestor = EventIngestor::start(
+            chain.clone(),
+            Filter::new(),
+            config.clone(),
+            config.cursor_store("challenger"),
+        )
+        .await
+        .unwrap();
+        chain.mine(1);
+        chain.mine(2);
+        chain.mine(1);
+        let logs = take(&mut ingestor, 4).await.unwrap();
+        assert_eq!(positions(&logs), vec![(1, 0), (2, 0), (2, 1), (3, 0)]);
+
+        // The new block 2 has the same first log, which must not be dropped as a duplicate.
+        chain.reorg(2);
+        chain.mine(1);
+        let logs = take(&mut ingestor, 4).await.unwrap();
+        assert_eq!(positions(&logs), vec![(2, 0), (2, 1), (3, 0), (2, 0)]);
+        let removed: Vec<bool> = logs.iter().map(|x| x.removed).collect();
+        assert_eq!(removed, vec![true, true, true, false]);
+        assert_eq!(ingestor.cursor(), &BlockCursor::end_of_block(1));
+        cleanup(&config);
+    }
+
+    #[tokio::test]
+    async fn should_reconnect_and_read_the_missed_logs() {
+        let chain = MockChain::new();
+        chain.allow_reconnect();
+        let config = config("reconnect", None);
+        let mut ingestor = EventIngestor::start(
+            chain.clone(),
+            Filter::new(),
+            config.clone(),
+            config.cursor_store("computer"),
+        )
+        .await
+        .unwrap();
+        chain.mine(1);
+        chain.mine(1);
+        let logs = take(&mut ingestor, 2).await.unwrap();
+        assert_eq!(positions(&logs), vec![(1, 0), (2, 0)]);
+
+        // Mined while the subscription is closed, so only read by the backfill.
+        chain.close_subscriptions();
+        chain.mine(2);
+        chain.mine(1);
+        let logs = take(&mut ingestor, 3).await.unwrap();
+        assert_eq!(positions(&logs), vec![(3, 0), (3, 1), (4, 0)]);
+        assert!(ingestor.status().is_connected());
+
+        chain.mine(1);
+        let logs = take(&mut ingestor, 1).await.unwrap();
+        assert_eq!(positions(&logs), vec![(5, 0)]);
+        // The head is read again before the backfill ends, so the cursor is reported until then.
+        assert_eq!(ingestor.status().synced_block(), 4);
+        cleanup(&config);
+    }
+}
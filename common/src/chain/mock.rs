@@ -0,0 +1,131 @@
+//! Scripted chain stand-in for the tests.
+
+use crate::chain::events::{LogSource, LogStream};
+use crate::chain::Error;
+use alloy::primitives::{Address, LogData, B256};
+use alloy::rpc::types::{Filter, Log};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+#[derive(Default)]
+struct State {
+    /// Logs of every block, indexed by block number.
+    blocks: Vec<Vec<Log>>,
+    subscribers: Vec<UnboundedSender<Log>>,
+    get_logs_ranges: Vec<(u64, u64)>,
+    /// Whether a closed subscription can be opened again.
+    reconnectable: bool,
+}
+
+#[derive(Clone)]
+/// Chain whose blocks are mined by the test. Block 0 has no logs.
+pub struct MockChain {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockChain {
+    pub fn new() -> Self {
+        let state = State {
+            blocks: vec![Vec::new()],
+            ..Default::default()
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Builds the log at the position, with a transaction hash derived from it.
+    pub fn log(block: u64, log_index: u64) -> Log {
+        let mut tx_hash = [0; 32];
+        tx_hash[..8].copy_from_slice(&block.to_be_bytes());
+        tx_hash[8..16].copy_from_slice(&log_index.to_be_bytes());
+        Log {
+            inner: alloy::primitives::Log {
+                address: Address::default(),
+                data: LogData::new_unchecked(Vec::new(), Default::default()),
+            },
+            block_hash: Some(B256::with_last_byte(block as u8)),
+            block_number: Some(block),
+            transaction_hash: Some(B256::from(tx_hash)),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    /// Mines a block with the given number of logs, and sends them to the subscribers.
+    pub fn mine(&self, num_logs: u64) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let block = state.blocks.len() as u64;
+        let logs: Vec<Log> = (0..num_logs).map(|i| Self::log(block, i)).collect();
+        for log in &logs {
+            state.subscribers.retain(|x| x.send(log.clone()).is_ok());
+        }
+        state.blocks.push(logs);
+        block
+    }
+
+    /// Drops the last blocks, and sends their logs to the subscribers as removed.
+    pub fn reorg(&self, depth: u64) {
+        let mut state = self.state.lock().unwrap();
+        let keep = state.blocks.len() - depth as usize;
+        let dropped = state.blocks.split_off(keep);
+        for log in dropped.into_iter().flatten() {
+            let log = Log {
+                removed: true,
+                ..log
+            };
+            state.subscribers.retain(|x| x.send(log.clone()).is_ok());
+        }
+    }
+
+    /// Ends every subscription, as a dropped connection would.
+    pub fn close_subscriptions(&self) {
+        self.state.lock().unwrap().subscribers.clear();
+    }
+
+    /// Lets the subscriptions be opened again after they were closed.
+    pub fn allow_reconnect(&self) {
+        self.state.lock().unwrap().reconnectable = true;
+    }
+
+    /// Returns the block ranges of the `get_logs` calls so far.
+    pub fn get_logs_ranges(&self) -> Vec<(u64, u64)> {
+        self.state.lock().unwrap().get_logs_ranges.clone()
+    }
+}
+
+#[async_trait]
+impl LogSource for MockChain {
+    async fn block_number(&self) -> Result<u64, Error> {
+        Ok(self.state.lock().unwrap().blocks.len() as u64 - 1)
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, Error> {
+        let mut state = self.state.lock().unwrap();
+        let head = state.blocks.len() as u64 - 1;
+        let from = filter.get_from_block().unwrap_or(0);
+        let to = filter.get_to_block().unwrap_or(head).min(head);
+        state.get_logs_ranges.push((from, to));
+        let logs = (from..=to)
+            .flat_map(|x| state.blocks[x as usize].clone())
+            .collect();
+        Ok(logs)
+    }
+
+    async fn subscribe_logs(&self, _filter: &Filter) -> Result<LogStream, Error> {
+        let (sender, receiver) = unbounded_channel();
+        self.state.lock().unwrap().subscribers.push(sender);
+        let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|x| (x, receiver))
+        });
+        Ok(Box::pin(stream))
+    }
+
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        match self.state.lock().unwrap().reconnectable {
+            true => Ok(()),
+            false => Err(Error::SubscriptionClosed),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Access to the OpenRank contracts.
+
+pub mod client;
+pub mod contract;
+pub mod cursor;
+pub mod events;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod nonce;
+pub mod pending;
+pub mod revert;
+pub mod signer;
+
+pub use client::{OpenRankClient, TxConfig};
+pub use cursor::{BlockCursor, CursorStore};
+pub use events::{
+    decode_log, EventIngestor, IngestConfig, IngestStatus, LogSource, ProviderLogSource,
+    WsLogSource,
+};
+pub use nonce::NonceManager;
+pub use pending::{PendingTx, TxTracker};
+pub use revert::ManagerError;
+pub use signer::{RemoteSigner, SignerConfig};
+
+#[derive(thiserror::Error, Debug)]
+/// Errors that can arise while reading from or writing to the chain.
+pub enum Error {
+    /// The RPC request failed.
+    #[error("RPC error: {0}")]
+    Rpc(String),
+    /// The log subscription was closed by the node.
+    #[error("Log subscription closed")]
+    SubscriptionClosed,
+    /// The log doesn't match the event.
+    #[error("Log decoding error: {0}")]
+    Decode(String),
+    /// The cursor couldn't be read or written.
+    #[error("I/O error: {0}")]
+    Io(std::io::Error),
+    /// The cursor couldn't be parsed.
+    #[error("Serde error: {0}")]
+    Serde(serde_json::Error),
+    /// The contract call reverted with a known error.
+    #[error("Contract reverted: {0}")]
+    Revert(ManagerError),
+    /// The transaction was included, but reverted.
+    #[error("Transaction reverted: {0}")]
+    Reverted(alloy::primitives::B256),
+    /// The nonce of the transaction was used by another transaction.
+    #[error("Transaction dropped: {0}")]
+    Dropped(alloy::primitives::B256),
+    /// The transaction wasn't included in time.
+    #[error("Timed out waiting for the receipt of {0}")]
+    ReceiptTimeout(alloy::primitives::B256),
+    /// The key couldn't be loaded, or the remote signer failed.
+    #[error("Signer error: {0}")]
+    Signer(String),
+    /// The configuration is not valid.
+    #[error("Config error: {0}")]
+    Config(String),
+}
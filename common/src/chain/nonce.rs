@@ -0,0 +1,52 @@
+use crate::chain::Error;
+use alloy::primitives::Address;
+use alloy::providers::Provider;
+use tokio::sync::Mutex;
+
+/// Hands out the nonces of an account, so that concurrent transactions don't collide.
+pub struct NonceManager {
+    address: Address,
+    /// Next nonce to use, or `None` if it must be read from the chain first.
+    next: Mutex<Option<u64>>,
+}
+
+impl NonceManager {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            next: Mutex::new(None),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Reserves the next nonce. The pending nonce of the account is read from the chain the
+    /// first time, and after a [reset](Self::reset).
+    pub async fn reserve<P: Provider>(&self, provider: &P) -> Result<u64, Error> {
+        let mut next = self.next.lock().await;
+        let nonce = match *next {
+            Some(nonce) => nonce,
+            None => provider
+                .get_transaction_count(self.address)
+                .pending()
+                .await
+                .map_err(|e| Error::Rpc(e.to_string()))?,
+        };
+        *next = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Makes sure the nonces handed out from now on are at least `nonce`.
+    pub async fn skip_to(&self, nonce: u64) {
+        let mut next = self.next.lock().await;
+        *next = Some(next.map_or(nonce, |x| x.max(nonce)));
+    }
+
+    /// Forgets the next nonce, so that it is read from the chain again. Used after a failed
+    /// send, since the reserved nonce might not have been used.
+    pub async fn reset(&self) {
+        *self.next.lock().await = None;
+    }
+}
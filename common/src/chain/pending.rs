@@ -0,0 +1,474 @@
+use crate::chain::client::RECEIPT_POLL_INTERVAL;
+use crate::chain::nonce::NonceManager;
+use crate::chain::{Error, TxConfig};
+use alloy::primitives::{Address, B256};
+use alloy::providers::Provider;
+use alloy::rpc::types::{TransactionReceipt, TransactionRequest};
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use tracing::{info, warn};
+
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Transaction sent by the node, and not included yet.
+pub struct PendingTx {
+    nonce: u64,
+    /// Hashes of every version of the transaction, starting with the original one.
+    hashes: Vec<B256>,
+    /// Last version of the transaction.
+    request: TransactionRequest,
+    /// When the last version was sent. Not persisted, since pending transactions are sent
+    /// again on restart.
+    #[serde(skip, default = "Instant::now")]
+    #[getset(skip)]
+    sent_at: Instant,
+}
+
+/// Raises the fee by the percentage, and at least by 1 wei.
+fn bump(fee: u128, percent: u64) -> u128 {
+    fee + (fee * percent as u128 / 100).max(1)
+}
+
+fn rpc_error(e: impl ToString) -> Error {
+    Error::Rpc(e.to_string())
+}
+
+/// Sends the transactions of an account, and tracks them until they are included.
+///
+/// Transactions that are not included after a while are replaced by a version with bumped
+/// fees and the same nonce. Pending transactions are persisted in a JSON file, so that they
+/// can be [reconciled](Self::reconcile) with the chain on restart.
+pub struct TxTracker {
+    nonces: NonceManager,
+    path: PathBuf,
+    pending: Mutex<BTreeMap<u64, PendingTx>>,
+    receipt_timeout: Duration,
+    replace_after: Duration,
+    fee_bump_percent: u64,
+}
+
+impl TxTracker {
+    /// Opens the tracker, loading the transactions left pending by a previous run.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        sender: Address,
+        config: &TxConfig,
+    ) -> Result<Self, Error> {
+        let path = path.into();
+        let pending = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice::<Vec<PendingTx>>(&data)
+                .map_err(Error::Serde)?
+                .into_iter()
+                .map(|x| (x.nonce, x))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        Ok(Self {
+            nonces: NonceManager::new(sender),
+            path,
+            pending: Mutex::new(pending),
+            receipt_timeout: *config.receipt_timeout(),
+            replace_after: *config.replace_after(),
+            fee_bump_percent: *config.fee_bump_percent(),
+        })
+    }
+
+    /// Account the transactions are sent from.
+    pub fn sender(&self) -> Address {
+        self.nonces.address()
+    }
+
+    /// Returns the pending transactions, by nonce.
+    pub fn pending(&self) -> Vec<PendingTx> {
+        self.pending.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Returns `true` if the transaction, or one of its replacements, is pending.
+    pub fn is_pending(&self, tx_hash: &B256) -> bool {
+        self.find(tx_hash).is_some()
+    }
+
+    /// Sends the transaction with the next nonce of the account, filling in the gas limit and
+    /// the fees if they are not set. Returns the transaction hash.
+    pub async fn send<P: Provider>(
+        &self,
+        provider: &P,
+        mut request: TransactionRequest,
+    ) -> Result<B256, Error> {
+        request.from = Some(self.sender());
+        if request.gas.is_none() {
+            request.gas = Some(
+                provider
+                    .estimate_gas(request.clone())
+                    .await
+                    .map_err(rpc_error)?,
+            );
+        }
+        if request.max_fee_per_gas.is_none() || request.max_priority_fee_per_gas.is_none() {
+            let fees = provider.estimate_eip1559_fees().await.map_err(rpc_error)?;
+            request.max_fee_per_gas.get_or_insert(fees.max_fee_per_gas);
+            request
+                .max_priority_fee_per_gas
+                .get_or_insert(fees.max_priority_fee_per_gas);
+        }
+
+        let nonce = self.nonces.reserve(provider).await?;
+        request.nonce = Some(nonce);
+        let tx_hash = match provider.send_transaction(request.clone()).await {
+            Ok(pending_tx) => *pending_tx.tx_hash(),
+            Err(e) => {
+                // The nonce was not used, so the next transaction must take it.
+                self.nonces.reset().await;
+                return Err(rpc_error(e));
+            }
+        };
+        let mut pending = self.pending.lock().unwrap();
+        pending.insert(
+            nonce,
+            PendingTx {
+                nonce,
+                hashes: vec![tx_hash],
+                request,
+                sent_at: Instant::now(),
+            },
+        );
+        self.save(&pending)?;
+        Ok(tx_hash)
+    }
+
+    /// Waits for the transaction, or one of its replacements, to be included, until the
+    /// configured timeout. The transaction is replaced whenever it is not included in time.
+    ///
+    /// Fails with [Error::Reverted] if the transaction reverted, and with [Error::Dropped] if
+    /// its nonce was used by another transaction.
+    pub async fn wait<P: Provider>(
+        &self,
+        provider: &P,
+        tx_hash: B256,
+    ) -> Result<TransactionReceipt, Error> {
+        let deadline = Instant::now() + self.receipt_timeout;
+        loop {
+            let Some(tx) = self.find(&tx_hash) else {
+                return Err(Error::Dropped(tx_hash));
+            };
+            // Read before the receipts, so that a transaction included in between isn't
+            // taken for a dropped one.
+            let mined = self.mined_nonce(provider).await?;
+            if let Some(receipt) = find_receipt(provider, &tx.hashes).await? {
+                self.remove(tx.nonce)?;
+                if !receipt.status() {
+                    return Err(Error::Reverted(receipt.transaction_hash));
+                }
+                return Ok(receipt);
+            }
+            if mined > tx.nonce {
+                self.remove(tx.nonce)?;
+                warn!(
+                    "Transaction dropped: Nonce({}), Tx Hash({:#})",
+                    tx.nonce, tx_hash
+                );
+                return Err(Error::Dropped(tx_hash));
+            }
+            if tx.sent_at.elapsed() >= self.replace_after {
+                if let Err(e) = self.replace(provider, tx.nonce).await {
+                    warn!("Failed to replace transaction: Nonce({}): {}", tx.nonce, e);
+                }
+            }
+            if Instant::now() + RECEIPT_POLL_INTERVAL > deadline {
+                return Err(Error::ReceiptTimeout(tx_hash));
+            }
+            sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Reconciles the transactions left pending by a previous run with the chain: included
+    /// and dropped ones are forgotten, and the others are sent again with bumped fees, so that
+    /// their nonces don't hold up the next transactions.
+    pub async fn reconcile<P: Provider>(&self, provider: &P) -> Result<(), Error> {
+        let mined = self.mined_nonce(provider).await?;
+        let mut next = provider
+            .get_transaction_count(self.sender())
+            .pending()
+            .await
+            .map_err(rpc_error)?;
+        for tx in self.pending() {
+            if let Some(receipt) = find_receipt(provider, &tx.hashes).await? {
+                info!(
+                    "Pending transaction was included: Nonce({}), Tx Hash({:#})",
+                    tx.nonce, receipt.transaction_hash
+                );
+                self.remove(tx.nonce)?;
+            } else if mined > tx.nonce {
+                warn!(
+                    "Pending transaction was dropped: Nonce({}), Tx Hash({:#})",
+                    tx.nonce, tx.hashes[0]
+                );
+                self.remove(tx.nonce)?;
+            } else {
+                if let Err(e) = self.replace(provider, tx.nonce).await {
+                    warn!("Failed to replace transaction: Nonce({}): {}", tx.nonce, e);
+                }
+                next = next.max(tx.nonce + 1);
+            }
+        }
+        self.nonces.skip_to(next).await;
+        Ok(())
+    }
+
+    /// Sends the transaction again with the same nonce and bumped fees.
+    async fn replace<P: Provider>(&self, provider: &P, nonce: u64) -> Result<(), Error> {
+        let request = {
+            let mut pending = self.pending.lock().unwrap();
+            let Some(tx) = pending.get_mut(&nonce) else {
+                return Ok(());
+            };
+            // Also when the replacement fails, so that it is not retried at every poll.
+            tx.sent_at = Instant::now();
+            let mut request = tx.request.clone();
+            request.max_fee_per_gas = request
+                .max_fee_per_gas
+                .map(|x| bump(x, self.fee_bump_percent));
+            request.max_priority_fee_per_gas = request
+                .max_priority_fee_per_gas
+                .map(|x| bump(x, self.fee_bump_percent));
+            request
+        };
+        let pending_tx = provider
+            .send_transaction(request.clone())
+            .await
+            .map_err(rpc_error)?;
+        let tx_hash = *pending_tx.tx_hash();
+        info!(
+            "Replaced transaction: Nonce({}), Tx Hash({:#})",
+            nonce, tx_hash
+        );
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(tx) = pending.get_mut(&nonce) {
+            tx.hashes.push(tx_hash);
+            tx.request = request;
+        }
+        self.save(&pending)
+    }
+
+    /// Returns the nonce of the next transaction to be included.
+    async fn mined_nonce<P: Provider>(&self, provider: &P) -> Result<u64, Error> {
+        provider
+            .get_transaction_count(self.sender())
+            .latest()
+            .await
+            .map_err(rpc_error)
+    }
+
+    fn find(&self, tx_hash: &B256) -> Option<PendingTx> {
+        let pending = self.pending.lock().unwrap();
+        pending
+            .values()
+            .find(|x| x.hashes.contains(tx_hash))
+            .cloned()
+    }
+
+    fn remove(&self, nonce: u64) -> Result<(), Error> {
+        let mut pending = self.pending.lock().unwrap();
+        pending.remove(&nonce);
+        self.save(&pending)
+    }
+
+    /// Saves the pending transactions, replacing the previous file atomically.
+    fn save(&self, pending: &BTreeMap<u64, PendingTx>) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(Error::Io)?;
+        }
+        let txs: Vec<&PendingTx> = pending.values().collect();
+        let data = serde_json::to_vec(&txs).map_err(Error::Serde)?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, data).map_err(Error::Io)?;
+        fs::rename(&tmp, &self.path).map_err(Error::Io)
+    }
+}
+
+/// Returns the receipt of the first of the transactions that was included.
+async fn find_receipt<P: Provider>(
+    provider: &P,
+    hashes: &[B256],
+) -> Result<Option<TransactionReceipt>, Error> {
+    for tx_hash in hashes {
+        let receipt = provider
+            .get_transaction_receipt(*tx_hash)
+            .await
+            .map_err(rpc_error)?;
+        if receipt.is_some() {
+            return Ok(receipt);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::pending::{bump, TxTracker};
+    use crate::chain::{Error, TxConfig};
+    use alloy::primitives::{Address, B256, U64};
+    use alloy::providers::{PendingTransactionBuilder, Provider, ProviderBuilder, RootProvider};
+    use alloy::rpc::types::{TransactionReceipt, TransactionRequest};
+    use alloy::transports::mock::Asserter;
+    use alloy::transports::TransportResult;
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Provider that sends transactions without starting the block poller of the default
+    /// implementation, which would take the mocked responses.
+    struct MockProvider(RootProvider);
+
+    #[async_trait::async_trait]
+    impl Provider for MockProvider {
+        fn root(&self) -> &RootProvider {
+            &self.0
+        }
+
+        async fn send_transaction(
+            &self,
+            tx: TransactionRequest,
+        ) -> TransportResult<PendingTransactionBuilder<alloy::network::Ethereum>> {
+            let tx_hash = self.client().request("eth_sendTransaction", (tx,)).await?;
+            Ok(PendingTransactionBuilder::new(self.0.clone(), tx_hash))
+        }
+    }
+
+    fn provider(asserter: &Asserter) -> MockProvider {
+        MockProvider(
+            ProviderBuilder::new()
+                .disable_recommended_fillers()
+                .on_mocked_client(asserter.clone()),
+        )
+    }
+
+    fn receipt(tx_hash: B256, status: bool) -> serde_json::Value {
+        json!({
+            "type": "0x2",
+            "status": if status { "0x1" } else { "0x0" },
+            "cumulativeGasUsed": "0x0",
+            "logs": [],
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "transactionHash": tx_hash,
+            "transactionIndex": "0x0",
+            "blockHash": B256::repeat_byte(0xbb),
+            "blockNumber": "0x1",
+            "gasUsed": "0x0",
+            "effectiveGasPrice": "0x0",
+            "from": Address::ZERO,
+            "to": null,
+            "contractAddress": null
+        })
+    }
+
+    fn request() -> TransactionRequest {
+        TransactionRequest::default()
+            .to(Address::with_last_byte(1))
+            .gas_limit(21000)
+            .max_fee_per_gas(100)
+            .max_priority_fee_per_gas(10)
+    }
+
+    fn config() -> TxConfig {
+        TxConfig::default().with_replacement(Duration::from_secs(2), 12)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_replace_and_detect_dropped_transactions() {
+        assert_eq!(bump(100, 12), 112);
+        assert_eq!(bump(1, 12), 2);
+
+        let path = std::env::temp_dir()
+            .join(format!("openrank-pending-dropped-{}", std::process::id()))
+            .join("computer.json");
+        let asserter = Asserter::new();
+        let provider = provider(&asserter);
+        let tracker = TxTracker::open(&path, Address::with_last_byte(7), &config()).unwrap();
+
+        let (a, b) = (B256::repeat_byte(0xa), B256::repeat_byte(0xb));
+        asserter.push_success(&U64::from(5));
+        asserter.push_success(&a);
+        assert_eq!(tracker.send(&provider, request()).await.unwrap(), a);
+        assert_eq!(*tracker.pending()[0].nonce(), 5);
+
+        // Not included for a while, so it is replaced with bumped fees.
+        asserter.push_success(&U64::from(5));
+        asserter.push_success(&Option::<TransactionReceipt>::None);
+        asserter.push_success(&U64::from(5));
+        asserter.push_success(&Option::<TransactionReceipt>::None);
+        asserter.push_success(&b);
+        // Then the nonce is used by a transaction of another process.
+        asserter.push_success(&U64::from(6));
+        asserter.push_success(&Option::<TransactionReceipt>::None);
+        asserter.push_success(&Option::<TransactionReceipt>::None);
+        let res = tracker.wait(&provider, a).await;
+        assert!(matches!(res, Err(Error::Dropped(x)) if x == a));
+        assert!(asserter.read_q().is_empty());
+        assert!(tracker.pending().is_empty());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_reconcile_pending_transactions() {
+        let path = std::env::temp_dir()
+            .join(format!("openrank-pending-reconcile-{}", std::process::id()))
+            .join("computer.json");
+        let asserter = Asserter::new();
+        let provider = provider(&asserter);
+        let sender = Address::with_last_byte(7);
+        let (a, b, c, d) = (
+            B256::repeat_byte(0xa),
+            B256::repeat_byte(0xb),
+            B256::repeat_byte(0xc),
+            B256::repeat_byte(0xd),
+        );
+        {
+            let tracker = TxTracker::open(&path, sender, &config()).unwrap();
+            asserter.push_success(&U64::from(3));
+            asserter.push_success(&a);
+            asserter.push_success(&b);
+            tracker.send(&provider, request()).await.unwrap();
+            tracker.send(&provider, request()).await.unwrap();
+        }
+
+        // After a restart, the first transaction turns out included and the second is still
+        // pending, so it is sent again.
+        let tracker = TxTracker::open(&path, sender, &config()).unwrap();
+        assert_eq!(tracker.pending().len(), 2);
+        asserter.push_success(&U64::from(4));
+        asserter.push_success(&U64::from(4));
+        asserter.push_success(&receipt(a, true));
+        asserter.push_success(&Option::<TransactionReceipt>::None);
+        asserter.push_success(&c);
+        tracker.reconcile(&provider).await.unwrap();
+        let pending = tracker.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(*pending[0].nonce(), 4);
+        assert_eq!(pending[0].hashes(), &vec![b, c]);
+        assert_eq!(pending[0].request().max_fee_per_gas, Some(112));
+        assert!(tracker.is_pending(&b) && tracker.is_pending(&c));
+
+        // The next nonce comes after the pending transaction, without asking the chain.
+        asserter.push_success(&d);
+        tracker.send(&provider, request()).await.unwrap();
+        assert_eq!(*tracker.pending()[1].nonce(), 5);
+
+        // The replacement is included.
+        asserter.push_success(&U64::from(6));
+        asserter.push_success(&Option::<TransactionReceipt>::None);
+        asserter.push_success(&receipt(c, true));
+        let receipt = tracker.wait(&provider, b).await.unwrap();
+        assert_eq!(receipt.transaction_hash, c);
+        assert!(asserter.read_q().is_empty());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+//! Typed errors of the OpenRank contracts.
+
+use crate::chain::contract::IOpenRankManager::IOpenRankManagerErrors as ManagerErrors;
+use alloy::sol_types::SolInterface;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+/// Reason a call to the `OpenRankManager` contract reverted.
+pub enum ManagerError {
+    #[error("Compute request not found")]
+    ComputeRequestNotFound,
+    #[error("Compute result already submitted")]
+    ComputeResultAlreadySubmitted,
+    #[error("Compute result not found")]
+    ComputeResultNotFound,
+    #[error("Challenge not found")]
+    ChallengeNotFound,
+    #[error("Challenge period expired")]
+    ChallengePeriodExpired,
+    #[error("Caller not whitelisted")]
+    CallerNotWhitelisted,
+    #[error("Invalid reservation for image id")]
+    InvalidReservationForImageId,
+}
+
+impl ManagerError {
+    /// Decodes the revert data of a call, if it is one of the `IOpenRankManager` errors.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        ManagerErrors::abi_decode(data, true).ok().map(Self::from)
+    }
+
+    /// Returns the reason the contract call failed, if it reverted with a known error.
+    pub fn from_contract_error(e: &alloy::contract::Error) -> Option<Self> {
+        e.as_decoded_interface_error::<ManagerErrors>()
+            .map(Self::from)
+    }
+}
+
+impl From<ManagerErrors> for ManagerError {
+    fn from(e: ManagerErrors) -> Self {
+        match e {
+            ManagerErrors::ComputeRequestNotFound(_) => Self::ComputeRequestNotFound,
+            ManagerErrors::ComputeResultAlreadySubmitted(_) => Self::ComputeResultAlreadySubmitted,
+            ManagerErrors::ComputeResultNotFound(_) => Self::ComputeResultNotFound,
+            ManagerErrors::ChallengeNotFound(_) => Self::ChallengeNotFound,
+            ManagerErrors::ChallengePeriodExpired(_) => Self::ChallengePeriodExpired,
+            ManagerErrors::CallerNotWhitelisted(_) => Self::CallerNotWhitelisted,
+            ManagerErrors::InvalidReservationForImageId(_) => Self::InvalidReservationForImageId,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::contract::IOpenRankManager::{
+        CallerNotWhitelisted, ComputeResultAlreadySubmitted,
+    };
+    use crate::chain::revert::ManagerError;
+    use alloy::sol_types::SolError;
+
+    #[test]
+    fn should_decode_revert_selectors() {
+        let data = ComputeResultAlreadySubmitted {}.abi_encode();
+        assert_eq!(data.len(), 4);
+        assert_eq!(
+            ManagerError::decode(&data),
+            Some(ManagerError::ComputeResultAlreadySubmitted)
+        );
+        let data = CallerNotWhitelisted {}.abi_encode();
+        assert_eq!(
+            ManagerError::decode(&data),
+            Some(ManagerError::CallerNotWhitelisted)
+        );
+        assert_eq!(ManagerError::decode(&[0xde, 0xad, 0xbe, 0xef]), None);
+        assert_eq!(ManagerError::decode(&[]), None);
+    }
+}
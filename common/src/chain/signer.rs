@@ -0,0 +1,359 @@
+use crate::chain::Error;
+use crate::config::{Secret, Settings};
+use alloy::consensus::SignableTransaction;
+use alloy::hex;
+use alloy::network::{EthereumWallet, TxSigner};
+use alloy::primitives::{keccak256, Address, PrimitiveSignature as Signature};
+use alloy::signers::local::coins_bip39::English;
+use alloy::signers::local::{MnemonicBuilder, PrivateKeySigner};
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+/// Selects and configures the key signing the transactions.
+pub enum SignerConfig {
+    /// Key derived from a BIP-39 mnemonic, at the index of the default derivation path.
+    Mnemonic { phrase: Secret, index: u32 },
+    /// Raw private key, hex encoded in a file.
+    PrivateKey { path: PathBuf },
+    /// Encrypted JSON keystore.
+    Keystore { path: PathBuf, passphrase: Secret },
+    /// Key held by a remote signer, see [RemoteSigner].
+    Remote { url: Url, address: Address },
+}
+
+impl SignerConfig {
+    /// Reads the config from the `SIGNER` (`mnemonic`, `private_key`, `keystore` or `remote`),
+    /// `MNEMONIC`, `MNEMONIC_INDEX`, `PRIVATE_KEY_PATH`, `KEYSTORE_PATH`, `KEYSTORE_PASSPHRASE`,
+    /// `REMOTE_SIGNER_URL` and `REMOTE_SIGNER_ADDRESS` settings. Defaults to the mnemonic at
+    /// index 0.
+    ///
+    /// With a role, the settings prefixed by the role, e.g. `CHALLENGER_MNEMONIC_INDEX`, take
+    /// precedence, so that the roles running on one host can use different keys.
+    pub fn from_settings(settings: &Settings, role: Option<&str>) -> Result<Self, Error> {
+        let get = |name: &str| setting::<String>(settings, role, name);
+        let require = |name: &str| -> Result<String, Error> {
+            get(name)?.ok_or(Error::Config(format!("{} must be set", name)))
+        };
+        match get("SIGNER")?.as_deref() {
+            None | Some("mnemonic") => Ok(Self::Mnemonic {
+                phrase: Secret::new(require("MNEMONIC")?),
+                index: setting(settings, role, "MNEMONIC_INDEX")?.unwrap_or(0),
+            }),
+            Some("private_key") => Ok(Self::PrivateKey {
+                path: require("PRIVATE_KEY_PATH")?.into(),
+            }),
+            Some("keystore") => Ok(Self::Keystore {
+                path: require("KEYSTORE_PATH")?.into(),
+                passphrase: Secret::new(require("KEYSTORE_PASSPHRASE")?),
+            }),
+            Some("remote") => Ok(Self::Remote {
+                url: require("REMOTE_SIGNER_URL")?
+                    .parse()
+                    .map_err(|e| Error::Config(format!("Invalid REMOTE_SIGNER_URL: {}", e)))?,
+                address: require("REMOTE_SIGNER_ADDRESS")?
+                    .parse()
+                    .map_err(|e| Error::Config(format!("Invalid REMOTE_SIGNER_ADDRESS: {}", e)))?,
+            }),
+            Some(other) => Err(Error::Config(format!("Unknown signer: '{}'", other))),
+        }
+    }
+
+    /// Creates the wallet of the configured key.
+    pub fn build(&self) -> Result<EthereumWallet, Error> {
+        let signer_error = |e: &dyn Display| Error::Signer(e.to_string());
+        match self {
+            Self::Mnemonic { phrase, index } => {
+                let signer = MnemonicBuilder::<English>::default()
+                    .phrase(phrase.expose())
+                    .index(*index)
+                    .map_err(|e| signer_error(&e))?
+                    .build()
+                    .map_err(|e| signer_error(&e))?;
+                Ok(signer.into())
+            }
+            Self::PrivateKey { path } => {
+                let key = std::fs::read_to_string(path).map_err(Error::Io)?;
+                // The key itself must not end up in the error.
+                let key = hex::decode(key.trim())
+                    .map_err(|_| Error::Signer(format!("Invalid private key in {:?}", path)))?;
+                let signer = PrivateKeySigner::from_slice(&key)
+                    .map_err(|_| Error::Signer(format!("Invalid private key in {:?}", path)))?;
+                Ok(signer.into())
+            }
+            Self::Keystore { path, passphrase } => {
+                let signer = PrivateKeySigner::decrypt_keystore(path, passphrase.expose())
+                    .map_err(|e| signer_error(&e))?;
+                Ok(signer.into())
+            }
+            Self::Remote { url, address } => Ok(RemoteSigner::new(url.clone(), *address).into()),
+        }
+    }
+}
+
+/// Parses the setting of the role if it is set, or else the shared setting.
+fn setting<T: FromStr>(
+    settings: &Settings,
+    role: Option<&str>,
+    name: &str,
+) -> Result<Option<T>, Error>
+where
+    T::Err: Display,
+{
+    let parse = |name: &str| {
+        settings
+            .parse(name)
+            .map_err(|e| Error::Config(e.to_string()))
+    };
+    if let Some(role) = role {
+        if let Some(value) = parse(&format!("{}_{}", role.to_uppercase(), name))? {
+            return Ok(Some(value));
+        }
+    }
+    parse(name)
+}
+
+/// Signs transactions with a key held by a remote signer, over the `eth1` sign API of
+/// web3signer: the signing payload of the transaction is posted to
+/// `{url}/api/v1/eth1/sign/{address}`, which returns the signature of its keccak hash.
+#[derive(Clone, Debug)]
+pub struct RemoteSigner {
+    client: Client,
+    url: Url,
+    address: Address,
+}
+
+impl RemoteSigner {
+    pub fn new(url: Url, address: Address) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+            address,
+        }
+    }
+
+    /// Signs the keccak hash of the payload, checking that the signature is from the address.
+    pub async fn sign_payload(&self, payload: &[u8]) -> Result<Signature, Error> {
+        let url = self
+            .url
+            .join(&format!("api/v1/eth1/sign/{}", self.address))
+            .map_err(|e| Error::Signer(e.to_string()))?;
+        let body = serde_json::json!({ "data": format!("0x{}", hex::encode(payload)) });
+        let res = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| Error::Signer(e.to_string()))?;
+        let status = res.status();
+        let text = res.text().await.map_err(|e| Error::Signer(e.to_string()))?;
+        if !status.is_success() {
+            return Err(Error::Signer(format!(
+                "Remote signer returned {}: {}",
+                status, text
+            )));
+        }
+        let bytes = hex::decode(text.trim().trim_matches('"'))
+            .map_err(|e| Error::Signer(format!("Invalid signature: {}", e)))?;
+        let signature = Signature::try_from(bytes.as_slice())
+            .map_err(|e| Error::Signer(format!("Invalid signature: {}", e)))?;
+        let signer = signature
+            .recover_address_from_prehash(&keccak256(payload))
+            .map_err(|e| Error::Signer(format!("Invalid signature: {}", e)))?;
+        if signer != self.address {
+            return Err(Error::Signer(format!(
+                "Signature is from {}, expected {}",
+                signer, self.address
+            )));
+        }
+        Ok(signature)
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        let mut payload = Vec::new();
+        tx.encode_for_signing(&mut payload);
+        self.sign_payload(&payload)
+            .await
+            .map_err(alloy::signers::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::signer::{RemoteSigner, SignerConfig};
+    use crate::chain::Error;
+    use crate::config::{Secret, Settings};
+    use alloy::consensus::{SignableTransaction, TxLegacy};
+    use alloy::hex;
+    use alloy::network::TxSigner;
+    use alloy::primitives::{keccak256, Address, U256};
+    use alloy::signers::local::PrivateKeySigner;
+    use alloy::signers::SignerSync;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    /// Stand-in for web3signer, signing with the key. Returns its URL.
+    async fn serve_remote_signer(key: PrivateKeySigner) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                // The body is the last part of the request, after the headers.
+                let body = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+                        let length: usize = headers
+                            .lines()
+                            .find_map(|x| {
+                                x.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(|x| x.parse().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break body.to_string();
+                        }
+                    }
+                };
+                let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+                let data = hex::decode(body["data"].as_str().unwrap()).unwrap();
+                let signature = key.sign_hash_sync(&keccak256(&data)).unwrap();
+                let signature = format!("0x{}", hex::encode(signature.as_bytes()));
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                    signature.len(),
+                    signature
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn should_read_the_signer_of_the_role() {
+        let mut settings = Settings::default();
+        settings.set("SIGNER", "mnemonic".to_string());
+        settings.set("MNEMONIC", MNEMONIC.to_string());
+        settings.set("CHALLENGER_MNEMONIC_INDEX", "1".to_string());
+        settings.set("SDK_SIGNER", "private_key".to_string());
+        settings.set("SDK_PRIVATE_KEY_PATH", "./key".to_string());
+
+        let computer = SignerConfig::from_settings(&settings, Some("computer")).unwrap();
+        let challenger = SignerConfig::from_settings(&settings, Some("challenger")).unwrap();
+        let sdk = SignerConfig::from_settings(&settings, Some("sdk")).unwrap();
+        let phrase = Secret::new(MNEMONIC.to_string());
+        assert_eq!(
+            computer,
+            SignerConfig::Mnemonic {
+                phrase: phrase.clone(),
+                index: 0
+            }
+        );
+        assert_eq!(challenger, SignerConfig::Mnemonic { phrase, index: 1 });
+        assert_eq!(
+            sdk,
+            SignerConfig::PrivateKey {
+                path: "./key".into()
+            }
+        );
+
+        let computer = computer.build().unwrap().default_signer().address();
+        let challenger = challenger.build().unwrap().default_signer().address();
+        assert_ne!(computer, challenger);
+
+        settings.set("SIGNER", "keystore".to_string());
+        let err = SignerConfig::from_settings(&settings, None).unwrap_err();
+        assert!(matches!(err, Error::Config(x) if x == "KEYSTORE_PATH must be set"));
+    }
+
+    #[test]
+    fn should_load_key_files() {
+        let dir = std::env::temp_dir().join(format!("openrank-signer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = PrivateKeySigner::random();
+
+        let key_path = dir.join("key");
+        std::fs::write(&key_path, format!("0x{}\n", hex::encode(key.to_bytes()))).unwrap();
+        let config = SignerConfig::PrivateKey { path: key_path };
+        let wallet = config.build().unwrap();
+        assert_eq!(wallet.default_signer().address(), key.address());
+
+        let mut rng = k256::elliptic_curve::rand_core::OsRng;
+        PrivateKeySigner::encrypt_keystore(
+            &dir,
+            &mut rng,
+            key.to_bytes(),
+            "pass",
+            Some("keystore"),
+        )
+        .unwrap();
+        let config = SignerConfig::Keystore {
+            path: dir.join("keystore"),
+            passphrase: Secret::new("pass".to_string()),
+        };
+        let wallet = config.build().unwrap();
+        assert_eq!(wallet.default_signer().address(), key.address());
+
+        let config = SignerConfig::Keystore {
+            path: dir.join("keystore"),
+            passphrase: Secret::new("wrong".to_string()),
+        };
+        assert!(matches!(config.build(), Err(Error::Signer(_))));
+
+        let config = SignerConfig::PrivateKey {
+            path: dir.join("missing"),
+        };
+        assert!(matches!(config.build(), Err(Error::Io(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_sign_with_the_remote_signer() {
+        let key = PrivateKeySigner::random();
+        let url = serve_remote_signer(key.clone()).await;
+
+        let signer = RemoteSigner::new(url.parse().unwrap(), key.address());
+        let mut tx = TxLegacy {
+            chain_id: Some(31337),
+            nonce: 1,
+            gas_price: 1_000_000_000,
+            gas_limit: 21_000,
+            to: Address::ZERO.into(),
+            value: U256::from(1),
+            ..Default::default()
+        };
+        let signature = signer.sign_transaction(&mut tx).await.unwrap();
+        let recovered = signature
+            .recover_address_from_prehash(&tx.signature_hash())
+            .unwrap();
+        assert_eq!(recovered, key.address());
+
+        // Signatures from another key are rejected.
+        let other = RemoteSigner::new(url.parse().unwrap(), Address::repeat_byte(1));
+        let err = other.sign_payload(b"payload").await.unwrap_err();
+        assert!(matches!(err, Error::Signer(_)));
+    }
+}
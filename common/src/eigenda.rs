@@ -1,5 +1,7 @@
+use crate::metrics;
 use alloy::hex;
 use reqwest::Client;
+use std::time::Instant;
 
 const BLOB_SIZE_BYTES: usize = 15777216;
 
@@ -17,13 +19,22 @@ impl EigenDAProxyClient {
         }
     }
 
-    pub async fn health(&self) {
-        let resp = reqwest::get("https://httpbin.org/ip").await.unwrap();
-        println!("{resp:#?}");
+    /// Checks that the proxy is up, with its `/health` endpoint.
+    pub async fn health(&self) -> Result<(), reqwest::Error> {
+        self.client
+            .get(format!("{}/health", self.url))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
     }
 
     pub async fn put(&self, data: Vec<u8>) -> Vec<u8> {
         let put_url = format!("{}/put?commitment_mode=standard", self.url);
+        let start = Instant::now();
+        metrics::EIGENDA_BYTES
+            .with_label_values(&["put"])
+            .inc_by(data.len() as u64);
         let res = self
             .client
             .post(put_url.as_str())
@@ -34,7 +45,9 @@ impl EigenDAProxyClient {
             .unwrap();
 
         println!("Response Status: {}", res.status());
-        res.bytes().await.unwrap().to_vec()
+        let cert = res.bytes().await.unwrap().to_vec();
+        metrics::observe_since(&metrics::EIGENDA_DURATION, &["put"], start);
+        cert
     }
 
     // Get data from EigenDA given the commitment bytes
@@ -44,6 +57,7 @@ impl EigenDAProxyClient {
             self.url,
             hex::encode(cert_bytes)
         );
+        let start = Instant::now();
         let res = self
             .client
             .get(get_url.as_str())
@@ -51,7 +65,12 @@ impl EigenDAProxyClient {
             .send()
             .await
             .unwrap();
-        res.bytes().await.unwrap().to_vec()
+        let data = res.bytes().await.unwrap().to_vec();
+        metrics::observe_since(&metrics::EIGENDA_DURATION, &["get"], start);
+        metrics::EIGENDA_BYTES
+            .with_label_values(&["get"])
+            .inc_by(data.len() as u64);
+        data
     }
 
     pub async fn get_chunks(&self, certs: Vec<Vec<u8>>) -> Vec<u8> {
@@ -77,14 +96,12 @@ impl EigenDAProxyClient {
     pub async fn put_meta(&self, data: Vec<u8>) -> Vec<u8> {
         let certs = self.put_chunks(data).await;
         let certs_flatten = serde_json::to_vec(&certs).unwrap();
-        let meta_cert = self.put(certs_flatten).await;
-        meta_cert
+        self.put(certs_flatten).await
     }
 
     pub async fn get_meta(&self, meta_cert_bytes: Vec<u8>) -> Vec<u8> {
         let certs_json = self.get(meta_cert_bytes).await;
         let certs: Vec<Vec<u8>> = serde_json::from_slice(&certs_json).unwrap();
-        let data = self.get_chunks(certs).await;
-        data
+        self.get_chunks(certs).await
     }
 }
@@ -0,0 +1,434 @@
+//! Liveness and readiness checks of the nodes, served along with the metrics.
+//!
+//! `/healthz` fails when restarting the node would help, e.g. when the log subscription could
+//! not be opened again for a while. `/readyz` fails while any dependency of the node is
+//! unreachable or the logs are read too far behind the chain head. Both return a JSON report
+//! with the outcome of every check.
+
+use crate::blob::{BlobKey, BlobStore};
+use crate::chain::IngestStatus;
+use crate::config::{self, Settings};
+use crate::eigenda::EigenDAProxyClient;
+use crate::jobs::JobStore;
+use crate::metrics;
+use alloy::providers::Provider;
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use getset::Getters;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+use tracing::info;
+
+/// Largest number of blocks the logs may be read behind the chain head, used when none is
+/// configured.
+pub const DEFAULT_MAX_LAG_BLOCKS: u64 = 100;
+/// How long the log subscription may stay closed before the node is reported as not live,
+/// used when none is configured.
+pub const DEFAULT_MAX_DISCONNECTED_SECS: u64 = 300;
+/// How long a single check may take before it fails.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(thiserror::Error, Debug)]
+/// Errors of the health checks and their server.
+pub enum Error {
+    #[error("Config error: {0}")]
+    Config(config::Error),
+
+    #[error("Failed to bind {0}: {1}")]
+    Bind(SocketAddr, hyper::Error),
+
+    #[error("Server error: {0}")]
+    Server(hyper::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Getters)]
+#[getset(get = "pub")]
+/// Thresholds of the health checks.
+pub struct HealthConfig {
+    /// Largest number of blocks the logs may be read behind the chain head.
+    max_lag_blocks: u64,
+    /// How long the log subscription may stay closed before the node is not live.
+    max_disconnected: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MAX_LAG_BLOCKS,
+            Duration::from_secs(DEFAULT_MAX_DISCONNECTED_SECS),
+        )
+    }
+}
+
+impl HealthConfig {
+    pub fn new(max_lag_blocks: u64, max_disconnected: Duration) -> Self {
+        Self {
+            max_lag_blocks,
+            max_disconnected,
+        }
+    }
+
+    /// Reads the config from the `HEALTH_MAX_LAG_BLOCKS` and `HEALTH_MAX_DISCONNECTED_SECS`
+    /// settings, falling back to the defaults.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        let max_lag_blocks = settings
+            .parse("HEALTH_MAX_LAG_BLOCKS")
+            .map_err(Error::Config)?
+            .unwrap_or(DEFAULT_MAX_LAG_BLOCKS);
+        let max_disconnected_secs = settings
+            .parse("HEALTH_MAX_DISCONNECTED_SECS")
+            .map_err(Error::Config)?
+            .unwrap_or(DEFAULT_MAX_DISCONNECTED_SECS);
+        Ok(Self::new(
+            max_lag_blocks,
+            Duration::from_secs(max_disconnected_secs),
+        ))
+    }
+}
+
+#[async_trait]
+/// Single check of the node or of one of its dependencies.
+pub trait Check: Send + Sync {
+    /// Name of the check in the report.
+    fn name(&self) -> &'static str;
+    /// Returns a short description of the state, or why the check failed.
+    async fn check(&self) -> Result<String, String>;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Getters)]
+#[getset(get = "pub")]
+/// Outcome of a [Check].
+pub struct CheckResult {
+    healthy: bool,
+    detail: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Getters)]
+#[getset(get = "pub")]
+/// Outcome of every check, healthy if they all passed.
+pub struct Report {
+    healthy: bool,
+    checks: BTreeMap<&'static str, CheckResult>,
+}
+
+#[derive(Default)]
+/// Checks run by `/healthz` and `/readyz`.
+pub struct Health {
+    live: Vec<Box<dyn Check>>,
+    ready: Vec<Box<dyn Check>>,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a check to `/healthz`.
+    pub fn with_live(mut self, check: impl Check + 'static) -> Self {
+        self.live.push(Box::new(check));
+        self
+    }
+
+    /// Adds a check to `/readyz`.
+    pub fn with_ready(mut self, check: impl Check + 'static) -> Self {
+        self.ready.push(Box::new(check));
+        self
+    }
+
+    /// Runs the liveness checks.
+    pub async fn live(&self) -> Report {
+        run_checks(&self.live).await
+    }
+
+    /// Runs the readiness checks.
+    pub async fn ready(&self) -> Report {
+        run_checks(&self.ready).await
+    }
+}
+
+/// Runs the checks concurrently, failing those that time out.
+async fn run_checks(checks: &[Box<dyn Check>]) -> Report {
+    let results = join_all(checks.iter().map(|x| async move {
+        let res = match timeout(CHECK_TIMEOUT, x.check()).await {
+            Ok(res) => res,
+            Err(_) => Err(format!("timed out after {:?}", CHECK_TIMEOUT)),
+        };
+        let result = match res {
+            Ok(detail) => CheckResult {
+                healthy: true,
+                detail,
+            },
+            Err(detail) => CheckResult {
+                healthy: false,
+                detail,
+            },
+        };
+        (x.name(), result)
+    }))
+    .await;
+    Report {
+        healthy: results.iter().all(|(_, x)| x.healthy),
+        checks: results.into_iter().collect(),
+    }
+}
+
+/// Reads the chain head over RPC.
+pub struct RpcCheck<P> {
+    provider: P,
+}
+
+impl<P> RpcCheck<P> {
+    pub fn new(provider: P) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Check for RpcCheck<P> {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        let head = self
+            .provider
+            .get_block_number()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format!("block {}", head))
+    }
+}
+
+/// Checks that the log subscription is open, or was closed for less than the grace period.
+pub struct WsCheck {
+    status: Arc<IngestStatus>,
+    grace: Duration,
+}
+
+impl WsCheck {
+    pub fn new(status: Arc<IngestStatus>, grace: Duration) -> Self {
+        Self { status, grace }
+    }
+}
+
+#[async_trait]
+impl Check for WsCheck {
+    fn name(&self) -> &'static str {
+        "ws"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        match self.status.disconnected_for() {
+            None => Ok("connected".to_string()),
+            Some(x) if x < self.grace => Ok(format!("connecting for {}s", x.as_secs())),
+            Some(x) => Err(format!("disconnected for {}s", x.as_secs())),
+        }
+    }
+}
+
+/// Checks that the logs are read close enough to the chain head, read over RPC.
+pub struct LagCheck<P> {
+    provider: P,
+    status: Arc<IngestStatus>,
+    max_lag_blocks: u64,
+}
+
+impl<P> LagCheck<P> {
+    pub fn new(provider: P, status: Arc<IngestStatus>, max_lag_blocks: u64) -> Self {
+        Self {
+            provider,
+            status,
+            max_lag_blocks,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Check for LagCheck<P> {
+    fn name(&self) -> &'static str {
+        "subscription_lag"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        let head = self
+            .provider
+            .get_block_number()
+            .await
+            .map_err(|e| e.to_string())?;
+        let lag = head.saturating_sub(self.status.synced_block());
+        if lag > self.max_lag_blocks {
+            return Err(format!("{} blocks behind", lag));
+        }
+        Ok(format!("{} blocks behind", lag))
+    }
+}
+
+/// Checks that the blob store answers requests.
+pub struct BlobStoreCheck {
+    store: Arc<dyn BlobStore>,
+}
+
+impl BlobStoreCheck {
+    pub fn new(store: Arc<dyn BlobStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Check for BlobStoreCheck {
+    fn name(&self) -> &'static str {
+        "blob_store"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        self.store
+            .exists(&BlobKey::meta("healthz"))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok("reachable".to_string())
+    }
+}
+
+/// Checks that the EigenDA proxy is up.
+pub struct EigenDaCheck {
+    client: EigenDAProxyClient,
+}
+
+impl EigenDaCheck {
+    pub fn new(client: EigenDAProxyClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Check for EigenDaCheck {
+    fn name(&self) -> &'static str {
+        "eigenda"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        self.client.health().await.map_err(|e| e.to_string())?;
+        Ok("reachable".to_string())
+    }
+}
+
+/// Reports the number of jobs not completed yet.
+pub struct QueueCheck {
+    jobs: Arc<JobStore>,
+}
+
+impl QueueCheck {
+    pub fn new(jobs: Arc<JobStore>) -> Self {
+        Self { jobs }
+    }
+}
+
+#[async_trait]
+impl Check for QueueCheck {
+    fn name(&self) -> &'static str {
+        "queue"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        let depth = self.jobs.pending().map_err(|e| e.to_string())?;
+        Ok(format!("{} pending", depth))
+    }
+}
+
+fn json_response(report: &Report) -> Response<Body> {
+    let status = match report.healthy {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let body = serde_json::to_vec(report).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn handle(health: Arc<Health>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let res = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::from(metrics::render()))
+            .unwrap(),
+        (&Method::GET, "/healthz") => json_response(&health.live().await),
+        (&Method::GET, "/readyz") => json_response(&health.ready().await),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap(),
+    };
+    Ok(res)
+}
+
+/// Serves `/metrics`, `/healthz` and `/readyz`, until the process stops.
+pub async fn serve(addr: SocketAddr, health: Arc<Health>) -> Result<(), Error> {
+    let make_service = make_service_fn(move |_| {
+        let health = health.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(health.clone(), req))) }
+    });
+    let server = Server::try_bind(&addr)
+        .map_err(|e| Error::Bind(addr, e))?
+        .serve(make_service);
+    info!(
+        "Serving metrics and health checks: Addr({})",
+        server.local_addr()
+    );
+    server.await.map_err(Error::Server)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blob::MemoryBlobStore;
+    use crate::chain::IngestStatus;
+    use crate::health::{BlobStoreCheck, Check, Health, WsCheck};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct Failing;
+
+    #[async_trait]
+    impl Check for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn check(&self) -> Result<String, String> {
+            Err("unreachable".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn should_report_every_check() {
+        let status = Arc::new(IngestStatus::default());
+        let health = Health::new()
+            .with_live(WsCheck::new(status.clone(), Duration::from_secs(60)))
+            .with_ready(BlobStoreCheck::new(Arc::new(MemoryBlobStore::new())))
+            .with_ready(WsCheck::new(status, Duration::ZERO))
+            .with_ready(Failing);
+
+        // The subscription may take a while to open before the node is not live.
+        let report = health.live().await;
+        assert!(report.healthy());
+        assert_eq!(report.checks()["ws"].detail(), "connecting for 0s");
+
+        let report = health.ready().await;
+        assert!(!report.healthy());
+        assert!(report.checks()["blob_store"].healthy());
+        assert!(!report.checks()["ws"].healthy());
+        assert_eq!(report.checks()["failing"].detail(), "unreachable");
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.starts_with(r#"{"healthy":false,"checks":{"blob_store":{"healthy":true"#));
+    }
+}
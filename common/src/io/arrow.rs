@@ -0,0 +1,283 @@
+use crate::io::{Error, SCORE_HEADERS, TRUST_HEADERS};
+use crate::tx::trust::{ScoreEntry, TrustEntry};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type};
+use arrow_array::{Array, ArrayRef, Float32Array, RecordBatch, StringArray};
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::io::{Read, Seek, Write};
+use std::sync::Arc;
+
+/// Number of rows written per record batch.
+pub const BATCH_SIZE: usize = 64 * 1024;
+
+/// Schema written for trust datasets.
+pub fn trust_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new(TRUST_HEADERS[0], DataType::Utf8, false),
+        Field::new(TRUST_HEADERS[1], DataType::Utf8, false),
+        Field::new(TRUST_HEADERS[2], DataType::Float32, false),
+    ]))
+}
+
+/// Schema written for seed and score datasets.
+pub fn score_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new(SCORE_HEADERS[0], DataType::Utf8, false),
+        Field::new(SCORE_HEADERS[1], DataType::Float32, false),
+    ]))
+}
+
+/// Looks up a column by name.
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef, Error> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| Error::MissingColumn(name.to_string()))
+}
+
+/// Reads a string column, accepting both `Utf8` and `LargeUtf8` arrays.
+fn string_column(batch: &RecordBatch, name: &str, first_row: u64) -> Result<Vec<String>, Error> {
+    let array = column(batch, name)?;
+    let values: Vec<Option<&str>> = match array.data_type() {
+        DataType::Utf8 => array.as_string::<i32>().iter().collect(),
+        DataType::LargeUtf8 => array.as_string::<i64>().iter().collect(),
+        found => {
+            return Err(Error::ColumnType {
+                column: name.to_string(),
+                found: found.to_string(),
+            })
+        }
+    };
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, x)| {
+            x.map(|x| x.to_string()).ok_or_else(|| Error::NullValue {
+                row: first_row + i as u64,
+                column: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Reads a value column, accepting both `Float32` and `Float64` arrays.
+fn value_column(batch: &RecordBatch, name: &str, first_row: u64) -> Result<Vec<f32>, Error> {
+    let array = column(batch, name)?;
+    let values: Vec<Option<f32>> = match array.data_type() {
+        DataType::Float32 => array.as_primitive::<Float32Type>().iter().collect(),
+        DataType::Float64 => array
+            .as_primitive::<Float64Type>()
+            .iter()
+            .map(|x| x.map(|x| x as f32))
+            .collect(),
+        found => {
+            return Err(Error::ColumnType {
+                column: name.to_string(),
+                found: found.to_string(),
+            })
+        }
+    };
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, x)| {
+            x.ok_or_else(|| Error::NullValue {
+                row: first_row + i as u64,
+                column: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Converts a record batch into trust entries.
+/// `first_row` is the position of the first row of the batch in the dataset.
+pub fn trust_from_batch(batch: &RecordBatch, first_row: u64) -> Result<Vec<TrustEntry>, Error> {
+    let from = string_column(batch, TRUST_HEADERS[0], first_row)?;
+    let to = string_column(batch, TRUST_HEADERS[1], first_row)?;
+    let value = value_column(batch, TRUST_HEADERS[2], first_row)?;
+    let entries = from
+        .into_iter()
+        .zip(to)
+        .zip(value)
+        .map(|((from, to), value)| TrustEntry::new(from, to, value))
+        .collect();
+    Ok(entries)
+}
+
+/// Converts a record batch into seed or score entries.
+/// `first_row` is the position of the first row of the batch in the dataset.
+pub fn scores_from_batch(batch: &RecordBatch, first_row: u64) -> Result<Vec<ScoreEntry>, Error> {
+    let id = string_column(batch, SCORE_HEADERS[0], first_row)?;
+    let value = value_column(batch, SCORE_HEADERS[1], first_row)?;
+    let entries = id
+        .into_iter()
+        .zip(value)
+        .map(|(id, value)| ScoreEntry::new(id, value))
+        .collect();
+    Ok(entries)
+}
+
+/// Converts trust entries into a record batch with the [`trust_schema`].
+pub fn trust_to_batch(entries: &[TrustEntry]) -> Result<RecordBatch, Error> {
+    let from = StringArray::from_iter_values(entries.iter().map(|x| x.from()));
+    let to = StringArray::from_iter_values(entries.iter().map(|x| x.to()));
+    let value = Float32Array::from_iter_values(entries.iter().map(|x| *x.value()));
+    RecordBatch::try_new(
+        trust_schema(),
+        vec![Arc::new(from), Arc::new(to), Arc::new(value)],
+    )
+    .map_err(Error::Arrow)
+}
+
+/// Converts seed or score entries into a record batch with the [`score_schema`].
+pub fn scores_to_batch(entries: &[ScoreEntry]) -> Result<RecordBatch, Error> {
+    let id = StringArray::from_iter_values(entries.iter().map(|x| x.id()));
+    let value = Float32Array::from_iter_values(entries.iter().map(|x| *x.value()));
+    RecordBatch::try_new(score_schema(), vec![Arc::new(id), Arc::new(value)]).map_err(Error::Arrow)
+}
+
+/// Streaming reader of entries from a sequence of record batches.
+/// Only one batch is converted at a time, and row positions are tracked for errors.
+pub struct BatchEntries<T, I> {
+    batches: I,
+    convert: fn(&RecordBatch, u64) -> Result<Vec<T>, Error>,
+    buffer: std::vec::IntoIter<T>,
+    next_row: u64,
+}
+
+impl<T, I: Iterator<Item = Result<RecordBatch, Error>>> BatchEntries<T, I> {
+    pub fn new(batches: I, convert: fn(&RecordBatch, u64) -> Result<Vec<T>, Error>) -> Self {
+        Self {
+            batches,
+            convert,
+            buffer: Vec::new().into_iter(),
+            next_row: 1,
+        }
+    }
+}
+
+impl<T, I: Iterator<Item = Result<RecordBatch, Error>>> Iterator for BatchEntries<T, I> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.buffer.next() {
+                return Some(Ok(entry));
+            }
+            let batch = match self.batches.next()? {
+                Ok(batch) => batch,
+                Err(e) => return Some(Err(e)),
+            };
+            let entries = match (self.convert)(&batch, self.next_row) {
+                Ok(entries) => entries,
+                Err(e) => return Some(Err(e)),
+            };
+            self.next_row += batch.num_rows() as u64;
+            self.buffer = entries.into_iter();
+        }
+    }
+}
+
+/// Streaming reader of trust datasets in the Arrow IPC file format.
+pub fn trust_reader<R: Read + Seek>(
+    reader: R,
+) -> Result<BatchEntries<TrustEntry, impl Iterator<Item = Result<RecordBatch, Error>>>, Error> {
+    let reader = FileReader::try_new(reader, None).map_err(Error::Arrow)?;
+    Ok(BatchEntries::new(
+        reader.map(|x| x.map_err(Error::Arrow)),
+        trust_from_batch,
+    ))
+}
+
+/// Streaming reader of seed and score datasets in the Arrow IPC file format.
+pub fn scores_reader<R: Read + Seek>(
+    reader: R,
+) -> Result<BatchEntries<ScoreEntry, impl Iterator<Item = Result<RecordBatch, Error>>>, Error> {
+    let reader = FileReader::try_new(reader, None).map_err(Error::Arrow)?;
+    Ok(BatchEntries::new(
+        reader.map(|x| x.map_err(Error::Arrow)),
+        scores_from_batch,
+    ))
+}
+
+/// Reads a whole trust dataset in the Arrow IPC file format.
+pub fn read_trust<R: Read + Seek>(reader: R) -> Result<Vec<TrustEntry>, Error> {
+    trust_reader(reader)?.collect()
+}
+
+/// Reads a whole seed or score dataset in the Arrow IPC file format.
+pub fn read_scores<R: Read + Seek>(reader: R) -> Result<Vec<ScoreEntry>, Error> {
+    scores_reader(reader)?.collect()
+}
+
+/// Writes a whole trust dataset in the Arrow IPC file format.
+pub fn write_trust<W: Write>(writer: W, entries: &[TrustEntry]) -> Result<W, Error> {
+    let mut writer = FileWriter::try_new(writer, &trust_schema()).map_err(Error::Arrow)?;
+    for chunk in entries.chunks(BATCH_SIZE) {
+        writer
+            .write(&trust_to_batch(chunk)?)
+            .map_err(Error::Arrow)?;
+    }
+    writer.finish().map_err(Error::Arrow)?;
+    writer.into_inner().map_err(Error::Arrow)
+}
+
+/// Writes a whole seed or score dataset in the Arrow IPC file format.
+pub fn write_scores<W: Write>(writer: W, entries: &[ScoreEntry]) -> Result<W, Error> {
+    let mut writer = FileWriter::try_new(writer, &score_schema()).map_err(Error::Arrow)?;
+    for chunk in entries.chunks(BATCH_SIZE) {
+        writer
+            .write(&scores_to_batch(chunk)?)
+            .map_err(Error::Arrow)?;
+    }
+    writer.finish().map_err(Error::Arrow)?;
+    writer.into_inner().map_err(Error::Arrow)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::arrow::{read_scores, read_trust, trust_from_batch, write_scores, write_trust};
+    use crate::io::Error;
+    use crate::tx::trust::{ScoreEntry, TrustEntry};
+    use arrow_array::{Float64Array, LargeStringArray, RecordBatch, StringArray};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn should_write_and_read_ipc() {
+        let trust = vec![
+            TrustEntry::new("a".to_string(), "b".to_string(), 1.5),
+            TrustEntry::new("b".to_string(), "c".to_string(), 0.25),
+        ];
+        let scores = vec![
+            ScoreEntry::new("a".to_string(), 0.75),
+            ScoreEntry::new("b".to_string(), 0.25),
+        ];
+
+        let trust_bytes = write_trust(Vec::new(), &trust).unwrap();
+        let scores_bytes = write_scores(Vec::new(), &scores).unwrap();
+
+        assert_eq!(read_trust(Cursor::new(trust_bytes)).unwrap(), trust);
+        assert_eq!(read_scores(Cursor::new(scores_bytes)).unwrap(), scores);
+    }
+
+    #[test]
+    fn should_accept_wide_columns_and_reject_nulls() {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("i", Arc::new(LargeStringArray::from(vec!["a", "b"])) as _),
+            ("j", Arc::new(StringArray::from(vec![Some("b"), None])) as _),
+            ("v", Arc::new(Float64Array::from(vec![1.5, 0.25])) as _),
+        ])
+        .unwrap();
+
+        let first = trust_from_batch(&batch.slice(0, 1), 1).unwrap();
+        assert_eq!(
+            first,
+            vec![TrustEntry::new("a".to_string(), "b".to_string(), 1.5)]
+        );
+
+        let res = trust_from_batch(&batch, 10);
+        assert!(matches!(res, Err(Error::NullValue { row: 11, .. })));
+    }
+}
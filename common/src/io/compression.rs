@@ -0,0 +1,289 @@
+use crate::io::Error;
+use flate2::write::{GzDecoder, GzEncoder};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Compression level used for zstd. Favours speed, since datasets are compressed on every upload.
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Compression applied to datasets in transport.
+/// Content ids are always computed over the uncompressed bytes.
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    /// Detects the compression from the magic bytes at the start of the data.
+    /// CSV, Parquet, Arrow and JSON data can't start with either magic, so anything else is
+    /// treated as uncompressed.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&ZSTD_MAGIC) {
+            Self::Zstd
+        } else if data.starts_with(&GZIP_MAGIC) {
+            Self::Gzip
+        } else {
+            Self::None
+        }
+    }
+
+    /// Value of the `Content-Encoding` header for the compressed data.
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Zstd => Some("zstd"),
+            Self::Gzip => Some("gzip"),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "zstd" => Ok(Self::Zstd),
+            "gzip" => Ok(Self::Gzip),
+            _ => Err(Error::UnknownCompression(s.to_string())),
+        }
+    }
+}
+
+/// Compresses the data.
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>, Error> {
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Zstd => zstd::encode_all(data, ZSTD_LEVEL).map_err(Error::Io),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).map_err(Error::Io)?;
+            encoder.finish().map_err(Error::Io)
+        }
+    }
+}
+
+/// Compresses everything read from the reader, without buffering the uncompressed data.
+pub fn compress_reader<R: Read>(mut reader: R, compression: Compression) -> Result<Vec<u8>, Error> {
+    match compression {
+        Compression::None => {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).map_err(Error::Io)?;
+            Ok(data)
+        }
+        Compression::Zstd => zstd::encode_all(reader, ZSTD_LEVEL).map_err(Error::Io),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            std::io::copy(&mut reader, &mut encoder).map_err(Error::Io)?;
+            encoder.finish().map_err(Error::Io)
+        }
+    }
+}
+
+/// Decompresses the data, detecting the compression from its magic bytes.
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    match Compression::detect(&data) {
+        Compression::None => Ok(data),
+        _ => {
+            let mut writer = DecompressWriter::new(Vec::new());
+            writer.write_all(&data).map_err(Error::Io)?;
+            writer.finish()
+        }
+    }
+}
+
+enum State<W: Write> {
+    /// Not enough bytes seen yet to detect the compression.
+    Pending(W, Vec<u8>),
+    Plain(W),
+    Zstd(zstd::stream::write::Decoder<'static, W>),
+    Gzip(GzDecoder<W>),
+}
+
+/// Writer that decompresses the data written into it, detecting the compression from the
+/// first bytes. Used to decompress downloads while they are streamed to disk.
+///
+/// Once a write to the underlying writer failed, every later write fails too.
+pub struct DecompressWriter<W: Write> {
+    state: Option<State<W>>,
+}
+
+impl<W: Write> DecompressWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            state: Some(State::Pending(writer, Vec::new())),
+        }
+    }
+
+    /// Picks the decoder once the magic bytes are known, and forwards the buffered bytes.
+    fn start(&mut self, eof: bool) -> std::io::Result<()> {
+        let (writer, head) = match self.state.take() {
+            Some(State::Pending(writer, head)) => (writer, head),
+            other => {
+                self.state = other;
+                return Ok(());
+            }
+        };
+        if head.len() < ZSTD_MAGIC.len() && !eof {
+            self.state = Some(State::Pending(writer, head));
+            return Ok(());
+        }
+        let mut state = match Compression::detect(&head) {
+            Compression::None => State::Plain(writer),
+            Compression::Zstd => State::Zstd(zstd::stream::write::Decoder::new(writer)?),
+            Compression::Gzip => State::Gzip(GzDecoder::new(writer)),
+        };
+        match &mut state {
+            State::Pending(..) => unreachable!(),
+            State::Plain(w) => w.write_all(&head)?,
+            State::Zstd(w) => w.write_all(&head)?,
+            State::Gzip(w) => w.write_all(&head)?,
+        }
+        self.state = Some(state);
+        Ok(())
+    }
+
+    /// Flushes the decoder and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.start(true).map_err(Error::Io)?;
+        match self.state.take() {
+            Some(State::Plain(mut w)) => {
+                w.flush().map_err(Error::Io)?;
+                Ok(w)
+            }
+            Some(State::Zstd(mut w)) => {
+                w.flush().map_err(Error::Io)?;
+                Ok(w.into_inner())
+            }
+            Some(State::Gzip(w)) => w.finish().map_err(Error::Io),
+            Some(State::Pending(..)) => unreachable!(),
+            None => Err(Error::Io(failed())),
+        }
+    }
+}
+
+/// Error of the writes following a failed one, whose decoder is lost.
+fn failed() -> std::io::Error {
+    std::io::Error::other("a previous write of the decompressed data failed")
+}
+
+impl<W: Write> Write for DecompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(State::Pending(_, head)) = &mut self.state {
+            head.extend_from_slice(buf);
+            self.start(false)?;
+            return Ok(buf.len());
+        }
+        match &mut self.state {
+            Some(State::Plain(w)) => w.write(buf),
+            Some(State::Zstd(w)) => w.write(buf),
+            Some(State::Gzip(w)) => w.write(buf),
+            Some(State::Pending(..)) => unreachable!(),
+            None => Err(failed()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.state {
+            Some(State::Plain(w)) => w.flush(),
+            Some(State::Zstd(w)) => w.flush(),
+            Some(State::Gzip(w)) => w.flush(),
+            Some(State::Pending(..)) | None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::compression::{
+        compress, compress_reader, decompress, Compression, DecompressWriter,
+    };
+    use std::io::Write;
+
+    #[test]
+    fn should_round_trip_every_compression() {
+        let data = "i,j,v\na,b,1.5\nb,c,0.25\n".repeat(100).into_bytes();
+        for compression in [Compression::None, Compression::Zstd, Compression::Gzip] {
+            let compressed = compress(&data, compression).unwrap();
+            assert_eq!(Compression::detect(&compressed), compression);
+            assert_eq!(decompress(compressed).unwrap(), data);
+            let streamed = compress_reader(data.as_slice(), compression).unwrap();
+            assert_eq!(decompress(streamed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn should_decompress_streamed_chunks() {
+        let data = "i,v\na,0.75\nb,0.25\n".repeat(100).into_bytes();
+        for compression in [Compression::None, Compression::Zstd, Compression::Gzip] {
+            let compressed = compress(&data, compression).unwrap();
+            let mut writer = DecompressWriter::new(Vec::new());
+            // Split the magic bytes over several writes.
+            for chunk in compressed.chunks(3) {
+                writer.write_all(chunk).unwrap();
+            }
+            assert_eq!(writer.finish().unwrap(), data);
+        }
+
+        // Shorter than the magic bytes.
+        let mut writer = DecompressWriter::new(Vec::new());
+        writer.write_all(b"i").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"i".to_vec());
+    }
+
+    #[test]
+    fn should_parse_compression_names() {
+        assert_eq!("ZSTD".parse::<Compression>().unwrap(), Compression::Zstd);
+        assert_eq!("gzip".parse::<Compression>().unwrap(), Compression::Gzip);
+        assert!("brotli".parse::<Compression>().is_err());
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_keep_failing_after_a_failed_write() {
+        let data = "i,j,v\na,b,1.5\n".repeat(100).into_bytes();
+        // The buffered head is forwarded as soon as the compression is known.
+        let mut writer = DecompressWriter::new(FailingWriter);
+        assert!(writer.write_all(&data).is_err());
+        assert!(writer.write_all(&data).is_err());
+        assert!(writer.finish().is_err());
+
+        // The decoders may buffer their output until they are finished.
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            let compressed = compress(&data, compression).unwrap();
+            let mut writer = DecompressWriter::new(FailingWriter);
+            let _ = writer.write_all(&compressed);
+            assert!(writer.finish().is_err());
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::io::{CsvConfig, Error, SCORE_HEADERS, TRUST_HEADERS};
+use crate::tx::trust::{ScoreEntry, TrustEntry};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use std::io::{Read, Write};
+
+/// Reads records with a fixed number of columns, reporting errors with their position.
+struct RecordReader<R: Read> {
+    reader: csv::Reader<R>,
+    record: StringRecord,
+    num_columns: usize,
+    has_headers: bool,
+    headers_checked: bool,
+}
+
+impl<R: Read> RecordReader<R> {
+    fn new(reader: R, config: &CsvConfig, num_columns: usize) -> Self {
+        let reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(*config.delimiter())
+            .from_reader(reader);
+        Self {
+            reader,
+            record: StringRecord::new(),
+            num_columns,
+            has_headers: *config.has_headers(),
+            headers_checked: false,
+        }
+    }
+
+    /// Reads the next record, returning its line number.
+    fn read(&mut self) -> Option<Result<u64, Error>> {
+        if self.has_headers && !self.headers_checked {
+            self.headers_checked = true;
+            match self.read_raw()? {
+                Ok(line) if self.record.len() != self.num_columns => {
+                    return Some(Err(Error::HeaderMismatch {
+                        line,
+                        expected: self.num_columns,
+                        found: self.record.iter().map(|x| x.to_string()).collect(),
+                    }))
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let line = match self.read_raw()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        if self.record.len() != self.num_columns {
+            return Some(Err(Error::ColumnCount {
+                line,
+                expected: self.num_columns,
+                found: self.record.len(),
+            }));
+        }
+        Some(Ok(line))
+    }
+
+    fn read_raw(&mut self) -> Option<Result<u64, Error>> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(Ok(self.line())),
+            Ok(false) => None,
+            Err(e) => Some(Err(csv_error(e))),
+        }
+    }
+
+    fn line(&self) -> u64 {
+        self.record.position().map(|x| x.line()).unwrap_or(0)
+    }
+
+    fn field(&self, column: usize) -> String {
+        self.record[column].to_string()
+    }
+
+    fn value(&self, line: u64, column: usize) -> Result<f32, Error> {
+        let value = self.record[column].trim();
+        value.parse().map_err(|_| Error::InvalidValue {
+            line,
+            column: column + 1,
+            value: value.to_string(),
+        })
+    }
+}
+
+fn csv_error(e: csv::Error) -> Error {
+    let line = e.position().map(|x| x.line()).unwrap_or(0);
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => Error::Io(e),
+        kind => Error::Csv {
+            line,
+            message: format!("{:?}", kind),
+        },
+    }
+}
+
+/// Streaming reader of trust datasets, with `from,to,value` records.
+pub struct TrustReader<R: Read> {
+    inner: RecordReader<R>,
+}
+
+impl<R: Read> TrustReader<R> {
+    pub fn new(reader: R, config: &CsvConfig) -> Self {
+        Self {
+            inner: RecordReader::new(reader, config, TRUST_HEADERS.len()),
+        }
+    }
+
+    /// Returns the line of the last record read, or 0 before the first one.
+    pub fn line(&self) -> u64 {
+        self.inner.line()
+    }
+}
+
+impl<R: Read> Iterator for TrustReader<R> {
+    type Item = Result<TrustEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.inner.read()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let entry = self
+            .inner
+            .value(line, 2)
+            .map(|value| TrustEntry::new(self.inner.field(0), self.inner.field(1), value));
+        Some(entry)
+    }
+}
+
+/// Streaming reader of seed and score datasets, with `id,value` records.
+pub struct ScoreReader<R: Read> {
+    inner: RecordReader<R>,
+}
+
+impl<R: Read> ScoreReader<R> {
+    pub fn new(reader: R, config: &CsvConfig) -> Self {
+        Self {
+            inner: RecordReader::new(reader, config, SCORE_HEADERS.len()),
+        }
+    }
+
+    /// Returns the line of the last record read, or 0 before the first one.
+    pub fn line(&self) -> u64 {
+        self.inner.line()
+    }
+}
+
+impl<R: Read> Iterator for ScoreReader<R> {
+    type Item = Result<ScoreEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.inner.read()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let entry = self
+            .inner
+            .value(line, 1)
+            .map(|value| ScoreEntry::new(self.inner.field(0), value));
+        Some(entry)
+    }
+}
+
+/// Streaming reader of seed datasets.
+pub type SeedReader<R> = ScoreReader<R>;
+
+/// Streaming writer of trust datasets.
+pub struct TrustWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> TrustWriter<W> {
+    pub fn new(writer: W, config: &CsvConfig) -> Result<Self, Error> {
+        let mut writer = WriterBuilder::new()
+            .delimiter(*config.delimiter())
+            .from_writer(writer);
+        if *config.has_headers() {
+            writer.write_record(TRUST_HEADERS).map_err(csv_error)?;
+        }
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, entry: &TrustEntry) -> Result<(), Error> {
+        self.writer
+            .write_record([entry.from(), entry.to(), &entry.value().to_string()])
+            .map_err(csv_error)
+    }
+
+    /// Flushes the records and returns the underlying writer.
+    pub fn finish(self) -> Result<W, Error> {
+        self.writer
+            .into_inner()
+            .map_err(|e| Error::Io(e.into_error()))
+    }
+}
+
+/// Streaming writer of seed and score datasets.
+pub struct ScoreWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> ScoreWriter<W> {
+    pub fn new(writer: W, config: &CsvConfig) -> Result<Self, Error> {
+        let mut writer = WriterBuilder::new()
+            .delimiter(*config.delimiter())
+            .from_writer(writer);
+        if *config.has_headers() {
+            writer.write_record(SCORE_HEADERS).map_err(csv_error)?;
+        }
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, entry: &ScoreEntry) -> Result<(), Error> {
+        self.writer
+            .write_record([entry.id(), &entry.value().to_string()])
+            .map_err(csv_error)
+    }
+
+    /// Flushes the records and returns the underlying writer.
+    pub fn finish(self) -> Result<W, Error> {
+        self.writer
+            .into_inner()
+            .map_err(|e| Error::Io(e.into_error()))
+    }
+}
+
+/// Reads a whole trust dataset.
+pub fn read_trust<R: Read>(reader: R, config: &CsvConfig) -> Result<Vec<TrustEntry>, Error> {
+    TrustReader::new(reader, config).collect()
+}
+
+/// Reads a whole seed or score dataset.
+pub fn read_scores<R: Read>(reader: R, config: &CsvConfig) -> Result<Vec<ScoreEntry>, Error> {
+    ScoreReader::new(reader, config).collect()
+}
+
+/// Writes a whole seed or score dataset.
+pub fn write_scores<W: Write>(
+    writer: W,
+    config: &CsvConfig,
+    entries: &[ScoreEntry],
+) -> Result<W, Error> {
+    let mut writer = ScoreWriter::new(writer, config)?;
+    for entry in entries {
+        writer.write(entry)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::csv::{read_scores, read_trust, write_scores, TrustReader};
+    use crate::io::{CsvConfig, Error};
+    use crate::tx::trust::{ScoreEntry, TrustEntry};
+
+    #[test]
+    fn should_read_trust_with_headers() {
+        let data = "i,j,v\na,b,1.5\nb,c,0.25\n";
+        let entries = read_trust(data.as_bytes(), &CsvConfig::default()).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                TrustEntry::new("a".to_string(), "b".to_string(), 1.5),
+                TrustEntry::new("b".to_string(), "c".to_string(), 0.25),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_read_headerless_with_delimiter() {
+        let data = "a;0.5\nb;0.5\n";
+        let entries = read_scores(data.as_bytes(), &CsvConfig::new(false, b';')).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ScoreEntry::new("a".to_string(), 0.5),
+                ScoreEntry::new("b".to_string(), 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_position_of_errors() {
+        let data = "i,j\na,b,1.0\n";
+        let res = read_trust(data.as_bytes(), &CsvConfig::default());
+        assert!(matches!(res, Err(Error::HeaderMismatch { line: 1, .. })));
+
+        let data = "i,j,v\na,b,1.0\na,c,x\n";
+        let res = read_trust(data.as_bytes(), &CsvConfig::default());
+        assert!(matches!(
+            res,
+            Err(Error::InvalidValue {
+                line: 3,
+                column: 3,
+                ..
+            })
+        ));
+
+        let data = "a,b,1.0\na,c\n";
+        let mut reader = TrustReader::new(data.as_bytes(), &CsvConfig::new(false, b','));
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next().unwrap(),
+            Err(Error::ColumnCount {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn should_write_and_read_scores() {
+        let entries = vec![
+            ScoreEntry::new("a".to_string(), 0.75),
+            ScoreEntry::new("b".to_string(), 0.25),
+        ];
+        let bytes = write_scores(Vec::new(), &CsvConfig::default(), &entries).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "i,v\na,0.75\nb,0.25\n"
+        );
+        assert_eq!(
+            read_scores(bytes.as_slice(), &CsvConfig::default()).unwrap(),
+            entries
+        );
+    }
+}
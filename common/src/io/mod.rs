@@ -0,0 +1,442 @@
+//! Readers and writers for the trust, seed and score datasets.
+
+use crate::config::{self, Settings};
+use crate::merkle::Hash;
+use crate::runners::interner::Error as InternerError;
+use crate::tx::canonical::IdHasher;
+use crate::tx::trust::{ScoreEntry, TrustEntry};
+use bytes::Bytes;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+
+pub mod arrow;
+pub mod compression;
+pub mod csv;
+pub mod parquet;
+pub mod validate;
+
+/// Column headers written for trust datasets.
+pub const TRUST_HEADERS: [&str; 3] = ["i", "j", "v"];
+/// Column headers written for seed and score datasets.
+pub const SCORE_HEADERS: [&str; 2] = ["i", "v"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Getters, Serialize, Deserialize)]
+#[getset(get = "pub")]
+/// Configuration of the dataset readers and writers.
+pub struct CsvConfig {
+    /// Whether the first line of the dataset is a header.
+    has_headers: bool,
+    /// Field delimiter.
+    delimiter: u8,
+}
+
+impl CsvConfig {
+    pub fn new(has_headers: bool, delimiter: u8) -> Self {
+        Self {
+            has_headers,
+            delimiter,
+        }
+    }
+
+    /// Reads the config from the `CSV_HAS_HEADERS` and `CSV_DELIMITER` settings, falling back
+    /// to the defaults. The delimiter must be a single ASCII character.
+    pub fn from_settings(settings: &Settings) -> Result<Self, config::Error> {
+        let default = Self::default();
+        let has_headers = settings.parse("CSV_HAS_HEADERS")?;
+        let delimiter = match settings.get("CSV_DELIMITER") {
+            Some(value) => match value.as_bytes() {
+                [x] if x.is_ascii() => *x,
+                _ => {
+                    return Err(config::Error::Invalid {
+                        name: "CSV_DELIMITER".to_string(),
+                        value,
+                        reason: "expected a single ASCII character".to_string(),
+                    })
+                }
+            },
+            None => default.delimiter,
+        };
+        Ok(Self::new(
+            has_headers.unwrap_or(default.has_headers),
+            delimiter,
+        ))
+    }
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self::new(true, b',')
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Encoding of a trust, seed or score dataset.
+pub enum DataFormat {
+    #[default]
+    Csv,
+    Parquet,
+    ArrowIpc,
+}
+
+impl DataFormat {
+    /// Guesses the format from the file extension, falling back to CSV.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let extension = path.as_ref().extension().and_then(|x| x.to_str());
+        match extension.map(|x| x.to_ascii_lowercase()).as_deref() {
+            Some("parquet") => Self::Parquet,
+            Some("arrow") | Some("ipc") | Some("feather") => Self::ArrowIpc,
+            _ => Self::Csv,
+        }
+    }
+}
+
+/// Streaming reader of trust entries.
+pub type TrustEntries = Box<dyn Iterator<Item = Result<TrustEntry, Error>> + Send>;
+/// Streaming reader of seed or score entries.
+pub type ScoreEntries = Box<dyn Iterator<Item = Result<ScoreEntry, Error>> + Send>;
+
+/// Streams a trust dataset from a file. At most one record batch is buffered.
+pub fn stream_trust_file(
+    format: DataFormat,
+    config: &CsvConfig,
+    file: File,
+) -> Result<TrustEntries, Error> {
+    Ok(match format {
+        DataFormat::Csv => Box::new(csv::TrustReader::new(file, config)),
+        DataFormat::Parquet => Box::new(parquet::trust_reader(file)?),
+        DataFormat::ArrowIpc => Box::new(arrow::trust_reader(file)?),
+    })
+}
+
+/// Streams a seed or score dataset from a file. At most one record batch is buffered.
+pub fn stream_scores_file(
+    format: DataFormat,
+    config: &CsvConfig,
+    file: File,
+) -> Result<ScoreEntries, Error> {
+    Ok(match format {
+        DataFormat::Csv => Box::new(csv::ScoreReader::new(file, config)),
+        DataFormat::Parquet => Box::new(parquet::scores_reader(file)?),
+        DataFormat::ArrowIpc => Box::new(arrow::scores_reader(file)?),
+    })
+}
+
+/// Streams a trust dataset from memory.
+pub fn stream_trust_bytes(
+    format: DataFormat,
+    config: &CsvConfig,
+    data: Vec<u8>,
+) -> Result<TrustEntries, Error> {
+    Ok(match format {
+        DataFormat::Csv => Box::new(csv::TrustReader::new(Cursor::new(data), config)),
+        DataFormat::Parquet => Box::new(parquet::trust_reader(Bytes::from(data))?),
+        DataFormat::ArrowIpc => Box::new(arrow::trust_reader(Cursor::new(data))?),
+    })
+}
+
+/// Streams a seed or score dataset from memory.
+pub fn stream_scores_bytes(
+    format: DataFormat,
+    config: &CsvConfig,
+    data: Vec<u8>,
+) -> Result<ScoreEntries, Error> {
+    Ok(match format {
+        DataFormat::Csv => Box::new(csv::ScoreReader::new(Cursor::new(data), config)),
+        DataFormat::Parquet => Box::new(parquet::scores_reader(Bytes::from(data))?),
+        DataFormat::ArrowIpc => Box::new(arrow::scores_reader(Cursor::new(data))?),
+    })
+}
+
+/// Reads a whole trust dataset from a file.
+pub fn read_trust_file(
+    format: DataFormat,
+    config: &CsvConfig,
+    file: File,
+) -> Result<Vec<TrustEntry>, Error> {
+    stream_trust_file(format, config, file)?.collect()
+}
+
+/// Reads a whole seed or score dataset from a file.
+pub fn read_scores_file(
+    format: DataFormat,
+    config: &CsvConfig,
+    file: File,
+) -> Result<Vec<ScoreEntry>, Error> {
+    stream_scores_file(format, config, file)?.collect()
+}
+
+/// Reads a whole trust dataset from memory.
+pub fn read_trust_bytes(
+    format: DataFormat,
+    config: &CsvConfig,
+    data: Vec<u8>,
+) -> Result<Vec<TrustEntry>, Error> {
+    stream_trust_bytes(format, config, data)?.collect()
+}
+
+/// Reads a whole seed or score dataset from memory.
+pub fn read_scores_bytes(
+    format: DataFormat,
+    config: &CsvConfig,
+    data: Vec<u8>,
+) -> Result<Vec<ScoreEntry>, Error> {
+    stream_scores_bytes(format, config, data)?.collect()
+}
+
+/// Adapter that stops at the first error of a dataset stream, so that a dataset can be
+/// streamed straight into a runner. The error is then returned by [`EntryReader::finish`].
+pub struct EntryReader<I> {
+    entries: I,
+    error: Option<Error>,
+}
+
+impl<I> EntryReader<I> {
+    pub fn new(entries: I) -> Self {
+        Self {
+            entries,
+            error: None,
+        }
+    }
+
+    /// Returns the error that stopped the reader, if any.
+    pub fn finish(self) -> Result<(), Error> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T, I> Iterator for EntryReader<I>
+where
+    I: Iterator<Item = Result<T, Error>>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.entries.next()? {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// Adapter that computes the content id of the entries passing through it, for datasets that
+/// are not loaded into a runner, e.g. before an upload.
+///
+/// Iteration stops at the first error, which is then returned by [`HashingReader::finish`].
+pub struct HashingReader<I, H> {
+    entries: I,
+    hasher: H,
+    error: Option<Error>,
+}
+
+impl<I, H> HashingReader<I, H> {
+    pub fn new(entries: I, hasher: H) -> Self {
+        Self {
+            entries,
+            hasher,
+            error: None,
+        }
+    }
+}
+
+impl<I, H: IdHasher> HashingReader<I, H> {
+    /// Returns the content id of the entries read so far, or the error that stopped the reader.
+    pub fn finish(self) -> Result<Hash, Error> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.hasher.finalize()),
+        }
+    }
+}
+
+impl<T, I, H> Iterator for HashingReader<I, H>
+where
+    I: Iterator<Item = Result<T, Error>>,
+    H: IdHasher<Entry = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.entries.next()? {
+            Ok(entry) => {
+                if let Err(e) = self.hasher.update(&entry) {
+                    self.error = Some(Error::Interner(e));
+                    return None;
+                }
+                Some(entry)
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// Writes a whole seed or score dataset into memory.
+pub fn write_scores_bytes(
+    format: DataFormat,
+    config: &CsvConfig,
+    entries: &[ScoreEntry],
+) -> Result<Vec<u8>, Error> {
+    match format {
+        DataFormat::Csv => csv::write_scores(Vec::new(), config, entries),
+        DataFormat::Parquet => parquet::write_scores(Vec::new(), entries),
+        DataFormat::ArrowIpc => arrow::write_scores(Vec::new(), entries),
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+/// Errors that can arise while reading or writing datasets.
+/// Line, column and row numbers start from 1.
+pub enum Error {
+    /// The underlying reader or writer failed.
+    #[error("I/O error: {0}")]
+    Io(std::io::Error),
+    /// The record could not be parsed as CSV.
+    #[error("CSV error at line {line}: {message}")]
+    Csv { line: u64, message: String },
+    /// The header does not have the expected number of columns.
+    #[error("Header mismatch at line {line}: expected {expected} columns, found {found:?}")]
+    HeaderMismatch {
+        line: u64,
+        expected: usize,
+        found: Vec<String>,
+    },
+    /// The record does not have the expected number of columns.
+    #[error("Unexpected number of columns at line {line}: expected {expected}, found {found}")]
+    ColumnCount {
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
+    /// The value in the column could not be parsed.
+    #[error("Invalid value at line {line}, column {column}: '{value}'")]
+    InvalidValue {
+        line: u64,
+        column: usize,
+        value: String,
+    },
+    /// The Arrow data could not be read or written.
+    #[error("Arrow error: {0}")]
+    Arrow(arrow_schema::ArrowError),
+    /// The Parquet data could not be read or written.
+    #[error("Parquet error: {0}")]
+    Parquet(::parquet::errors::ParquetError),
+    /// The compression name is not one of `none`, `zstd` or `gzip`.
+    #[error("Unknown compression: '{0}'")]
+    UnknownCompression(String),
+    /// A required column is not part of the schema.
+    #[error("Missing column '{0}'")]
+    MissingColumn(String),
+    /// The column has a type that can't be converted to the entry field.
+    #[error("Unsupported type for column '{column}': {found}")]
+    ColumnType { column: String, found: String },
+    /// The column has a null value.
+    #[error("Null value at row {row}, column '{column}'")]
+    NullValue { row: u64, column: String },
+    /// The peer ids of the entries couldn't be interned while hashing them.
+    #[error("{0}")]
+    Interner(InternerError),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Error as ConfigError, Settings};
+    use crate::io::{
+        read_scores_bytes, stream_trust_bytes, write_scores_bytes, CsvConfig, DataFormat,
+        EntryReader, Error, HashingReader,
+    };
+    use crate::tx::canonical::{trust_id, TrustIdHasher};
+    use crate::tx::trust::{ScoreEntry, TrustEntry};
+
+    #[test]
+    fn should_pick_format_from_path() {
+        assert_eq!(DataFormat::from_path("trust/a.csv"), DataFormat::Csv);
+        assert_eq!(DataFormat::from_path("trust/a"), DataFormat::Csv);
+        assert_eq!(DataFormat::from_path("a.PARQUET"), DataFormat::Parquet);
+        assert_eq!(DataFormat::from_path("a.arrow"), DataFormat::ArrowIpc);
+    }
+
+    #[test]
+    fn should_round_trip_scores_in_every_format() {
+        let scores = vec![
+            ScoreEntry::new("a".to_string(), 0.75),
+            ScoreEntry::new("b".to_string(), 0.25),
+        ];
+        let csv = CsvConfig::default();
+        for format in [DataFormat::Csv, DataFormat::Parquet, DataFormat::ArrowIpc] {
+            let data = write_scores_bytes(format, &csv, &scores).unwrap();
+            assert_eq!(read_scores_bytes(format, &csv, data).unwrap(), scores);
+        }
+    }
+
+    #[test]
+    fn should_read_csv_config_from_settings() {
+        let mut settings = Settings::from_toml("").unwrap();
+        assert_eq!(
+            CsvConfig::from_settings(&settings).unwrap(),
+            CsvConfig::default()
+        );
+
+        settings.set("CSV_HAS_HEADERS", "false".to_string());
+        settings.set("CSV_DELIMITER", ";".to_string());
+        let csv = CsvConfig::from_settings(&settings).unwrap();
+        assert_eq!(csv, CsvConfig::new(false, b';'));
+
+        let data = b"a;b;0.5\n".to_vec();
+        let entries = stream_trust_bytes(DataFormat::Csv, &csv, data).unwrap();
+        assert_eq!(entries.count(), 1);
+
+        settings.set("CSV_DELIMITER", "||".to_string());
+        let err = CsvConfig::from_settings(&settings).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { name, .. } if name == "CSV_DELIMITER"));
+    }
+
+    #[test]
+    fn should_hash_streamed_entries() {
+        let data = b"i,j,v\nb,a,0.5\na,b,0.25\n".to_vec();
+        let mut reader = HashingReader::new(
+            stream_trust_bytes(DataFormat::Csv, &CsvConfig::default(), data).unwrap(),
+            TrustIdHasher::new(),
+        );
+        let entries: Vec<TrustEntry> = reader.by_ref().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(reader.finish().unwrap(), trust_id(&entries));
+
+        let data = b"i,j,v\na,b,0.5\na,c,x\na,d,1\n".to_vec();
+        let mut reader = HashingReader::new(
+            stream_trust_bytes(DataFormat::Csv, &CsvConfig::default(), data).unwrap(),
+            TrustIdHasher::new(),
+        );
+        assert_eq!(reader.by_ref().count(), 1);
+        let res = reader.finish();
+        assert!(matches!(res, Err(Error::InvalidValue { line: 3, .. })));
+    }
+
+    #[test]
+    fn should_stop_reading_at_the_first_error() {
+        let data = b"i,j,v\na,b,0.5\na,c,x\na,d,1\n".to_vec();
+        let mut reader = EntryReader::new(
+            stream_trust_bytes(DataFormat::Csv, &CsvConfig::default(), data).unwrap(),
+        );
+        assert_eq!(reader.by_ref().count(), 1);
+        let res = reader.finish();
+        assert!(matches!(res, Err(Error::InvalidValue { line: 3, .. })));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::io::arrow::{
+    score_schema, scores_from_batch, scores_to_batch, trust_from_batch, trust_schema,
+    trust_to_batch, BatchEntries, BATCH_SIZE,
+};
+use crate::io::Error;
+use crate::tx::trust::{ScoreEntry, TrustEntry};
+use arrow_array::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::reader::ChunkReader;
+use std::io::Write;
+
+fn batches<R: ChunkReader + 'static>(
+    reader: R,
+) -> Result<impl Iterator<Item = Result<RecordBatch, Error>>, Error> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(reader)
+        .map_err(Error::Parquet)?
+        .with_batch_size(BATCH_SIZE)
+        .build()
+        .map_err(Error::Parquet)?;
+    Ok(reader.map(|x| x.map_err(Error::Arrow)))
+}
+
+/// Streaming reader of trust datasets in the Parquet format, one batch of rows at a time.
+pub fn trust_reader<R: ChunkReader + 'static>(
+    reader: R,
+) -> Result<BatchEntries<TrustEntry, impl Iterator<Item = Result<RecordBatch, Error>>>, Error> {
+    Ok(BatchEntries::new(batches(reader)?, trust_from_batch))
+}
+
+/// Streaming reader of seed and score datasets in the Parquet format, one batch of rows at a
+/// time.
+pub fn scores_reader<R: ChunkReader + 'static>(
+    reader: R,
+) -> Result<BatchEntries<ScoreEntry, impl Iterator<Item = Result<RecordBatch, Error>>>, Error> {
+    Ok(BatchEntries::new(batches(reader)?, scores_from_batch))
+}
+
+/// Reads a whole trust dataset in the Parquet format.
+pub fn read_trust<R: ChunkReader + 'static>(reader: R) -> Result<Vec<TrustEntry>, Error> {
+    trust_reader(reader)?.collect()
+}
+
+/// Reads a whole seed or score dataset in the Parquet format.
+pub fn read_scores<R: ChunkReader + 'static>(reader: R) -> Result<Vec<ScoreEntry>, Error> {
+    scores_reader(reader)?.collect()
+}
+
+/// Writes a whole trust dataset in the Parquet format.
+pub fn write_trust<W: Write + Send>(writer: W, entries: &[TrustEntry]) -> Result<W, Error> {
+    let mut writer = ArrowWriter::try_new(writer, trust_schema(), None).map_err(Error::Parquet)?;
+    for chunk in entries.chunks(BATCH_SIZE) {
+        writer
+            .write(&trust_to_batch(chunk)?)
+            .map_err(Error::Parquet)?;
+    }
+    writer.into_inner().map_err(Error::Parquet)
+}
+
+/// Writes a whole seed or score dataset in the Parquet format.
+pub fn write_scores<W: Write + Send>(writer: W, entries: &[ScoreEntry]) -> Result<W, Error> {
+    let mut writer = ArrowWriter::try_new(writer, score_schema(), None).map_err(Error::Parquet)?;
+    for chunk in entries.chunks(BATCH_SIZE) {
+        writer
+            .write(&scores_to_batch(chunk)?)
+            .map_err(Error::Parquet)?;
+    }
+    writer.into_inner().map_err(Error::Parquet)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::arrow::{trust_schema, trust_to_batch};
+    use crate::io::parquet::{read_scores, read_trust, write_scores, write_trust};
+    use crate::io::Error;
+    use crate::tx::trust::{ScoreEntry, TrustEntry};
+    use bytes::Bytes;
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+    use parquet::file::properties::WriterProperties;
+
+    #[test]
+    fn should_write_and_read_parquet() {
+        let trust = vec![
+            TrustEntry::new("a".to_string(), "b".to_string(), 1.5),
+            TrustEntry::new("b".to_string(), "c".to_string(), 0.25),
+        ];
+        let scores = vec![
+            ScoreEntry::new("a".to_string(), 0.75),
+            ScoreEntry::new("b".to_string(), 0.25),
+        ];
+
+        let trust_bytes = write_trust(Vec::new(), &trust).unwrap();
+        let scores_bytes = write_scores(Vec::new(), &scores).unwrap();
+
+        assert_eq!(read_trust(Bytes::from(trust_bytes)).unwrap(), trust);
+        assert_eq!(
+            read_scores(Bytes::from(scores_bytes.clone())).unwrap(),
+            scores
+        );
+
+        // Seed and score files don't have the `j` column.
+        let res = read_trust(Bytes::from(scores_bytes));
+        assert!(matches!(res, Err(Error::MissingColumn(column)) if column == "j"));
+    }
+
+    #[test]
+    fn should_read_compressed_parquet() {
+        let trust = vec![
+            TrustEntry::new("a".to_string(), "b".to_string(), 1.5),
+            TrustEntry::new("b".to_string(), "c".to_string(), 0.25),
+        ];
+        let compressions = [
+            Compression::UNCOMPRESSED,
+            Compression::SNAPPY,
+            Compression::GZIP(GzipLevel::default()),
+            Compression::LZ4,
+            Compression::LZ4_RAW,
+            Compression::ZSTD(ZstdLevel::default()),
+        ];
+        for compression in compressions {
+            let props = WriterProperties::builder()
+                .set_compression(compression)
+                .build();
+            let mut writer = ArrowWriter::try_new(Vec::new(), trust_schema(), Some(props)).unwrap();
+            writer.write(&trust_to_batch(&trust).unwrap()).unwrap();
+            let bytes = writer.into_inner().unwrap();
+
+            assert_eq!(
+                read_trust(Bytes::from(bytes)).unwrap(),
+                trust,
+                "{compression}"
+            );
+        }
+    }
+}
@@ -0,0 +1,644 @@
+//! Linter for trust and seed datasets.
+//!
+//! Unlike the readers, the [Validator] doesn't stop at the first bad record: it reports every
+//! issue it finds with its location, and computes a few statistics about the trust graph.
+
+use crate::io::{csv, stream_scores_bytes, stream_scores_file, stream_trust_bytes};
+use crate::io::{stream_trust_file, CsvConfig, DataFormat, Error};
+use crate::runners::interner::{PeerId, PeerInterner};
+use crate::tx::trust::{ScoreEntry, TrustEntry};
+use getset::Getters;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::Cursor;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// How bad an [Issue] is.
+pub enum Severity {
+    /// The dataset can be used, but probably not as intended.
+    Warning,
+    /// The record is rejected, or the dataset can't be used at all.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Which dataset an [Issue] was found in.
+pub enum Dataset {
+    Trust,
+    Seed,
+}
+
+impl Display for Dataset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Trust => write!(f, "trust"),
+            Self::Seed => write!(f, "seed"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Position of a record: a line for CSV, a row for Parquet and Arrow. Both start from 1.
+pub enum Location {
+    Line(u64),
+    Row(u64),
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Line(x) => write!(f, "line {}", x),
+            Self::Row(x) => write!(f, "row {}", x),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Problem found in a dataset.
+pub enum IssueKind {
+    /// The record or the file couldn't be read.
+    Malformed(String),
+    /// The dataset has no records.
+    Empty,
+    /// A peer id is empty.
+    EmptyPeerId,
+    /// The value is NaN or infinite.
+    NonFiniteValue(f32),
+    /// The value is negative.
+    NegativeValue(f32),
+    /// A peer trusts itself.
+    SelfTrust(String),
+    /// The edge was already defined; the last value wins.
+    DuplicateEdge {
+        from: String,
+        to: String,
+        first: Location,
+    },
+    /// The seed peer was already defined; the last value wins.
+    DuplicateSeed { id: String, first: Location },
+    /// The seed values don't sum up to a positive number, so every peer gets the same seed trust.
+    ZeroSeed,
+    /// The seed peer doesn't appear in the trust graph.
+    SeedNotInGraph(String),
+    /// The seed peer doesn't trust anyone, so its seed trust reaches no other peer.
+    UnreachableSeed(String),
+}
+
+impl IssueKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Malformed(_)
+            | Self::Empty
+            | Self::EmptyPeerId
+            | Self::NonFiniteValue(_)
+            | Self::NegativeValue(_) => Severity::Error,
+            Self::SelfTrust(_)
+            | Self::DuplicateEdge { .. }
+            | Self::DuplicateSeed { .. }
+            | Self::ZeroSeed
+            | Self::SeedNotInGraph(_)
+            | Self::UnreachableSeed(_) => Severity::Warning,
+        }
+    }
+}
+
+impl Display for IssueKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Malformed(e) => write!(f, "{}", e),
+            Self::Empty => write!(f, "dataset is empty"),
+            Self::EmptyPeerId => write!(f, "empty peer id"),
+            Self::NonFiniteValue(x) => write!(f, "value {} is not a finite number", x),
+            Self::NegativeValue(x) => write!(f, "value {} is negative", x),
+            Self::SelfTrust(id) => write!(f, "peer '{}' trusts itself", id),
+            Self::DuplicateEdge { from, to, first } => {
+                write!(
+                    f,
+                    "edge '{}' -> '{}' already defined at {}",
+                    from, to, first
+                )
+            }
+            Self::DuplicateSeed { id, first } => {
+                write!(f, "seed peer '{}' already defined at {}", id, first)
+            }
+            Self::ZeroSeed => write!(f, "seed values don't sum up to a positive number"),
+            Self::SeedNotInGraph(id) => write!(f, "seed peer '{}' is not in the trust graph", id),
+            Self::UnreachableSeed(id) => {
+                write!(f, "seed peer '{}' has no outbound trust", id)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Getters)]
+#[getset(get = "pub")]
+/// Problem found in a dataset, with its location if it concerns a single record.
+pub struct Issue {
+    severity: Severity,
+    dataset: Dataset,
+    location: Option<Location>,
+    kind: IssueKind,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.location {
+            Some(location) => write!(
+                f,
+                "{}: {} {}: {}",
+                self.severity, self.dataset, location, self.kind
+            ),
+            None => write!(f, "{}: {}: {}", self.severity, self.dataset, self.kind),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Getters)]
+#[getset(get = "pub")]
+/// Statistics of the trust graph, as the runners would see it.
+pub struct GraphStats {
+    /// Number of peers in the trust dataset.
+    nodes: usize,
+    /// Number of edges with a positive value.
+    edges: usize,
+    /// Number of peers without outbound trust.
+    dangling_nodes: usize,
+    /// Number of peers with a positive seed value.
+    seed_peers: usize,
+    /// Number of peers reachable from the seed peers, if a seed was checked.
+    reachable_nodes: Option<usize>,
+}
+
+impl GraphStats {
+    /// Fraction of the peers reachable from the seed peers, if a seed was checked.
+    pub fn reachable_fraction(&self) -> Option<f64> {
+        let reachable = self.reachable_nodes?;
+        if self.nodes == 0 {
+            return Some(0.0);
+        }
+        Some(reachable as f64 / self.nodes as f64)
+    }
+}
+
+impl Display for GraphStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "nodes: {}, edges: {}, dangling nodes: {}, seed peers: {}",
+            self.nodes, self.edges, self.dangling_nodes, self.seed_peers
+        )?;
+        if let (Some(reachable), Some(fraction)) = (self.reachable_nodes, self.reachable_fraction())
+        {
+            write!(
+                f,
+                ", reachable from seed: {} ({:.2}%)",
+                reachable,
+                fraction * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Getters)]
+#[getset(get = "pub")]
+/// Result of a [Validator] run.
+pub struct ValidationReport {
+    issues: Vec<Issue>,
+    stats: GraphStats,
+}
+
+impl ValidationReport {
+    /// Returns the number of issues with the given severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|x| x.severity == severity)
+            .count()
+    }
+
+    /// Returns `true` if the datasets shouldn't be used.
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+/// Records of a dataset, with their location. The location is missing if the file itself
+/// couldn't be read.
+type Records<T> = Box<dyn Iterator<Item = (Option<Location>, Result<T, Error>)>>;
+
+/// Streams the records of a CSV dataset, with their line.
+fn csv_records<T, R>(mut reader: R, line: fn(&R) -> u64) -> Records<T>
+where
+    R: Iterator<Item = Result<T, Error>> + 'static,
+{
+    Box::new(std::iter::from_fn(move || {
+        let entry = reader.next()?;
+        Some((Some(Location::Line(line(&reader))), entry))
+    }))
+}
+
+/// Streams the records of a Parquet or Arrow dataset, with their row.
+fn row_records<T: 'static, I>(entries: Result<I, Error>) -> Records<T>
+where
+    I: Iterator<Item = Result<T, Error>> + 'static,
+{
+    match entries {
+        Ok(entries) => Box::new(
+            entries
+                .enumerate()
+                .map(|(i, x)| (Some(Location::Row(i as u64 + 1)), x)),
+        ),
+        Err(e) => Box::new(std::iter::once((None, Err(e)))),
+    }
+}
+
+/// Location of the error, if it has one.
+fn error_location(e: &Error) -> Option<Location> {
+    match e {
+        Error::Csv { line, .. }
+        | Error::HeaderMismatch { line, .. }
+        | Error::ColumnCount { line, .. }
+        | Error::InvalidValue { line, .. } => Some(Location::Line(*line)),
+        Error::NullValue { row, .. } => Some(Location::Row(*row)),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+/// Checks a trust dataset and its seed, collecting the issues found.
+pub struct Validator {
+    interner: PeerInterner,
+    /// Peers of the trust dataset, including the ones only part of zero-valued edges.
+    peers: HashSet<PeerId>,
+    /// Location of the first definition and last value of every edge.
+    edges: HashMap<(PeerId, PeerId), (Location, f32)>,
+    /// Location of the first definition and last value of every seed peer.
+    seed: HashMap<PeerId, (Location, f32)>,
+    has_seed: bool,
+    issues: Vec<Issue>,
+    csv: CsvConfig,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the options used to read CSV datasets.
+    pub fn with_csv_config(mut self, csv: CsvConfig) -> Self {
+        self.csv = csv;
+        self
+    }
+
+    /// Checks a trust dataset stored in a file.
+    pub fn check_trust_file(&mut self, format: DataFormat, file: File) {
+        let records = match format {
+            DataFormat::Csv => csv_records(
+                csv::TrustReader::new(file, &self.csv),
+                csv::TrustReader::line,
+            ),
+            _ => row_records(stream_trust_file(format, &self.csv, file)),
+        };
+        self.check_trust(records);
+    }
+
+    /// Checks a trust dataset stored in memory.
+    pub fn check_trust_bytes(&mut self, format: DataFormat, data: Vec<u8>) {
+        let records = match format {
+            DataFormat::Csv => csv_records(
+                csv::TrustReader::new(Cursor::new(data), &self.csv),
+                csv::TrustReader::line,
+            ),
+            _ => row_records(stream_trust_bytes(format, &self.csv, data)),
+        };
+        self.check_trust(records);
+    }
+
+    /// Checks a seed dataset stored in a file.
+    pub fn check_seed_file(&mut self, format: DataFormat, file: File) {
+        let records = match format {
+            DataFormat::Csv => csv_records(
+                csv::ScoreReader::new(file, &self.csv),
+                csv::ScoreReader::line,
+            ),
+            _ => row_records(stream_scores_file(format, &self.csv, file)),
+        };
+        self.check_seed(records);
+    }
+
+    /// Checks a seed dataset stored in memory.
+    pub fn check_seed_bytes(&mut self, format: DataFormat, data: Vec<u8>) {
+        let records = match format {
+            DataFormat::Csv => csv_records(
+                csv::ScoreReader::new(Cursor::new(data), &self.csv),
+                csv::ScoreReader::line,
+            ),
+            _ => row_records(stream_scores_bytes(format, &self.csv, data)),
+        };
+        self.check_seed(records);
+    }
+
+    fn push(&mut self, dataset: Dataset, location: Option<Location>, kind: IssueKind) {
+        self.issues.push(Issue {
+            severity: kind.severity(),
+            dataset,
+            location,
+            kind,
+        });
+    }
+
+    /// Reports a read error. Returns `true` if the rest of the dataset can still be read,
+    /// which is only the case for CSV, where every line is parsed on its own.
+    fn push_error(&mut self, dataset: Dataset, location: Option<Location>, e: Error) -> bool {
+        let location = error_location(&e).or(location);
+        let recoverable = matches!(location, Some(Location::Line(_))) && !matches!(e, Error::Io(_));
+        self.push(dataset, location, IssueKind::Malformed(e.to_string()));
+        recoverable
+    }
+
+    /// Checks the value of a record, returning `true` if the runners would accept it.
+    fn check_value(&mut self, dataset: Dataset, location: Location, value: f32) -> bool {
+        if !value.is_finite() {
+            self.push(dataset, Some(location), IssueKind::NonFiniteValue(value));
+            return false;
+        }
+        if value < 0.0 {
+            self.push(dataset, Some(location), IssueKind::NegativeValue(value));
+            return false;
+        }
+        true
+    }
+
+    /// Interns the peer id, reporting an error at the record if every [PeerId] is taken.
+    /// The rest of the dataset can't be checked then, so the caller stops.
+    fn intern(&mut self, dataset: Dataset, location: Location, id: &str) -> Option<PeerId> {
+        match self.interner.intern(id) {
+            Ok(id) => Some(id),
+            Err(e) => {
+                self.push(dataset, Some(location), IssueKind::Malformed(e.to_string()));
+                None
+            }
+        }
+    }
+
+    fn check_trust(&mut self, records: Records<TrustEntry>) {
+        let mut num_records = 0;
+        for (location, entry) in records {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    if self.push_error(Dataset::Trust, location, e) {
+                        continue;
+                    }
+                    return;
+                }
+            };
+            num_records += 1;
+            let location = location.unwrap_or(Location::Row(num_records));
+            if entry.from().trim().is_empty() || entry.to().trim().is_empty() {
+                self.push(Dataset::Trust, Some(location), IssueKind::EmptyPeerId);
+                continue;
+            }
+            if !self.check_value(Dataset::Trust, location, *entry.value()) {
+                continue;
+            }
+            if entry.from() == entry.to() {
+                let kind = IssueKind::SelfTrust(entry.from().clone());
+                self.push(Dataset::Trust, Some(location), kind);
+            }
+
+            let Some(from) = self.intern(Dataset::Trust, location, entry.from()) else {
+                return;
+            };
+            let Some(to) = self.intern(Dataset::Trust, location, entry.to()) else {
+                return;
+            };
+            self.peers.insert(from);
+            self.peers.insert(to);
+            match self.edges.get_mut(&(from, to)) {
+                Some((first, value)) => {
+                    *value = *entry.value();
+                    let kind = IssueKind::DuplicateEdge {
+                        from: entry.from().clone(),
+                        to: entry.to().clone(),
+                        first: *first,
+                    };
+                    self.push(Dataset::Trust, Some(location), kind);
+                }
+                None => {
+                    self.edges.insert((from, to), (location, *entry.value()));
+                }
+            }
+        }
+        if num_records == 0 {
+            self.push(Dataset::Trust, None, IssueKind::Empty);
+        }
+    }
+
+    fn check_seed(&mut self, records: Records<ScoreEntry>) {
+        self.has_seed = true;
+        let mut num_records = 0;
+        for (location, entry) in records {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    if self.push_error(Dataset::Seed, location, e) {
+                        continue;
+                    }
+                    return;
+                }
+            };
+            num_records += 1;
+            let location = location.unwrap_or(Location::Row(num_records));
+            if entry.id().trim().is_empty() {
+                self.push(Dataset::Seed, Some(location), IssueKind::EmptyPeerId);
+                continue;
+            }
+            if !self.check_value(Dataset::Seed, location, *entry.value()) {
+                continue;
+            }
+
+            let Some(id) = self.intern(Dataset::Seed, location, entry.id()) else {
+                return;
+            };
+            match self.seed.get_mut(&id) {
+                Some((first, value)) => {
+                    *value = *entry.value();
+                    let kind = IssueKind::DuplicateSeed {
+                        id: entry.id().clone(),
+                        first: *first,
+                    };
+                    self.push(Dataset::Seed, Some(location), kind);
+                }
+                None => {
+                    self.seed.insert(id, (location, *entry.value()));
+                }
+            }
+        }
+    }
+
+    /// Runs the checks that need both datasets, and returns the report.
+    pub fn finish(mut self) -> ValidationReport {
+        let mut outbound: HashMap<PeerId, Vec<PeerId>> = HashMap::new();
+        for ((from, to), (_, value)) in &self.edges {
+            if *value > 0.0 {
+                outbound.entry(*from).or_default().push(*to);
+            }
+        }
+
+        // Seed issues are reported in the order of the seed dataset.
+        let mut seed: Vec<(PeerId, Location, f32)> =
+            self.seed.iter().map(|(id, (l, v))| (*id, *l, *v)).collect();
+        seed.sort_by_key(|(_, location, _)| match location {
+            Location::Line(x) | Location::Row(x) => *x,
+        });
+        let mut seed_peers = Vec::new();
+        for (id, location, value) in &seed {
+            if *value <= 0.0 {
+                continue;
+            }
+            let name = self.interner.resolve(*id).unwrap_or_default().to_string();
+            if !self.peers.contains(id) {
+                self.push(
+                    Dataset::Seed,
+                    Some(*location),
+                    IssueKind::SeedNotInGraph(name),
+                );
+                continue;
+            }
+            if !outbound.contains_key(id) {
+                self.push(
+                    Dataset::Seed,
+                    Some(*location),
+                    IssueKind::UnreachableSeed(name),
+                );
+            }
+            seed_peers.push(*id);
+        }
+        if self.has_seed && seed_peers.is_empty() {
+            self.push(Dataset::Seed, None, IssueKind::ZeroSeed);
+        }
+
+        let reachable_nodes = self.has_seed.then(|| {
+            if seed_peers.is_empty() {
+                // Without a usable seed, every peer gets the same seed trust.
+                return self.peers.len();
+            }
+            let mut visited: HashSet<PeerId> = seed_peers.iter().copied().collect();
+            let mut queue: VecDeque<PeerId> = seed_peers.iter().copied().collect();
+            while let Some(peer) = queue.pop_front() {
+                for next in outbound.get(&peer).into_iter().flatten() {
+                    if visited.insert(*next) {
+                        queue.push_back(*next);
+                    }
+                }
+            }
+            visited.len()
+        });
+
+        let stats = GraphStats {
+            nodes: self.peers.len(),
+            edges: outbound.values().map(|x| x.len()).sum(),
+            dangling_nodes: self.peers.len() - outbound.len(),
+            seed_peers: seed_peers.len(),
+            reachable_nodes,
+        };
+        ValidationReport {
+            issues: self.issues,
+            stats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::validate::{IssueKind, Location, Severity, Validator};
+    use crate::io::{write_scores_bytes, CsvConfig, DataFormat};
+    use crate::tx::trust::ScoreEntry;
+
+    #[test]
+    fn should_report_every_issue_with_its_line() {
+        let trust = "i,j,v\na,b,1\na,a,1\nb,c,x\nb,c,-1\na,b,2\n,c,1\nc,d,NaN\nb\n";
+        let seed = "i,v\na,1\nz,1\n";
+        let mut validator = Validator::new();
+        validator.check_trust_bytes(DataFormat::Csv, trust.as_bytes().to_vec());
+        validator.check_seed_bytes(DataFormat::Csv, seed.as_bytes().to_vec());
+        let report = validator.finish();
+
+        let found: Vec<(Severity, Option<Location>)> = report
+            .issues()
+            .iter()
+            .map(|x| (*x.severity(), *x.location()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Warning, Some(Location::Line(3))),
+                (Severity::Error, Some(Location::Line(4))),
+                (Severity::Error, Some(Location::Line(5))),
+                (Severity::Warning, Some(Location::Line(6))),
+                (Severity::Error, Some(Location::Line(7))),
+                (Severity::Error, Some(Location::Line(8))),
+                (Severity::Error, Some(Location::Line(9))),
+                (Severity::Warning, Some(Location::Line(3))),
+            ]
+        );
+        assert!(matches!(
+            report.issues()[3].kind(),
+            IssueKind::DuplicateEdge {
+                first: Location::Line(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            report.issues()[7].kind(),
+            &IssueKind::SeedNotInGraph("z".to_string())
+        );
+        assert_eq!(report.count(Severity::Error), 5);
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn should_compute_graph_stats() {
+        // a -> b -> c, d -> a, and e is only part of a zero-valued edge.
+        let trust = "i,j,v\na,b,1\nb,c,1\nd,a,1\nd,e,0\n";
+        let seed = write_scores_bytes(
+            DataFormat::Parquet,
+            &CsvConfig::default(),
+            &[
+                ScoreEntry::new("b".to_string(), 1.0),
+                ScoreEntry::new("c".to_string(), 1.0),
+            ],
+        )
+        .unwrap();
+        let mut validator = Validator::new();
+        validator.check_trust_bytes(DataFormat::Csv, trust.as_bytes().to_vec());
+        validator.check_seed_bytes(DataFormat::Parquet, seed);
+        let report = validator.finish();
+
+        let stats = report.stats();
+        assert_eq!(*stats.nodes(), 5);
+        assert_eq!(*stats.edges(), 3);
+        assert_eq!(*stats.dangling_nodes(), 2);
+        assert_eq!(*stats.seed_peers(), 2);
+        assert_eq!(*stats.reachable_nodes(), Some(2));
+        assert_eq!(stats.reachable_fraction(), Some(0.4));
+        assert_eq!(
+            report.issues()[0].kind(),
+            &IssueKind::UnreachableSeed("c".to_string())
+        );
+        assert_eq!(*report.issues()[0].location(), Some(Location::Row(2)));
+        assert!(!report.has_errors());
+    }
+}
@@ -0,0 +1,805 @@
+//! Persistent queue of compute jobs.
+//!
+//! Every compute request is stored as a [Job] as soon as its event is read, and moves through
+//! the [JobState]s as workers process it. Failed attempts are retried with exponential backoff,
+//! and jobs that were in progress when the node stopped are picked up again on startup.
+
+use crate::config::Settings;
+use crate::merkle::Hash;
+use crate::metrics;
+use alloy::primitives::{B256, U256};
+use getset::Getters;
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+/// Database file used when none is configured.
+pub const DEFAULT_JOBS_PATH: &str = "./jobs.redb";
+/// Number of workers used when none is configured.
+pub const DEFAULT_WORKERS: usize = 1;
+/// Number of attempts of a job used when none is configured.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry used when none is configured.
+pub const DEFAULT_RETRY_DELAY_SECS: u64 = 10;
+/// Upper bound of the delay between two attempts.
+const MAX_RETRY_DELAY_SECS: u64 = 3600;
+
+/// Jobs, keyed by the big-endian compute id, as JSON.
+const JOBS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("jobs");
+/// Compute ids of the jobs that are not in a terminal state, so that claiming a job doesn't
+/// read the whole history.
+const ACTIVE: TableDefinition<&[u8], ()> = TableDefinition::new("active_jobs");
+
+#[derive(thiserror::Error, Debug)]
+/// Errors that can arise while using the [JobStore].
+pub enum Error {
+    /// The database couldn't be opened, read or written.
+    #[error("Database error: {0}")]
+    Db(String),
+    /// A stored job couldn't be encoded or decoded.
+    #[error("Serde error: {0}")]
+    Serde(serde_json::Error),
+    /// There is no job with the compute id.
+    #[error("Job not found: ComputeId({0})")]
+    NotFound(U256),
+    /// The job can't move from its current state to the requested one.
+    #[error("Invalid job transition: {from} -> {to}")]
+    InvalidTransition { from: JobState, to: JobState },
+    /// The job was replaced by another request with the same compute id.
+    #[error("Job replaced by a new request: ComputeId({0})")]
+    Replaced(U256),
+    /// The configuration is not valid.
+    #[error("Config error: {0}")]
+    Config(String),
+}
+
+impl<E: Into<redb::Error>> From<E> for Error {
+    fn from(e: E) -> Self {
+        Error::Db(e.into().to_string())
+    }
+}
+
+/// Returns the current Unix time, in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Stage of a compute job.
+pub enum JobState {
+    /// The request was read from the chain.
+    Received,
+    /// The job description and datasets are being downloaded.
+    Downloading,
+    /// The scores are being computed.
+    Computing,
+    /// The scores and the result manifest are being uploaded.
+    Uploading,
+    /// The result transaction was sent.
+    Submitted,
+    /// The result transaction was included.
+    Confirmed,
+    /// The job was given up on.
+    Failed,
+    /// The request was removed by a reorg.
+    Cancelled,
+}
+
+impl JobState {
+    /// Every state, in pipeline order.
+    pub const ALL: [JobState; 8] = [
+        JobState::Received,
+        JobState::Downloading,
+        JobState::Computing,
+        JobState::Uploading,
+        JobState::Submitted,
+        JobState::Confirmed,
+        JobState::Failed,
+        JobState::Cancelled,
+    ];
+
+    /// Returns `true` if the job won't be worked on anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobState::Confirmed | JobState::Failed | JobState::Cancelled
+        )
+    }
+
+    /// Returns `true` if a job can move from this state to the next one. Downloading can be
+    /// started again from any stage before the submission, since the computed scores are not
+    /// persisted, and a dropped transaction can be sent again. A job whose results are found
+    /// on chain can be confirmed from any stage.
+    pub fn can_move_to(&self, next: JobState) -> bool {
+        use JobState::*;
+        match (self, next) {
+            (from, _) if from.is_terminal() => false,
+            (_, Failed | Confirmed) => true,
+            (Received | Downloading | Computing | Uploading, Downloading) => true,
+            (Downloading, Computing) | (Computing, Uploading) => true,
+            (Uploading | Submitted, Submitted) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for JobState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let name = match self {
+            JobState::Received => "received",
+            JobState::Downloading => "downloading",
+            JobState::Computing => "computing",
+            JobState::Uploading => "uploading",
+            JobState::Submitted => "submitted",
+            JobState::Confirmed => "confirmed",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Compute request and the progress made on it.
+pub struct Job {
+    compute_id: U256,
+    job_description_id: Hash,
+    /// Block of the request event.
+    block_number: u64,
+    state: JobState,
+    /// Number of failed attempts.
+    attempts: u32,
+    /// Unix time before which the job is not attempted again.
+    retry_at: u64,
+    last_error: Option<String>,
+    /// Result manifest id, once uploaded.
+    results_id: Option<Hash>,
+    /// Root of the sub-job commitments, once uploaded.
+    commitment: Option<Hash>,
+    /// Hash of the last result transaction sent.
+    tx_hash: Option<B256>,
+}
+
+impl Job {
+    pub fn new(compute_id: U256, job_description_id: Hash, block_number: u64) -> Self {
+        Self {
+            compute_id,
+            job_description_id,
+            block_number,
+            state: JobState::Received,
+            attempts: 0,
+            retry_at: 0,
+            last_error: None,
+            results_id: None,
+            commitment: None,
+            tx_hash: None,
+        }
+    }
+
+    /// Records the uploaded results, so that a retry only has to submit them.
+    pub fn set_results(&mut self, results_id: Hash, commitment: Hash) {
+        self.results_id = Some(results_id);
+        self.commitment = Some(commitment);
+    }
+
+    /// Records the hash of the result transaction.
+    pub fn set_tx_hash(&mut self, tx_hash: B256) {
+        self.tx_hash = Some(tx_hash);
+    }
+
+    fn key(&self) -> [u8; 32] {
+        self.compute_id.to_be_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Getters)]
+#[getset(get = "pub")]
+/// Location of the [JobStore], and how its jobs are worked on.
+pub struct JobQueueConfig {
+    path: PathBuf,
+    /// Number of jobs processed concurrently.
+    workers: usize,
+    /// Number of attempts before a job fails.
+    max_attempts: u32,
+    /// Delay before the first retry, doubled after every failed attempt.
+    retry_delay: Duration,
+}
+
+impl Default for JobQueueConfig {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_JOBS_PATH.into(),
+            DEFAULT_WORKERS,
+            DEFAULT_MAX_ATTEMPTS,
+            Duration::from_secs(DEFAULT_RETRY_DELAY_SECS),
+        )
+    }
+}
+
+impl JobQueueConfig {
+    pub fn new(path: PathBuf, workers: usize, max_attempts: u32, retry_delay: Duration) -> Self {
+        Self {
+            path,
+            workers: workers.max(1),
+            max_attempts: max_attempts.max(1),
+            retry_delay,
+        }
+    }
+
+    /// Reads the config from the environment only, see [Self::from_settings].
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_settings(&Settings::from_env())
+    }
+
+    /// Reads the config from the `JOBS_PATH`, `JOBS_WORKERS`, `JOBS_MAX_ATTEMPTS` and
+    /// `JOBS_RETRY_DELAY_SECS` settings, falling back to the defaults.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Error> {
+        fn number<T: FromStr>(settings: &Settings, name: &str) -> Result<Option<T>, Error>
+        where
+            T::Err: Display,
+        {
+            settings
+                .parse(name)
+                .map_err(|e| Error::Config(e.to_string()))
+        }
+        let path = settings
+            .get("JOBS_PATH")
+            .unwrap_or(DEFAULT_JOBS_PATH.to_string());
+        let workers = number(settings, "JOBS_WORKERS")?.unwrap_or(DEFAULT_WORKERS);
+        let max_attempts = number(settings, "JOBS_MAX_ATTEMPTS")?.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let retry_delay =
+            number(settings, "JOBS_RETRY_DELAY_SECS")?.unwrap_or(DEFAULT_RETRY_DELAY_SECS);
+        Ok(Self::new(
+            path.into(),
+            workers,
+            max_attempts,
+            Duration::from_secs(retry_delay),
+        ))
+    }
+
+    /// Returns the delay before the next attempt, after the given number of failed attempts.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+        let secs = self.retry_delay.as_secs().saturating_mul(factor);
+        Duration::from_secs(secs.min(MAX_RETRY_DELAY_SECS))
+    }
+}
+
+/// Jobs persisted in an embedded database, shared by the event loop and the workers.
+pub struct JobStore {
+    db: Database,
+    config: JobQueueConfig,
+    /// Jobs currently worked on in this process.
+    claimed: Mutex<HashSet<U256>>,
+    notify: Notify,
+}
+
+impl JobStore {
+    /// Opens the database, creating it if needed. Jobs left in progress by a previous run are
+    /// claimable again.
+    ///
+    /// Every job is read once here, to count them by state and to rebuild the index of the
+    /// active jobs.
+    pub fn open(config: JobQueueConfig) -> Result<Self, Error> {
+        if let Some(parent) = config.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| Error::Db(e.to_string()))?;
+        }
+        let db = Database::create(&config.path)?;
+        let tx = db.begin_write()?;
+        let mut counts = BTreeMap::new();
+        {
+            let table = tx.open_table(JOBS)?;
+            let mut active = tx.open_table(ACTIVE)?;
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                let job: Job = serde_json::from_slice(value.value()).map_err(Error::Serde)?;
+                if job.state.is_terminal() {
+                    active.remove(key.value())?;
+                } else {
+                    active.insert(key.value(), ())?;
+                }
+                *counts.entry(job.state).or_insert(0) += 1;
+            }
+        }
+        tx.commit()?;
+        for state in JobState::ALL {
+            let count = counts.get(&state).copied().unwrap_or(0);
+            metrics::JOBS
+                .with_label_values(&[&state.to_string()])
+                .set(count as i64);
+        }
+        let store = Self {
+            db,
+            config,
+            claimed: Mutex::new(HashSet::new()),
+            notify: Notify::new(),
+        };
+        let pending = store.pending()?;
+        if pending > 0 {
+            info!("Resuming jobs: Pending({})", pending);
+        }
+        Ok(store)
+    }
+
+    pub fn config(&self) -> &JobQueueConfig {
+        &self.config
+    }
+
+    /// Adds the job, unless there is one for the same request already, e.g. because the
+    /// request event was read again. Returns `true` if it was added.
+    ///
+    /// A job cancelled by a reorg, or stored for another job description, is replaced: the
+    /// request was included again, or another request took its compute id.
+    pub fn insert(&self, job: &Job) -> Result<bool, Error> {
+        let tx = self.db.begin_write()?;
+        let replaced = {
+            let mut table = tx.open_table(JOBS)?;
+            let stored: Option<Job> = match table.get(job.key().as_slice())? {
+                Some(value) => Some(serde_json::from_slice(value.value()).map_err(Error::Serde)?),
+                None => None,
+            };
+            match stored {
+                Some(stored)
+                    if stored.state != JobState::Cancelled
+                        && stored.job_description_id == job.job_description_id =>
+                {
+                    return Ok(false);
+                }
+                stored => {
+                    let value = serde_json::to_vec(job).map_err(Error::Serde)?;
+                    table.insert(job.key().as_slice(), value.as_slice())?;
+                    let mut active = tx.open_table(ACTIVE)?;
+                    if job.state.is_terminal() {
+                        active.remove(job.key().as_slice())?;
+                    } else {
+                        active.insert(job.key().as_slice(), ())?;
+                    }
+                    stored
+                }
+            }
+        };
+        tx.commit()?;
+        if let Some(stored) = replaced {
+            info!(
+                "Job replaced: ComputeId({}), State({}), JobDescriptionId({})",
+                stored.compute_id, stored.state, stored.job_description_id
+            );
+            metrics::JOBS
+                .with_label_values(&[&stored.state.to_string()])
+                .dec();
+        }
+        metrics::JOBS
+            .with_label_values(&[&job.state.to_string()])
+            .inc();
+        self.notify.notify_one();
+        Ok(true)
+    }
+
+    /// Returns the job with the compute id.
+    pub fn get(&self, compute_id: &U256) -> Result<Option<Job>, Error> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(JOBS)?;
+        let key: [u8; 32] = compute_id.to_be_bytes();
+        let Some(value) = table.get(key.as_slice())? else {
+            return Ok(None);
+        };
+        serde_json::from_slice(value.value())
+            .map(Some)
+            .map_err(Error::Serde)
+    }
+
+    /// Returns every job.
+    pub fn list(&self) -> Result<Vec<Job>, Error> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(JOBS)?;
+        let mut jobs = Vec::new();
+        for entry in table.iter()? {
+            let (_, value) = entry?;
+            jobs.push(serde_json::from_slice(value.value()).map_err(Error::Serde)?);
+        }
+        Ok(jobs)
+    }
+
+    /// Returns the jobs that are not in a terminal state.
+    pub fn list_active(&self) -> Result<Vec<Job>, Error> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(JOBS)?;
+        let active = tx.open_table(ACTIVE)?;
+        let mut jobs = Vec::new();
+        for entry in active.iter()? {
+            let (key, _) = entry?;
+            if let Some(value) = table.get(key.value())? {
+                jobs.push(serde_json::from_slice(value.value()).map_err(Error::Serde)?);
+            }
+        }
+        Ok(jobs)
+    }
+
+    /// Returns the number of jobs that are not in a terminal state.
+    pub fn pending(&self) -> Result<u64, Error> {
+        let tx = self.db.begin_read()?;
+        Ok(tx.open_table(ACTIVE)?.len()?)
+    }
+
+    /// Claims the job that is due for an attempt with the oldest request, if there is one. The
+    /// job must be released once the attempt is over.
+    pub fn claim(&self, now: u64) -> Result<Option<Job>, Error> {
+        let jobs = self.list_active()?;
+        let mut claimed = self.claimed.lock().unwrap();
+        let job = jobs
+            .into_iter()
+            .filter(|x| x.retry_at <= now)
+            .filter(|x| !claimed.contains(&x.compute_id))
+            .min_by_key(|x| (x.block_number, x.compute_id));
+        if let Some(job) = &job {
+            claimed.insert(job.compute_id);
+        }
+        Ok(job)
+    }
+
+    /// Releases a claimed job, so that it can be claimed again.
+    pub fn release(&self, compute_id: &U256) {
+        self.claimed.lock().unwrap().remove(compute_id);
+        self.notify.notify_one();
+    }
+
+    /// Waits until a job might be claimable, or the timeout passes.
+    pub async fn wait(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+    }
+
+    /// Moves the job to the state and saves it, along with its other changes.
+    ///
+    /// The transition is checked against the stored state, so a job failed by another task,
+    /// e.g. because its request was removed by a reorg, is not moved forward.
+    pub fn transition(&self, job: &mut Job, state: JobState) -> Result<(), Error> {
+        self.update_claimed(job, |stored| {
+            if !stored.state.can_move_to(state) {
+                return Err(Error::InvalidTransition {
+                    from: stored.state,
+                    to: state,
+                });
+            }
+            *stored = Job {
+                state,
+                attempts: stored.attempts,
+                retry_at: stored.retry_at,
+                last_error: stored.last_error.take(),
+                ..job.clone()
+            };
+            Ok(())
+        })
+        .map(|stored| *job = stored)
+    }
+
+    /// Records a failed attempt. The job is retried after an exponential backoff, or fails
+    /// once it ran out of attempts.
+    pub fn record_failure(&self, job: &mut Job, error: &str, now: u64) -> Result<(), Error> {
+        let max_attempts = self.config.max_attempts;
+        let stored = self.update_claimed(job, |stored| {
+            if stored.state.is_terminal() {
+                return Ok(());
+            }
+            stored.attempts += 1;
+            stored.last_error = Some(error.to_string());
+            if stored.attempts >= max_attempts {
+                stored.state = JobState::Failed;
+            } else {
+                stored.retry_at = now + self.config.backoff(stored.attempts).as_secs();
+            }
+            Ok(())
+        })?;
+        if stored.state == JobState::Failed {
+            warn!(
+                "Job failed: ComputeId({}), Attempts({}), Error({})",
+                stored.compute_id, stored.attempts, error
+            );
+        } else {
+            warn!(
+                "Job attempt failed: ComputeId({}), Attempts({}), RetryAt({}), Error({})",
+                stored.compute_id, stored.attempts, stored.retry_at, error
+            );
+        }
+        *job = stored;
+        Ok(())
+    }
+
+    /// Fails the job without further attempts, e.g. because another computer submitted its
+    /// results.
+    pub fn fail(&self, job: &mut Job, reason: &str) -> Result<(), Error> {
+        let stored = self.update_claimed(job, |stored| {
+            if !stored.state.can_move_to(JobState::Failed) {
+                return Err(Error::InvalidTransition {
+                    from: stored.state,
+                    to: JobState::Failed,
+                });
+            }
+            stored.state = JobState::Failed;
+            stored.last_error = Some(reason.to_string());
+            Ok(())
+        })?;
+        warn!(
+            "Job failed: ComputeId({}), Error({})",
+            stored.compute_id, reason
+        );
+        *job = stored;
+        Ok(())
+    }
+
+    /// Cancels the job unless its results were submitted already, because its request was
+    /// removed by a reorg. Returns `true` if the job was cancelled.
+    pub fn cancel(&self, compute_id: &U256, reason: &str) -> Result<bool, Error> {
+        let mut cancelled = false;
+        let res = self.update(compute_id, |stored| {
+            if stored.state.is_terminal() || stored.state == JobState::Submitted {
+                return Ok(());
+            }
+            stored.state = JobState::Cancelled;
+            stored.last_error = Some(reason.to_string());
+            cancelled = true;
+            Ok(())
+        });
+        match res {
+            Ok(_) => Ok(cancelled),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Applies the change to the stored job of a worker, unless the job was replaced by another
+    /// request since the worker claimed it.
+    fn update_claimed(
+        &self,
+        job: &Job,
+        f: impl FnOnce(&mut Job) -> Result<(), Error>,
+    ) -> Result<Job, Error> {
+        self.update(&job.compute_id, |stored| {
+            if stored.job_description_id != job.job_description_id {
+                return Err(Error::Replaced(job.compute_id));
+            }
+            f(stored)
+        })
+    }
+
+    /// Applies the change to the stored job in a single transaction, and returns the result.
+    fn update(
+        &self,
+        compute_id: &U256,
+        f: impl FnOnce(&mut Job) -> Result<(), Error>,
+    ) -> Result<Job, Error> {
+        let key: [u8; 32] = compute_id.to_be_bytes();
+        let tx = self.db.begin_write()?;
+        let from;
+        let job = {
+            let mut table = tx.open_table(JOBS)?;
+            let mut job: Job = match table.get(key.as_slice())? {
+                Some(value) => serde_json::from_slice(value.value()).map_err(Error::Serde)?,
+                None => return Err(Error::NotFound(*compute_id)),
+            };
+            from = job.state;
+            f(&mut job)?;
+            let value = serde_json::to_vec(&job).map_err(Error::Serde)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+            if job.state.is_terminal() {
+                tx.open_table(ACTIVE)?.remove(key.as_slice())?;
+            }
+            job
+        };
+        tx.commit()?;
+        if from != job.state {
+            metrics::JOBS.with_label_values(&[&from.to_string()]).dec();
+            metrics::JOBS
+                .with_label_values(&[&job.state.to_string()])
+                .inc();
+        }
+        Ok(job)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain::mock::MockChain;
+    use crate::chain::{EventIngestor, IngestConfig};
+    use crate::jobs::{Error, Job, JobQueueConfig, JobState, JobStore};
+    use crate::merkle::Hash;
+    use alloy::primitives::U256;
+    use alloy::rpc::types::{Filter, Log};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn open(name: &str) -> (JobStore, PathBuf) {
+        let root =
+            std::env::temp_dir().join(format!("openrank-jobs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let config = JobQueueConfig::new(root.join("jobs.redb"), 2, 3, Duration::from_secs(10));
+        (JobStore::open(config).unwrap(), root)
+    }
+
+    #[test]
+    fn should_move_jobs_through_the_pipeline_and_resume_them() {
+        let (store, root) = open("pipeline");
+        let first = Job::new(U256::from(2), Hash::from_bytes([1; 32]), 10);
+        let second = Job::new(U256::from(1), Hash::from_bytes([2; 32]), 11);
+        assert!(store.insert(&first).unwrap());
+        assert!(store.insert(&second).unwrap());
+        assert!(!store.insert(&first).unwrap());
+
+        // Oldest request first, and never twice at the same time.
+        let mut job = store.claim(0).unwrap().unwrap();
+        assert_eq!(job.compute_id(), &U256::from(2));
+        assert_eq!(
+            store.claim(0).unwrap().unwrap().compute_id(),
+            &U256::from(1)
+        );
+        assert_eq!(store.claim(0).unwrap(), None);
+
+        store.transition(&mut job, JobState::Downloading).unwrap();
+        store.transition(&mut job, JobState::Computing).unwrap();
+        let res = store.transition(&mut job, JobState::Submitted);
+        assert!(matches!(res, Err(Error::InvalidTransition { .. })));
+        store.transition(&mut job, JobState::Uploading).unwrap();
+        job.set_results(Hash::from_bytes([3; 32]), Hash::from_bytes([4; 32]));
+        store.transition(&mut job, JobState::Submitted).unwrap();
+        drop(store);
+
+        // Claims are not persisted, so both jobs are resumed after a restart.
+        let config = JobQueueConfig::new(root.join("jobs.redb"), 2, 3, Duration::from_secs(10));
+        let store = JobStore::open(config).unwrap();
+        let mut job = store.claim(0).unwrap().unwrap();
+        assert_eq!(job.state(), &JobState::Submitted);
+        assert_eq!(job.results_id(), &Some(Hash::from_bytes([3; 32])));
+        store.transition(&mut job, JobState::Confirmed).unwrap();
+        store.release(job.compute_id());
+        assert_eq!(
+            store.claim(0).unwrap().unwrap().compute_id(),
+            &U256::from(1)
+        );
+        store.release(&U256::from(1));
+
+        // A job cancelled by a reorg is not moved forward by its worker.
+        let mut job = store.claim(0).unwrap().unwrap();
+        assert!(store.cancel(job.compute_id(), "Request removed").unwrap());
+        assert!(store.transition(&mut job, JobState::Downloading).is_err());
+        store.release(job.compute_id());
+        assert_eq!(store.claim(u64::MAX).unwrap(), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_retry_with_backoff_then_fail() {
+        let (store, root) = open("retry");
+        let config = store.config().clone();
+        assert_eq!(config.backoff(1), Duration::from_secs(10));
+        assert_eq!(config.backoff(3), Duration::from_secs(40));
+        assert_eq!(config.backoff(40), Duration::from_secs(3600));
+
+        store
+            .insert(&Job::new(U256::from(7), Hash::default(), 1))
+            .unwrap();
+        let mut job = store.claim(100).unwrap().unwrap();
+        store.transition(&mut job, JobState::Downloading).unwrap();
+        store.record_failure(&mut job, "Timeout", 100).unwrap();
+        store.release(job.compute_id());
+        assert_eq!(job.retry_at(), &110);
+        assert_eq!(job.state(), &JobState::Downloading);
+        assert_eq!(store.claim(109).unwrap(), None);
+
+        let mut job = store.claim(110).unwrap().unwrap();
+        store.record_failure(&mut job, "Timeout", 110).unwrap();
+        assert_eq!(job.retry_at(), &130);
+        store.record_failure(&mut job, "Timeout", 130).unwrap();
+        store.release(job.compute_id());
+        assert_eq!(job.state(), &JobState::Failed);
+        assert_eq!(job.attempts(), &3);
+        assert_eq!(job.last_error().as_deref(), Some("Timeout"));
+        assert_eq!(store.claim(u64::MAX).unwrap(), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_only_read_active_jobs() {
+        let (store, root) = open("active");
+        for id in 1..=3 {
+            store
+                .insert(&Job::new(U256::from(id), Hash::default(), id))
+                .unwrap();
+        }
+        let mut job = store.claim(0).unwrap().unwrap();
+        store.transition(&mut job, JobState::Confirmed).unwrap();
+        store.release(job.compute_id());
+        assert!(store.cancel(&U256::from(2), "Request removed").unwrap());
+
+        assert_eq!(store.list().unwrap().len(), 3);
+        assert_eq!(store.pending().unwrap(), 1);
+        let active = store.list_active().unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].compute_id(), &U256::from(3));
+        drop(store);
+
+        let config = JobQueueConfig::new(root.join("jobs.redb"), 2, 3, Duration::from_secs(10));
+        let store = JobStore::open(config).unwrap();
+        assert_eq!(store.pending().unwrap(), 1);
+        assert_eq!(
+            store.claim(0).unwrap().unwrap().compute_id(),
+            &U256::from(3)
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_queue_a_request_again_after_a_reorg() {
+        let (store, root) = open("reorg");
+        let chain = MockChain::new();
+        let config = IngestConfig::new(root.join("cursors"), None, 2, 0);
+        let mut ingestor = EventIngestor::start(
+            chain.clone(),
+            Filter::new(),
+            config.clone(),
+            config.cursor_store("computer"),
+        )
+        .await
+        .unwrap();
+        // The request is identified by its log index, so that it is the same in both forks.
+        let request = |log: &Log, description: u8| {
+            let compute_id = U256::from(log.log_index.unwrap());
+            let block_number = log.block_number.unwrap();
+            Job::new(
+                compute_id,
+                Hash::from_bytes([description; 32]),
+                block_number,
+            )
+        };
+
+        chain.mine(1);
+        let log = ingestor.next().await.unwrap();
+        assert!(store.insert(&request(&log, 1)).unwrap());
+        let mut job = store.claim(0).unwrap().unwrap();
+        store.transition(&mut job, JobState::Downloading).unwrap();
+
+        chain.reorg(1);
+        let log = ingestor.next().await.unwrap();
+        assert!(log.removed);
+        assert!(store.cancel(&U256::from(0), "Request removed").unwrap());
+        assert!(store.transition(&mut job, JobState::Computing).is_err());
+        store.release(job.compute_id());
+        assert_eq!(store.pending().unwrap(), 0);
+
+        // Included again in the new fork, the request is queued from the start.
+        chain.mine(0);
+        chain.mine(1);
+        let log = ingestor.next().await.unwrap();
+        assert!(store.insert(&request(&log, 1)).unwrap());
+        assert!(!store.insert(&request(&log, 1)).unwrap());
+        let mut job = store.claim(0).unwrap().unwrap();
+        assert_eq!(job.state(), &JobState::Received);
+        assert_eq!(job.block_number(), &2);
+        assert_eq!(job.last_error(), &None);
+
+        // Another job description under the same compute id replaces the job, and the worker of
+        // the previous one can't change it anymore.
+        assert!(store.insert(&request(&log, 2)).unwrap());
+        let res = store.transition(&mut job, JobState::Downloading);
+        assert!(matches!(res, Err(Error::Replaced(_))));
+        store.release(job.compute_id());
+        let job = store.claim(0).unwrap().unwrap();
+        assert_eq!(job.job_description_id(), &Hash::from_bytes([2; 32]));
+        assert_eq!(job.state(), &JobState::Received);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,7 +1,15 @@
 pub mod algos;
+pub mod blob;
+pub mod chain;
+pub mod config;
 pub mod eigenda;
+pub mod health;
+pub mod io;
+pub mod jobs;
 pub mod logs;
+pub mod manifest;
 pub mod merkle;
+pub mod metrics;
 pub mod runners;
 pub mod tx;
 
@@ -68,7 +76,7 @@ impl Display for DomainHash {
     }
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize, Getters)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Getters)]
 #[getset(get = "pub")]
 /// Domain of the openrank network. Consists of a trust namespace and a seed namespace + algorithm id.
 pub struct Domain {
@@ -0,0 +1,534 @@
+//! Versioned manifests describing meta jobs and their results.
+//!
+//! Job and result manifests are stored as JSON Lines under `meta/<hash>`: the first line is a
+//! header with the version, the kind of the manifest and the job-wide fields, and every
+//! following line describes one sub-job. The challenge data posted on EigenDA carries the same
+//! version.
+
+use crate::algos::et::{DELTA, PRE_TRUST_WEIGHT};
+use crate::io::{CsvConfig, DataFormat};
+use crate::merkle::Hash;
+use crate::tx::trust::OwnedNamespace;
+use crate::Domain;
+use alloy_primitives::Address;
+use getset::Getters;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Version written by this build.
+pub const MANIFEST_VERSION: u32 = 1;
+/// Oldest version this build can still read.
+pub const MIN_MANIFEST_VERSION: u32 = 1;
+
+/// Checks that a manifest with the given version can be read by this build.
+pub fn check_version(version: u32) -> Result<(), Error> {
+    if !(MIN_MANIFEST_VERSION..=MANIFEST_VERSION).contains(&version) {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Kind of a manifest, stored in its header line.
+pub enum ManifestKind {
+    Job,
+    Result,
+}
+
+impl Display for ManifestKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Job => write!(f, "job"),
+            Self::Result => write!(f, "result"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+/// Algorithm run on every sub-job of a meta job, with its parameters.
+pub enum AlgorithmParams {
+    EigenTrust {
+        /// Weight given to the seed trust.
+        alpha: f32,
+        /// Convergence threshold.
+        delta: f32,
+    },
+}
+
+impl Default for AlgorithmParams {
+    fn default() -> Self {
+        Self::EigenTrust {
+            alpha: PRE_TRUST_WEIGHT,
+            delta: DELTA,
+        }
+    }
+}
+
+impl AlgorithmParams {
+    /// Checks that the runners implement these parameters.
+    ///
+    /// Only the default EigenTrust parameters are supported for now.
+    pub fn check_supported(&self) -> Result<(), Error> {
+        if self != &Self::default() {
+            return Err(Error::UnsupportedAlgorithm(format!("{self:?}")));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Input datasets of a sub-job.
+pub struct JobDescription {
+    trust_id: Hash,
+    seed_id: Hash,
+    #[serde(default)]
+    format: DataFormat,
+    /// Options of the CSV datasets, also used for the scores.
+    #[serde(default)]
+    csv_config: CsvConfig,
+    /// Trust and seed namespaces the datasets are loaded in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    domain: Option<Domain>,
+}
+
+impl JobDescription {
+    pub fn new(trust_id: Hash, seed_id: Hash, format: DataFormat) -> Self {
+        Self {
+            trust_id,
+            seed_id,
+            format,
+            csv_config: CsvConfig::default(),
+            domain: None,
+        }
+    }
+
+    pub fn with_domain(mut self, domain: Domain) -> Self {
+        self.domain = Some(domain);
+        self
+    }
+
+    pub fn with_csv_config(mut self, csv_config: CsvConfig) -> Self {
+        self.csv_config = csv_config;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Output of a sub-job.
+pub struct JobResult {
+    scores_id: Hash,
+    commitment: Hash,
+}
+
+impl JobResult {
+    pub fn new(scores_id: Hash, commitment: Hash) -> Self {
+        Self {
+            scores_id,
+            commitment,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Description of a meta job, referenced on-chain by its `jobDescriptionId`.
+pub struct JobManifest {
+    version: u32,
+    algorithm: AlgorithmParams,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    /// Unix timestamp, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<u64>,
+    #[serde(skip)]
+    jobs: Vec<JobDescription>,
+}
+
+impl JobManifest {
+    pub fn new(algorithm: AlgorithmParams, jobs: Vec<JobDescription>) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            algorithm,
+            label: None,
+            created_at: None,
+            jobs,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Returns the domain of every sub-job. Sub-jobs written without a domain get namespaces of
+    /// their own, numbered after the sub-job.
+    ///
+    /// The datasets of a job are loaded in the same runner, so two sub-jobs can't share a
+    /// namespace.
+    pub fn domains(&self) -> Result<Vec<Domain>, Error> {
+        let domains: Vec<Domain> = (0..self.jobs.len() as u32)
+            .zip(&self.jobs)
+            .map(|(i, job)| {
+                job.domain.clone().unwrap_or(Domain::new(
+                    Address::default(),
+                    i,
+                    Address::default(),
+                    i,
+                    0,
+                ))
+            })
+            .collect();
+        let mut trust = HashSet::new();
+        let mut seed = HashSet::new();
+        for domain in &domains {
+            if !trust.insert(domain.trust_namespace()) {
+                return Err(Error::SharedNamespace(domain.trust_namespace()));
+            }
+            if !seed.insert(domain.seed_namespace()) {
+                return Err(Error::SharedNamespace(domain.seed_namespace()));
+            }
+        }
+        Ok(domains)
+    }
+
+    /// Encodes the manifest as JSON Lines.
+    pub fn to_jsonl(&self) -> Result<Vec<u8>, Error> {
+        write_lines(ManifestKind::Job, self, &self.jobs)
+    }
+
+    /// Decodes a manifest, checking its version and kind.
+    pub fn from_jsonl(data: &[u8]) -> Result<Self, Error> {
+        let (mut manifest, jobs): (Self, _) = read_lines(ManifestKind::Job, data)?;
+        manifest.jobs = jobs;
+        Ok(manifest)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Results of a meta job, referenced on-chain by its `resultsId`.
+pub struct ResultManifest {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    /// Unix timestamp, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<u64>,
+    #[serde(skip)]
+    results: Vec<JobResult>,
+}
+
+impl ResultManifest {
+    pub fn new(results: Vec<JobResult>) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            label: None,
+            created_at: None,
+            results,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Encodes the manifest as JSON Lines.
+    pub fn to_jsonl(&self) -> Result<Vec<u8>, Error> {
+        write_lines(ManifestKind::Result, self, &self.results)
+    }
+
+    /// Decodes a manifest, checking its version and kind.
+    pub fn from_jsonl(data: &[u8]) -> Result<Self, Error> {
+        let (mut manifest, results): (Self, _) = read_lines(ManifestKind::Result, data)?;
+        manifest.results = results;
+        Ok(manifest)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+#[getset(get = "pub")]
+/// Data of a challenged sub-job, posted on EigenDA for re-execution.
+pub struct ChallengeData {
+    version: u32,
+    /// Commitments of the other sub-jobs, in order.
+    neighbour_commitments: Vec<Hash>,
+    trust_data: Vec<u8>,
+    seed_data: Vec<u8>,
+    scores_data: Vec<u8>,
+    #[serde(default)]
+    format: DataFormat,
+}
+
+impl ChallengeData {
+    pub fn new(
+        neighbour_commitments: Vec<Hash>,
+        trust_data: Vec<u8>,
+        seed_data: Vec<u8>,
+        scores_data: Vec<u8>,
+        format: DataFormat,
+    ) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            neighbour_commitments,
+            trust_data,
+            seed_data,
+            scores_data,
+            format,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec(self).map_err(|source| Error::Json { line: 1, source })
+    }
+
+    /// Decodes the data, checking its version.
+    pub fn from_json(data: &[u8]) -> Result<Self, Error> {
+        let probe: VersionProbe =
+            serde_json::from_slice(data).map_err(|source| Error::Json { line: 1, source })?;
+        check_version(probe.version)?;
+        serde_json::from_slice(data).map_err(|source| Error::Json { line: 1, source })
+    }
+
+    /// Consumes the data, returning the trust, seed and scores datasets.
+    pub fn into_datasets(self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        (self.trust_data, self.seed_data, self.scores_data)
+    }
+}
+
+#[derive(Deserialize)]
+/// Fields read before the rest of a header, so that newer versions fail with a clear error.
+struct VersionProbe {
+    version: u32,
+    kind: Option<ManifestKind>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header<M> {
+    kind: ManifestKind,
+    #[serde(flatten)]
+    manifest: M,
+}
+
+fn write_lines<M: Serialize, T: Serialize>(
+    kind: ManifestKind,
+    manifest: &M,
+    entries: &[T],
+) -> Result<Vec<u8>, Error> {
+    let mut bytes = serde_json::to_vec(&Header { kind, manifest })
+        .map_err(|source| Error::Json { line: 1, source })?;
+    bytes.push(b'\n');
+    for (i, entry) in entries.iter().enumerate() {
+        serde_json::to_writer(&mut bytes, entry).map_err(|source| Error::Json {
+            line: i + 2,
+            source,
+        })?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+fn read_lines<M: DeserializeOwned, T: DeserializeOwned>(
+    kind: ManifestKind,
+    data: &[u8],
+) -> Result<(M, Vec<T>), Error> {
+    // Line numbers start at 1 and count blank lines, so they match what an editor shows.
+    let mut lines = data
+        .split(|x| *x == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim_ascii()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let (line, header) = lines.next().ok_or(Error::Empty)?;
+    let probe: VersionProbe =
+        serde_json::from_slice(header).map_err(|source| Error::Json { line, source })?;
+    check_version(probe.version)?;
+    let found = probe.kind.ok_or(Error::MissingKind)?;
+    if found != kind {
+        return Err(Error::UnexpectedKind {
+            expected: kind,
+            found,
+        });
+    }
+    let header: Header<M> =
+        serde_json::from_slice(header).map_err(|source| Error::Json { line, source })?;
+
+    let entries = lines
+        .map(|(line, x)| serde_json::from_slice(x).map_err(|source| Error::Json { line, source }))
+        .collect::<Result<_, _>>()?;
+    Ok((header.manifest, entries))
+}
+
+#[derive(thiserror::Error, Debug)]
+/// Errors that can arise while encoding or decoding a manifest.
+pub enum Error {
+    /// The manifest has no header line.
+    #[error("Empty manifest")]
+    Empty,
+    /// A line is not valid JSON, or doesn't match the schema.
+    #[error("Invalid manifest line {line}: {source}")]
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+    /// The manifest was written by an incompatible version.
+    #[error(
+        "Unsupported manifest version {0}, expected {MIN_MANIFEST_VERSION} to {MANIFEST_VERSION}"
+    )]
+    UnsupportedVersion(u32),
+    /// The header doesn't say which kind of manifest it is.
+    #[error("Manifest header has no kind")]
+    MissingKind,
+    /// The manifest is of another kind, e.g. results where a job was expected.
+    #[error("Expected a {expected} manifest, found a {found} manifest")]
+    UnexpectedKind {
+        expected: ManifestKind,
+        found: ManifestKind,
+    },
+    /// The algorithm or its parameters are not implemented.
+    #[error("Unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    /// The manifest has no sub-job at the index.
+    #[error("Sub-job {0} not found in the manifest")]
+    SubJobNotFound(usize),
+    /// Two sub-jobs load their datasets in the same namespace.
+    #[error("Namespace {0} is used by more than one sub-job")]
+    SharedNamespace(OwnedNamespace),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::{CsvConfig, DataFormat};
+    use crate::manifest::{
+        AlgorithmParams, ChallengeData, Error, JobDescription, JobManifest, JobResult,
+        ResultManifest, MANIFEST_VERSION,
+    };
+    use crate::merkle::Hash;
+    use crate::Domain;
+    use alloy_primitives::Address;
+
+    fn job_manifest() -> JobManifest {
+        let jobs = vec![
+            JobDescription::new(
+                Hash::from_bytes([1; 32]),
+                Hash::from_bytes([2; 32]),
+                DataFormat::Csv,
+            ),
+            JobDescription::new(
+                Hash::from_bytes([3; 32]),
+                Hash::from_bytes([4; 32]),
+                DataFormat::Csv,
+            )
+            .with_csv_config(CsvConfig::new(false, b'\t'))
+            .with_domain(Domain::new(Address::repeat_byte(1), 7, Address::ZERO, 8, 0)),
+        ];
+        JobManifest::new(AlgorithmParams::default(), jobs)
+            .with_label("weekly")
+            .with_created_at(1_700_000_000)
+    }
+
+    #[test]
+    fn should_round_trip_manifests() {
+        let manifest = job_manifest();
+        let bytes = manifest.to_jsonl().unwrap();
+        assert_eq!(bytes.iter().filter(|x| **x == b'\n').count(), 3);
+        assert_eq!(JobManifest::from_jsonl(&bytes).unwrap(), manifest);
+
+        let results = ResultManifest::new(vec![JobResult::new(
+            Hash::from_bytes([5; 32]),
+            Hash::from_bytes([6; 32]),
+        )]);
+        let bytes = results.to_jsonl().unwrap();
+        let decoded = ResultManifest::from_jsonl(&bytes).unwrap();
+        assert_eq!(decoded, results);
+        assert_eq!(decoded.label(), &None);
+
+        let challenge = ChallengeData::new(
+            vec![Hash::from_bytes([7; 32])],
+            vec![1],
+            vec![2],
+            vec![3],
+            DataFormat::ArrowIpc,
+        );
+        let decoded = ChallengeData::from_json(&challenge.to_json().unwrap()).unwrap();
+        assert_eq!(
+            decoded.neighbour_commitments(),
+            challenge.neighbour_commitments()
+        );
+        assert_eq!(decoded.format(), &DataFormat::ArrowIpc);
+    }
+
+    #[test]
+    fn should_reject_incompatible_manifests() {
+        let bytes = job_manifest().to_jsonl().unwrap();
+        let newer = String::from_utf8(bytes.clone()).unwrap().replace(
+            &format!("\"version\":{MANIFEST_VERSION}"),
+            &format!("\"version\":{}", MANIFEST_VERSION + 1),
+        );
+        let res = JobManifest::from_jsonl(newer.as_bytes());
+        assert!(matches!(res, Err(Error::UnsupportedVersion(v)) if v == MANIFEST_VERSION + 1));
+
+        let res = ResultManifest::from_jsonl(&bytes);
+        assert!(matches!(res, Err(Error::UnexpectedKind { .. })));
+
+        // The pre-manifest format was a bare JSON array.
+        let res = JobManifest::from_jsonl(b"[{\"alpha\":0.5}]");
+        assert!(matches!(res, Err(Error::Json { line: 1, .. })));
+
+        let params = AlgorithmParams::EigenTrust {
+            alpha: 0.2,
+            delta: 0.01,
+        };
+        assert!(matches!(
+            params.check_supported(),
+            Err(Error::UnsupportedAlgorithm(_))
+        ));
+    }
+
+    #[test]
+    fn should_check_the_domains_of_the_sub_jobs() {
+        let manifest = job_manifest();
+        let domains = manifest.domains().unwrap();
+        assert_eq!(
+            domains[0],
+            Domain::new(Address::ZERO, 0, Address::ZERO, 0, 0)
+        );
+        assert_eq!(
+            domains[1],
+            Domain::new(Address::repeat_byte(1), 7, Address::ZERO, 8, 0)
+        );
+
+        let jobs = vec![
+            JobDescription::new(Hash::default(), Hash::default(), DataFormat::Csv),
+            JobDescription::new(Hash::default(), Hash::default(), DataFormat::Csv)
+                .with_domain(Domain::new(Address::ZERO, 1, Address::ZERO, 0, 0)),
+        ];
+        let manifest = JobManifest::new(AlgorithmParams::default(), jobs);
+        let res = manifest.domains();
+        assert!(matches!(res, Err(Error::SharedNamespace(_))));
+    }
+
+    #[test]
+    fn should_report_line_numbers() {
+        let mut bytes = job_manifest().to_jsonl().unwrap();
+        bytes.extend_from_slice(b"\n{\"trust_id\":\"zz\"}\n");
+        let res = JobManifest::from_jsonl(&bytes);
+        assert!(matches!(res, Err(Error::Json { line: 5, .. })));
+    }
+}
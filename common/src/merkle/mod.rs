@@ -23,9 +23,9 @@ impl Hash {
         if slice.len() > 32 {
             bytes.copy_from_slice(&slice[..32]);
         } else {
-            bytes[..slice.len()].copy_from_slice(&slice);
+            bytes[..slice.len()].copy_from_slice(slice);
         }
-        Self(<[u8; 32]>::try_from(bytes).unwrap())
+        Self(bytes)
     }
 
     pub fn from_bytes(bytes: [u8; 32]) -> Self {
@@ -52,7 +52,7 @@ impl Display for Hash {
 impl Hash {
     /// Generates a random hash. This is used for testing.
     pub fn random<R: Rng>(rng: &mut R) -> Self {
-        Hash(rng.gen::<[u8; 32]>())
+        Hash(rng.random::<[u8; 32]>())
     }
 }
 
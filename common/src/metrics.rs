@@ -0,0 +1,198 @@
+//! Prometheus metrics of the nodes.
+//!
+//! Metrics are process-wide statics, registered in the registry of this module on first use,
+//! updated where the work happens and rendered in the Prometheus text format by the `/metrics`
+//! endpoint of [crate::health::serve].
+
+use prometheus::core::Collector;
+use prometheus::{
+    GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+/// Buckets of the job stage durations, in seconds.
+const STAGE_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+/// Buckets of the EigenDA proxy request durations, in seconds.
+const EIGENDA_BUCKETS: &[f64] = &[0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0];
+
+/// Registry of every metric of this module.
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+/// Jobs of the job store, by state.
+pub static JOBS: LazyLock<IntGaugeVec> =
+    LazyLock::new(|| int_gauge("openrank_jobs", "Jobs of the job store.", &["state"]));
+/// Durations of the job stages: `download`, `compute`, `verify` and `upload`.
+pub static STAGE_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    histogram(
+        "openrank_stage_duration_seconds",
+        "Duration of the job stages.",
+        &["stage"],
+        STAGE_BUCKETS,
+    )
+});
+/// Iterations of the last EigenTrust run, by domain.
+pub static ET_ITERATIONS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    int_gauge(
+        "openrank_eigentrust_iterations",
+        "Iterations of the last EigenTrust run.",
+        &["domain"],
+    )
+});
+/// Final delta of the last EigenTrust run, by domain.
+pub static ET_DELTA: LazyLock<GaugeVec> = LazyLock::new(|| {
+    gauge(
+        "openrank_eigentrust_delta",
+        "Final delta of the last EigenTrust run.",
+        &["domain"],
+    )
+});
+/// Peers of the last trust graph loaded, by domain.
+pub static GRAPH_PEERS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    int_gauge(
+        "openrank_graph_peers",
+        "Peers of the last trust graph loaded.",
+        &["domain"],
+    )
+});
+/// Edges of the last trust graph loaded, by domain.
+pub static GRAPH_EDGES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    int_gauge(
+        "openrank_graph_edges",
+        "Edges of the last trust graph loaded.",
+        &["domain"],
+    )
+});
+/// Challenges, by outcome: `submitted`, `failed`, `observed`, `reexecution_requested` and
+/// `responded`.
+pub static CHALLENGES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    counter(
+        "openrank_challenges_total",
+        "Challenges, by outcome.",
+        &["outcome"],
+    )
+});
+/// Verified sub-jobs, by result: `valid` or `invalid`.
+pub static VERIFICATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    counter(
+        "openrank_verifications_total",
+        "Verified sub-jobs, by result.",
+        &["result"],
+    )
+});
+/// Failed transactions, by reason.
+pub static TX_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    counter(
+        "openrank_tx_failures_total",
+        "Failed transactions, by reason.",
+        &["reason"],
+    )
+});
+/// Last processed block, by cursor.
+pub static LAST_BLOCK: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    int_gauge(
+        "openrank_last_processed_block",
+        "Last processed block, by cursor.",
+        &["cursor"],
+    )
+});
+/// Last chain head seen by the event ingestion, by cursor.
+pub static CHAIN_HEAD: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    int_gauge(
+        "openrank_chain_head_block",
+        "Last chain head seen by the event ingestion, by cursor.",
+        &["cursor"],
+    )
+});
+/// Durations of the EigenDA proxy requests, by operation: `put` or `get`.
+pub static EIGENDA_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    histogram(
+        "openrank_eigenda_request_duration_seconds",
+        "Duration of the EigenDA proxy requests.",
+        &["op"],
+        EIGENDA_BUCKETS,
+    )
+});
+/// Bytes sent to and received from the EigenDA proxy, by operation.
+pub static EIGENDA_BYTES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    counter(
+        "openrank_eigenda_bytes_total",
+        "Bytes sent to and received from the EigenDA proxy.",
+        &["op"],
+    )
+});
+
+/// Registers the metric. Metrics are statics, so they can't be registered twice.
+fn register<T: Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Metric registered twice");
+    metric
+}
+
+/// Value that only goes up.
+fn counter(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    register(IntCounterVec::new(Opts::new(name, help), labels).expect("Invalid counter"))
+}
+
+/// Whole value that can go up and down.
+fn int_gauge(name: &str, help: &str, labels: &[&str]) -> IntGaugeVec {
+    register(IntGaugeVec::new(Opts::new(name, help), labels).expect("Invalid gauge"))
+}
+
+/// Value that can go up and down.
+fn gauge(name: &str, help: &str, labels: &[&str]) -> GaugeVec {
+    register(GaugeVec::new(Opts::new(name, help), labels).expect("Invalid gauge"))
+}
+
+/// Distribution of observations over the buckets.
+fn histogram(name: &str, help: &str, labels: &[&str], buckets: &[f64]) -> HistogramVec {
+    let opts = HistogramOpts::new(name, help).buckets(buckets.to_vec());
+    register(HistogramVec::new(opts, labels).expect("Invalid histogram"))
+}
+
+/// Records the time elapsed since the start, in seconds.
+pub fn observe_since(histogram: &HistogramVec, labels: &[&str], start: Instant) {
+    histogram
+        .with_label_values(labels)
+        .observe(start.elapsed().as_secs_f64());
+}
+
+/// Renders every metric used so far in the Prometheus text format.
+pub fn render() -> String {
+    TextEncoder::new()
+        .encode_to_string(&REGISTRY.gather())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::{observe_since, render, CHALLENGES, EIGENDA_DURATION, ET_DELTA};
+    use std::time::Instant;
+
+    #[test]
+    fn should_render_counters_gauges_and_histograms() {
+        CHALLENGES.with_label_values(&["test\"outcome"]).inc_by(3);
+        ET_DELTA.with_label_values(&["test-domain"]).set(0.5);
+        let histogram = EIGENDA_DURATION.with_label_values(&["test-op"]);
+        histogram.observe(0.07);
+        histogram.observe(100.0);
+        observe_since(&EIGENDA_DURATION, &["test-op"], Instant::now());
+
+        assert_eq!(CHALLENGES.with_label_values(&["test\"outcome"]).get(), 3);
+        assert_eq!(histogram.get_sample_count(), 3);
+
+        let out = render();
+        assert!(out.contains("# TYPE openrank_challenges_total counter\n"));
+        assert!(out.contains("openrank_challenges_total{outcome=\"test\\\"outcome\"} 3\n"));
+        assert!(out.contains("openrank_eigentrust_delta{domain=\"test-domain\"} 0.5\n"));
+        assert!(out.contains("# TYPE openrank_eigenda_request_duration_seconds histogram\n"));
+        assert!(out.contains(
+            "openrank_eigenda_request_duration_seconds_bucket{op=\"test-op\",le=\"0.1\"} 2\n"
+        ));
+        assert!(out.contains(
+            "openrank_eigenda_request_duration_seconds_bucket{op=\"test-op\",le=\"+Inf\"} 3\n"
+        ));
+        assert!(out.contains("openrank_eigenda_request_duration_seconds_count{op=\"test-op\"} 3\n"));
+    }
+}
@@ -1,16 +1,65 @@
 use crate::{
-    algos::et::positive_run,
+    algos::et::{positive_run, Convergence},
     merkle::{self, fixed::DenseMerkleTree, hash_leaf, Hash},
+    metrics,
     tx::trust::{ScoreEntry, TrustEntry},
     Domain, DomainHash,
 };
 use getset::Getters;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::{
+    iter::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPool, ThreadPoolBuilder,
+};
 use sha3::Keccak256;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
 use tracing::info;
 
-use super::{BaseRunner, Error as BaseError};
+use super::{BaseRunner, Error as BaseError, OutboundLocalTrust};
+
+/// Rough estimate of the memory used by a single local trust or seed entry while computing,
+/// accounting for the copies made during pre-processing and normalisation.
+const ESTIMATED_BYTES_PER_ENTRY: u64 = 128;
+
+#[derive(Clone, Debug, Default, Getters)]
+#[getset(get = "pub")]
+/// Configuration of the batch compute, see [ComputeRunner::compute_all].
+pub struct BatchConfig {
+    /// Number of threads of the pool the domains are computed on.
+    /// The global rayon pool is used if not set.
+    num_threads: Option<usize>,
+    /// Upper bound of the estimated memory used by the domains loaded and computed at the
+    /// same time, see [ComputeRunner::estimated_bytes].
+    /// All domains are computed concurrently if not set.
+    memory_budget_bytes: Option<u64>,
+}
+
+impl BatchConfig {
+    pub fn new(num_threads: Option<usize>, memory_budget_bytes: Option<u64>) -> Self {
+        Self {
+            num_threads,
+            memory_budget_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Getters)]
+#[getset(get = "pub")]
+/// Outcome of computing a single domain in a batch.
+pub struct DomainComputeReport {
+    domain_hash: DomainHash,
+    num_scores: usize,
+    compute_root: Hash,
+    duration: Duration,
+}
+
+#[derive(Clone, Debug, Default, Getters)]
+#[getset(get = "pub")]
+/// Outcome of a batch compute, with the reports ordered as the given domains.
+pub struct ComputeReport {
+    domains: Vec<DomainComputeReport>,
+    total_duration: Duration,
+}
 
 #[derive(Getters)]
 #[getset(get = "pub")]
@@ -19,6 +68,9 @@ pub struct ComputeRunner {
     base: BaseRunner,
     compute_results: HashMap<DomainHash, Vec<(u64, f32)>>,
     compute_tree: HashMap<DomainHash, DenseMerkleTree<Keccak256>>,
+    batch_config: BatchConfig,
+    #[getset(skip)]
+    thread_pool: Option<ThreadPool>,
 }
 
 impl ComputeRunner {
@@ -33,14 +85,31 @@ impl ComputeRunner {
             base,
             compute_results,
             compute_tree: HashMap::new(),
+            batch_config: BatchConfig::default(),
+            thread_pool: None,
         }
     }
 
+    /// Set the configuration used by [ComputeRunner::compute_all].
+    pub fn set_batch_config(&mut self, batch_config: BatchConfig) -> Result<(), Error> {
+        self.thread_pool = match batch_config.num_threads() {
+            Some(num_threads) => Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(*num_threads)
+                    .build()
+                    .map_err(|e| Error::ThreadPool(e.to_string()))?,
+            ),
+            None => None,
+        };
+        self.batch_config = batch_config;
+        Ok(())
+    }
+
     /// Update the state of trees for certain domain, with the given trust entries.
     pub fn update_trust(
         &mut self,
         domain: Domain,
-        trust_entries: Vec<TrustEntry>,
+        trust_entries: impl IntoIterator<Item = TrustEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_trust(domain, trust_entries)
@@ -50,18 +119,45 @@ impl ComputeRunner {
     pub fn update_trust_map(
         &mut self,
         domain: Domain,
-        trust_entries: Vec<TrustEntry>,
+        trust_entries: impl IntoIterator<Item = TrustEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_trust_map(domain, trust_entries)
             .map_err(Error::Base)
     }
 
+    /// Remove the given peers from certain domain, and compact the remaining indices.
+    pub fn remove_peers(&mut self, domain: Domain, peer_ids: Vec<String>) -> Result<(), Error> {
+        self.base
+            .remove_peers(domain, peer_ids)
+            .map_err(Error::Base)
+    }
+
+    /// Compact the peer indices of certain domain.
+    pub fn compact_indices(&mut self, domain: Domain) -> Result<(), Error> {
+        self.base.compact_indices(domain).map_err(Error::Base)
+    }
+
+    /// Sort the peer indices of certain domain by peer id, so that they don't depend on the load order.
+    pub fn sort_indices(&mut self, domain: Domain) -> Result<(), Error> {
+        self.base.sort_indices(domain).map_err(Error::Base)
+    }
+
+    /// Content id of the trust loaded for certain domain. See [BaseRunner::trust_id].
+    pub fn trust_id(&self, domain: &Domain) -> Result<Hash, Error> {
+        self.base.trust_id(domain).map_err(Error::Base)
+    }
+
+    /// Content id of the seed loaded for certain domain. See [BaseRunner::seed_id].
+    pub fn seed_id(&self, domain: &Domain) -> Result<Hash, Error> {
+        self.base.seed_id(domain).map_err(Error::Base)
+    }
+
     /// Update the state of trees for certain domain, with the given seed entries.
     pub fn update_seed(
         &mut self,
         domain: Domain,
-        seed_entries: Vec<ScoreEntry>,
+        seed_entries: impl IntoIterator<Item = ScoreEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_seed(domain, seed_entries)
@@ -71,16 +167,36 @@ impl ComputeRunner {
     pub fn update_seed_map(
         &mut self,
         domain: Domain,
-        seed_entries: Vec<ScoreEntry>,
+        seed_entries: impl IntoIterator<Item = ScoreEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_seed_map(domain, seed_entries)
             .map_err(Error::Base)
     }
 
+    /// Drops the trust and seed data of a computed domain, keeping what is needed to read its
+    /// scores and root hashes. See [BaseRunner::release_inputs].
+    pub fn release_inputs(&mut self, domain: &Domain) {
+        self.base.release_inputs(domain);
+    }
+
+    /// Estimated memory used while computing certain domain, from its loaded trust and seed.
+    /// Zero once its inputs are released.
+    pub fn estimated_bytes(&self, domain: &Domain) -> u64 {
+        let lt = self.base.local_trust.get(&domain.trust_namespace());
+        let seed = self.base.seed_trust.get(&domain.seed_namespace());
+        match (lt, seed) {
+            (Some(lt), Some(seed)) => estimate_bytes(lt, seed),
+            (Some(lt), None) => estimate_bytes(lt, &BTreeMap::new()),
+            (None, Some(seed)) => estimate_bytes(&BTreeMap::new(), seed),
+            (None, None) => 0,
+        }
+    }
+
     /// Compute the EigenTrust scores for certain domain.
     pub fn compute(&mut self, domain: Domain) -> Result<(), Error> {
         info!("COMPUTE_RUN: {}", domain.to_hash());
+        let start = Instant::now();
         let lt = self
             .base
             .local_trust
@@ -96,11 +212,110 @@ impl ComputeRunner {
             .count
             .get(&domain.to_hash())
             .ok_or::<Error>(BaseError::CountNotFound(domain.to_hash()).into())?;
-        let res = positive_run(lt.clone(), seed.clone(), *count);
+        let (res, convergence) = positive_run(lt, seed, *count);
+        metrics::observe_since(&metrics::STAGE_DURATION, &["compute"], start);
+        record_convergence(&domain.to_hash(), convergence);
         self.compute_results.insert(domain.to_hash(), res);
         Ok(())
     }
 
+    /// Compute the EigenTrust scores and the compute trees for several domains concurrently.
+    ///
+    /// Domains are scheduled in waves whose estimated memory fits the memory budget of the
+    /// [BatchConfig], and each wave is computed in parallel on the configured thread pool.
+    pub fn compute_all(&mut self, domains: &[Domain]) -> Result<ComputeReport, Error> {
+        let start = Instant::now();
+        let mut jobs = Vec::new();
+        for domain in domains {
+            let lt = self
+                .base
+                .local_trust
+                .get(&domain.trust_namespace())
+                .ok_or::<Error>(BaseError::LocalTrustNotFound(domain.trust_namespace()).into())?;
+            let seed = self
+                .base
+                .seed_trust
+                .get(&domain.seed_namespace())
+                .ok_or::<Error>(BaseError::SeedTrustNotFound(domain.seed_namespace()).into())?;
+            let count = self
+                .base
+                .count
+                .get(&domain.to_hash())
+                .ok_or::<Error>(BaseError::CountNotFound(domain.to_hash()).into())?;
+            let estimated_bytes = estimate_bytes(lt, seed);
+            jobs.push((domain.to_hash(), lt, seed, *count, estimated_bytes));
+        }
+
+        // Greedily pack the domains into waves that fit the memory budget.
+        // A domain exceeding the budget on its own is computed alone.
+        let budget = self.batch_config.memory_budget_bytes().unwrap_or(u64::MAX);
+        let mut waves: Vec<Vec<_>> = Vec::new();
+        let mut wave_bytes = 0u64;
+        for job in jobs {
+            let fits = wave_bytes.saturating_add(job.4) <= budget;
+            match waves.last_mut() {
+                Some(wave) if fits => {
+                    wave_bytes += job.4;
+                    wave.push(job);
+                }
+                _ => {
+                    wave_bytes = job.4;
+                    waves.push(vec![job]);
+                }
+            }
+        }
+        info!(
+            "COMPUTE_ALL, NUM_DOMAINS: {}, NUM_WAVES: {}",
+            domains.len(),
+            waves.len()
+        );
+
+        let run_waves = || {
+            waves
+                .iter()
+                .flat_map(|wave| {
+                    wave.par_iter()
+                        .map(|(domain_hash, lt, seed, count, _)| {
+                            let start = Instant::now();
+                            info!("COMPUTE_RUN: {}", domain_hash);
+                            let (res, convergence) = positive_run(lt, seed, *count);
+                            record_convergence(domain_hash, convergence);
+                            let compute_tree = build_compute_tree(&res)?;
+                            Ok((*domain_hash, res, compute_tree, start.elapsed()))
+                        })
+                        .collect::<Vec<Result<_, Error>>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let results = match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(run_waves),
+            None => run_waves(),
+        };
+
+        let mut reports = Vec::new();
+        for res in results {
+            let (domain_hash, scores, compute_tree, duration) = res?;
+            metrics::STAGE_DURATION
+                .with_label_values(&["compute"])
+                .observe(duration.as_secs_f64());
+            reports.push(DomainComputeReport {
+                domain_hash,
+                num_scores: scores.len(),
+                compute_root: compute_tree.root().map_err(Error::Merkle)?,
+                duration,
+            });
+            self.compute_results.insert(domain_hash, scores);
+            self.compute_tree.insert(domain_hash, compute_tree);
+        }
+        let total_duration = start.elapsed();
+        info!("COMPUTE_ALL_END: {:?}", total_duration);
+
+        Ok(ComputeReport {
+            domains: reports,
+            total_duration,
+        })
+    }
+
     /// Create the compute tree for certain domain.
     pub fn create_compute_tree(&mut self, domain: Domain) -> Result<(), Error> {
         info!("CREATE_COMPUTE_TREE: {}", domain.to_hash());
@@ -108,40 +323,30 @@ impl ComputeRunner {
             .compute_results
             .get(&domain.to_hash())
             .ok_or(Error::ComputeResultsNotFound(domain.to_hash()))?;
-        let score_hashes: Vec<Hash> = scores
-            .par_iter()
-            .map(|(_, x)| hash_leaf::<Keccak256>(x.to_be_bytes().to_vec()))
-            .collect();
-        let compute_tree =
-            DenseMerkleTree::<Keccak256>::new(score_hashes).map_err(Error::Merkle)?;
-        info!(
-            "COMPUTE_TREE_ROOT_HASH: {}",
-            compute_tree.root().map_err(Error::Merkle)?
-        );
+        let compute_tree = build_compute_tree(scores)?;
         self.compute_tree.insert(domain.to_hash(), compute_tree);
         Ok(())
     }
 
     /// Get the compute scores for certain domain.
     pub fn get_compute_scores(&self, domain: Domain) -> Result<Vec<ScoreEntry>, Error> {
-        let domain_indices = self
+        let rev_domain_indices = self
             .base
-            .indices
+            .rev_indices
             .get(&domain.to_hash())
-            .ok_or::<Error>(BaseError::IndicesNotFound(domain.to_hash()).into())?;
+            .ok_or::<Error>(BaseError::ReverseIndicesNotFound(domain.to_hash()).into())?;
         let scores = self
             .compute_results
             .get(&domain.to_hash())
             .ok_or(Error::ComputeResultsNotFound(domain.to_hash()))?;
-        let index_to_address: HashMap<&u64, &String> =
-            domain_indices.iter().map(|(k, v)| (v, k)).collect();
 
         let mut entries = Vec::new();
         for (index, val) in scores {
-            let address = index_to_address
-                .get(&index)
+            let address = rev_domain_indices
+                .get(index)
+                .and_then(|peer_id| self.base.interner.resolve(*peer_id))
                 .ok_or(Error::IndexToAddressNotFound(*index))?;
-            let score_entry = ScoreEntry::new((*address).clone(), *val);
+            let score_entry = ScoreEntry::new(address.to_string(), *val);
             entries.push(score_entry);
         }
         Ok(entries)
@@ -161,6 +366,41 @@ impl ComputeRunner {
     }
 }
 
+/// Estimated memory used while computing the local trust and seed of a domain.
+fn estimate_bytes(lt: &BTreeMap<u64, OutboundLocalTrust>, seed: &BTreeMap<u64, f32>) -> u64 {
+    let num_entries: usize = lt
+        .values()
+        .map(|x| x.outbound_trust_scores().len())
+        .sum::<usize>()
+        + seed.len();
+    num_entries as u64 * ESTIMATED_BYTES_PER_ENTRY
+}
+
+/// Records how the EigenTrust run of the domain converged.
+fn record_convergence(domain_hash: &DomainHash, convergence: Convergence) {
+    let domain = domain_hash.to_hex();
+    metrics::ET_ITERATIONS
+        .with_label_values(&[&domain])
+        .set(convergence.iterations() as i64);
+    metrics::ET_DELTA
+        .with_label_values(&[&domain])
+        .set(convergence.delta() as f64);
+}
+
+/// Build the compute tree from the computed scores.
+fn build_compute_tree(scores: &[(u64, f32)]) -> Result<DenseMerkleTree<Keccak256>, Error> {
+    let score_hashes: Vec<Hash> = scores
+        .par_iter()
+        .map(|(_, x)| hash_leaf::<Keccak256>(x.to_be_bytes().to_vec()))
+        .collect();
+    let compute_tree = DenseMerkleTree::<Keccak256>::new(score_hashes).map_err(Error::Merkle)?;
+    info!(
+        "COMPUTE_TREE_ROOT_HASH: {}",
+        compute_tree.root().map_err(Error::Merkle)?
+    );
+    Ok(compute_tree)
+}
+
 #[derive(thiserror::Error, Debug)]
 /// Errors that can arise while using the compute runner.
 pub enum Error {
@@ -178,6 +418,9 @@ pub enum Error {
     /// The compute merkle tree error.
     #[error("Merkle Error: {0}")]
     Merkle(merkle::Error),
+    /// The thread pool for the batch compute could not be built.
+    #[error("ThreadPool Error: {0}")]
+    ThreadPool(String),
 }
 
 impl From<BaseError> for Error {
@@ -185,3 +428,83 @@ impl From<BaseError> for Error {
         Self::Base(err)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        runners::compute_runner::{BatchConfig, ComputeRunner},
+        tx::trust::{ScoreEntry, TrustEntry},
+        Domain,
+    };
+    use alloy_primitives::Address;
+
+    fn domain(id: u32) -> Domain {
+        Domain::new(Address::default(), id, Address::default(), id, 0)
+    }
+
+    fn load(runner: &mut ComputeRunner, domain: Domain, n: u32) {
+        let trust_entries: Vec<TrustEntry> = (0..n)
+            .map(|i| TrustEntry::new(i.to_string(), ((i + 1) % n).to_string(), 1.0))
+            .collect();
+        runner
+            .update_trust_map(domain.clone(), trust_entries)
+            .unwrap();
+        runner
+            .update_seed_map(domain, vec![ScoreEntry::new("0".to_string(), 1.0)])
+            .unwrap();
+    }
+
+    #[test]
+    fn should_compute_all_domains_like_compute() {
+        let domains = vec![domain(0), domain(1), domain(2)];
+        let mut batch = ComputeRunner::new(&domains);
+        let mut single = ComputeRunner::new(&domains);
+        for (i, domain) in domains.iter().enumerate() {
+            load(&mut batch, domain.clone(), 3 + i as u32);
+            load(&mut single, domain.clone(), 3 + i as u32);
+        }
+        // A budget of a single byte forces every domain into its own wave.
+        batch
+            .set_batch_config(BatchConfig::new(Some(2), Some(1)))
+            .unwrap();
+        let report = batch.compute_all(&domains).unwrap();
+
+        assert_eq!(report.domains().len(), domains.len());
+        for (domain, domain_report) in domains.iter().zip(report.domains()) {
+            single.compute(domain.clone()).unwrap();
+            single.create_compute_tree(domain.clone()).unwrap();
+            let (_, compute_root) = single.get_root_hashes(domain.clone()).unwrap();
+
+            assert_eq!(domain_report.domain_hash(), &domain.to_hash());
+            assert_eq!(domain_report.compute_root(), &compute_root);
+            assert_eq!(
+                batch.get_compute_scores(domain.clone()).unwrap(),
+                single.get_compute_scores(domain.clone()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn should_keep_scores_after_releasing_inputs() {
+        let domains = vec![domain(0), domain(1)];
+        let mut runner = ComputeRunner::new(&domains);
+        load(&mut runner, domains[0].clone(), 4);
+        assert!(runner.estimated_bytes(&domains[0]) > 0);
+        runner.compute_all(&domains[..1]).unwrap();
+        let scores = runner.get_compute_scores(domains[0].clone()).unwrap();
+        let roots = runner.get_root_hashes(domains[0].clone()).unwrap();
+
+        runner.release_inputs(&domains[0]);
+        assert_eq!(runner.estimated_bytes(&domains[0]), 0);
+        assert!(runner.compute(domains[0].clone()).is_err());
+        assert_eq!(
+            runner.get_compute_scores(domains[0].clone()).unwrap(),
+            scores
+        );
+        assert_eq!(runner.get_root_hashes(domains[0].clone()).unwrap(), roots);
+
+        // The other domains are not affected.
+        load(&mut runner, domains[1].clone(), 3);
+        runner.compute_all(&domains[1..]).unwrap();
+    }
+}
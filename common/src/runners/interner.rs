@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+/// Handle of a peer identifier stored in the [PeerInterner].
+pub struct PeerId(u32);
+
+impl PeerId {
+    /// Get the inner value of the handle.
+    pub fn inner(self) -> u32 {
+        self.0
+    }
+}
+
+impl Display for PeerId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// String interner for peer identifiers.
+///
+/// Each identifier is stored once and shared by all the domains of a runner,
+/// which refer to it through its [PeerId].
+/// Identifiers are never removed, since they might still be used by other domains.
+pub struct PeerInterner {
+    /// Lookup from the identifier to its handle.
+    ids: HashMap<Arc<str>, PeerId>,
+    /// Identifiers, indexed by their handle.
+    names: Vec<Arc<str>>,
+}
+
+impl PeerInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the handle of the identifier, storing it if it is not interned yet.
+    pub fn intern(&mut self, name: &str) -> Result<PeerId, Error> {
+        if let Some(id) = self.ids.get(name) {
+            return Ok(*id);
+        }
+        let id = PeerId(u32::try_from(self.names.len()).map_err(|_| Error::TooManyPeers)?);
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        Ok(id)
+    }
+
+    /// Returns the handle of the identifier, if it is interned.
+    pub fn get(&self, name: &str) -> Option<PeerId> {
+        self.ids.get(name).copied()
+    }
+
+    /// Returns the identifier behind the handle.
+    pub fn resolve(&self, id: PeerId) -> Option<&str> {
+        self.names.get(id.0 as usize).map(|x| x.as_ref())
+    }
+
+    /// Iterates over the handles and their identifiers, in interning order.
+    /// Every index fits in a handle, see [PeerInterner::intern].
+    pub fn iter(&self) -> impl Iterator<Item = (PeerId, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (PeerId(i as u32), name.as_ref()))
+    }
+
+    /// Returns the number of interned identifiers.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if no identifiers are interned.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+/// Errors that can arise while interning peer identifiers.
+pub enum Error {
+    /// Every [PeerId] is taken.
+    #[error("Too many peers: at most {} identifiers can be interned", u32::MAX as u64 + 1)]
+    TooManyPeers,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runners::interner::PeerInterner;
+
+    #[test]
+    fn should_intern_peer_ids_once() {
+        let mut interner = PeerInterner::new();
+        let a = interner.intern("0xa").unwrap();
+        let b = interner.intern("0xb").unwrap();
+        assert_eq!(interner.intern("0xa"), Ok(a));
+        assert_ne!(a, b);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get("0xb"), Some(b));
+        assert_eq!(interner.get("0xc"), None);
+        assert_eq!(interner.resolve(a), Some("0xa"));
+        let all: Vec<_> = interner.iter().collect();
+        assert_eq!(all, vec![(a, "0xa"), (b, "0xb")]);
+    }
+}
@@ -1,16 +1,20 @@
 use crate::{
     merkle::{self, hash_leaf, hash_two, incremental::DenseIncrementalMerkleTree, Hash},
+    metrics,
+    runners::interner::{Error as InternerError, PeerId, PeerInterner},
+    tx::canonical::{SortedSetHasher, SCORE_SET_TAG, TRUST_SET_TAG},
     tx::trust::{OwnedNamespace, ScoreEntry, TrustEntry},
     Domain, DomainHash,
 };
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 use sha3::Keccak256;
-use std::collections::BTreeMap;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{HashMap, HashSet};
 use tracing::info;
 
 pub mod compute_runner;
+pub mod interner;
 pub mod verification_runner;
 
 /// Local trust object.
@@ -111,11 +115,16 @@ impl OutboundLocalTrust {
 #[derive(Getters)]
 #[getset(get = "pub")]
 pub struct BaseRunner {
+    interner: PeerInterner,
     count: HashMap<DomainHash, u64>,
-    indices: HashMap<DomainHash, HashMap<String, u64>>,
-    rev_indices: HashMap<DomainHash, HashMap<u64, String>>,
+    indices: HashMap<DomainHash, HashMap<PeerId, u64>>,
+    rev_indices: HashMap<DomainHash, HashMap<u64, PeerId>>,
     local_trust: HashMap<OwnedNamespace, BTreeMap<u64, OutboundLocalTrust>>,
     seed_trust: HashMap<OwnedNamespace, BTreeMap<u64, f32>>,
+    /// Entries with a zero value, which are not part of the trust graph but of the content id
+    /// of the loaded data.
+    zero_trust: HashMap<OwnedNamespace, BTreeSet<(u64, u64)>>,
+    zero_seed: HashMap<OwnedNamespace, BTreeSet<u64>>,
     lt_sub_trees: HashMap<DomainHash, HashMap<u64, DenseIncrementalMerkleTree<Keccak256>>>,
     lt_master_tree: HashMap<DomainHash, DenseIncrementalMerkleTree<Keccak256>>,
     st_master_tree: HashMap<DomainHash, DenseIncrementalMerkleTree<Keccak256>>,
@@ -151,11 +160,14 @@ impl BaseRunner {
             compute_results.insert(domain_hash, Vec::<f32>::new());
         }
         Self {
+            interner: PeerInterner::new(),
             count,
             indices,
             rev_indices,
             local_trust,
             seed_trust,
+            zero_trust: HashMap::new(),
+            zero_seed: HashMap::new(),
             lt_sub_trees,
             lt_master_tree,
             st_master_tree,
@@ -165,8 +177,9 @@ impl BaseRunner {
     pub fn update_trust(
         &mut self,
         domain: Domain,
-        trust_entries: Vec<TrustEntry>,
+        trust_entries: impl IntoIterator<Item = TrustEntry>,
     ) -> Result<(), Error> {
+        let interner = &mut self.interner;
         let domain_indices = self
             .indices
             .get_mut(&domain.to_hash())
@@ -193,23 +206,26 @@ impl BaseRunner {
             .local_trust
             .get_mut(&domain.trust_namespace())
             .ok_or::<Error>(Error::LocalTrustNotFound(domain.trust_namespace()))?;
+        let zeros = self.zero_trust.entry(domain.trust_namespace()).or_default();
         let default_sub_tree = DenseIncrementalMerkleTree::<Keccak256>::new(32);
         for entry in trust_entries {
-            let from_index = if let Some(i) = domain_indices.get(entry.from()) {
+            let from_id = interner.intern(entry.from()).map_err(Error::Interner)?;
+            let from_index = if let Some(i) = domain_indices.get(&from_id) {
                 *i
             } else {
                 let curr_count = *count;
-                domain_indices.insert(entry.from().clone(), curr_count);
-                rev_domain_indices.insert(curr_count, entry.from().clone());
+                domain_indices.insert(from_id, curr_count);
+                rev_domain_indices.insert(curr_count, from_id);
                 *count += 1;
                 curr_count
             };
-            let to_index = if let Some(i) = domain_indices.get(entry.to()) {
+            let to_id = interner.intern(entry.to()).map_err(Error::Interner)?;
+            let to_index = if let Some(i) = domain_indices.get(&to_id) {
                 *i
             } else {
                 let curr_count = *count;
-                domain_indices.insert(entry.to().clone(), curr_count);
-                rev_domain_indices.insert(curr_count, entry.to().clone());
+                domain_indices.insert(to_id, curr_count);
+                rev_domain_indices.insert(curr_count, to_id);
                 *count += 1;
                 curr_count
             };
@@ -222,6 +238,11 @@ impl BaseRunner {
             } else if !is_zero {
                 from_map.insert(to_index, *entry.value());
             }
+            if is_zero {
+                zeros.insert((from_index, to_index));
+            } else {
+                zeros.remove(&(from_index, to_index));
+            }
 
             lt_sub_trees
                 .entry(from_index)
@@ -238,6 +259,7 @@ impl BaseRunner {
             let leaf = hash_leaf::<Keccak256>(sub_tree_root.inner().to_vec());
             lt_master_tree.insert_leaf(from_index, leaf);
         }
+        record_graph_size(&domain, *count, lt);
         let lt_root = lt_master_tree.root().map_err(Error::Merkle)?;
         info!(
             "LT_UPDATE, DOMAIN: {}, NEW_MERKLE_ROOT: {}",
@@ -251,8 +273,9 @@ impl BaseRunner {
     pub fn update_trust_map(
         &mut self,
         domain: Domain,
-        trust_entries: Vec<TrustEntry>,
+        trust_entries: impl IntoIterator<Item = TrustEntry>,
     ) -> Result<(), Error> {
+        let interner = &mut self.interner;
         let domain_indices = self
             .indices
             .get_mut(&domain.to_hash())
@@ -269,22 +292,25 @@ impl BaseRunner {
             .local_trust
             .get_mut(&domain.trust_namespace())
             .ok_or::<Error>(Error::LocalTrustNotFound(domain.trust_namespace()))?;
+        let zeros = self.zero_trust.entry(domain.trust_namespace()).or_default();
         for entry in trust_entries {
-            let from_index = if let Some(i) = domain_indices.get(entry.from()) {
+            let from_id = interner.intern(entry.from()).map_err(Error::Interner)?;
+            let from_index = if let Some(i) = domain_indices.get(&from_id) {
                 *i
             } else {
                 let curr_count = *count;
-                domain_indices.insert(entry.from().clone(), curr_count);
-                rev_domain_indices.insert(curr_count, entry.from().clone());
+                domain_indices.insert(from_id, curr_count);
+                rev_domain_indices.insert(curr_count, from_id);
                 *count += 1;
                 curr_count
             };
-            let to_index = if let Some(i) = domain_indices.get(entry.to()) {
+            let to_id = interner.intern(entry.to()).map_err(Error::Interner)?;
+            let to_index = if let Some(i) = domain_indices.get(&to_id) {
                 *i
             } else {
                 let curr_count = *count;
-                domain_indices.insert(entry.to().clone(), curr_count);
-                rev_domain_indices.insert(curr_count, entry.to().clone());
+                domain_indices.insert(to_id, curr_count);
+                rev_domain_indices.insert(curr_count, to_id);
                 *count += 1;
                 curr_count
             };
@@ -297,7 +323,13 @@ impl BaseRunner {
             } else if !is_zero {
                 from_map.insert(to_index, *entry.value());
             }
+            if is_zero {
+                zeros.insert((from_index, to_index));
+            } else {
+                zeros.remove(&(from_index, to_index));
+            }
         }
+        record_graph_size(&domain, *count, lt);
         info!("LT_MAP_UPDATE, DOMAIN: {}", domain.to_hash(),);
 
         Ok(())
@@ -306,8 +338,9 @@ impl BaseRunner {
     pub fn update_seed(
         &mut self,
         domain: Domain,
-        seed_entries: Vec<ScoreEntry>,
+        seed_entries: impl IntoIterator<Item = ScoreEntry>,
     ) -> Result<(), Error> {
+        let interner = &mut self.interner;
         let domain_indices = self
             .indices
             .get_mut(&domain.to_hash())
@@ -328,13 +361,15 @@ impl BaseRunner {
             .seed_trust
             .get_mut(&domain.seed_namespace())
             .ok_or::<Error>(Error::SeedTrustNotFound(domain.seed_namespace()))?;
+        let zeros = self.zero_seed.entry(domain.seed_namespace()).or_default();
         for entry in seed_entries {
-            let index = if let Some(i) = domain_indices.get(entry.id()) {
+            let peer_id = interner.intern(entry.id()).map_err(Error::Interner)?;
+            let index = if let Some(i) = domain_indices.get(&peer_id) {
                 *i
             } else {
                 let curr_count = *count;
-                domain_indices.insert(entry.id().clone(), curr_count);
-                rev_domain_indices.insert(curr_count, entry.id().clone());
+                domain_indices.insert(peer_id, curr_count);
+                rev_domain_indices.insert(curr_count, peer_id);
                 *count += 1;
                 curr_count
             };
@@ -345,6 +380,11 @@ impl BaseRunner {
             } else if !is_zero {
                 seed.insert(index, *entry.value());
             }
+            if is_zero {
+                zeros.insert(index);
+            } else {
+                zeros.remove(&index);
+            }
 
             let leaf = hash_leaf::<Keccak256>(entry.value().to_be_bytes().to_vec());
             st_master_tree.insert_leaf(index, leaf);
@@ -362,8 +402,9 @@ impl BaseRunner {
     pub fn update_seed_map(
         &mut self,
         domain: Domain,
-        seed_entries: Vec<ScoreEntry>,
+        seed_entries: impl IntoIterator<Item = ScoreEntry>,
     ) -> Result<(), Error> {
+        let interner = &mut self.interner;
         let domain_indices = self
             .indices
             .get_mut(&domain.to_hash())
@@ -380,13 +421,15 @@ impl BaseRunner {
             .seed_trust
             .get_mut(&domain.seed_namespace())
             .ok_or::<Error>(Error::SeedTrustNotFound(domain.seed_namespace()))?;
+        let zeros = self.zero_seed.entry(domain.seed_namespace()).or_default();
         for entry in seed_entries {
-            let index = if let Some(i) = domain_indices.get(entry.id()) {
+            let peer_id = interner.intern(entry.id()).map_err(Error::Interner)?;
+            let index = if let Some(i) = domain_indices.get(&peer_id) {
                 *i
             } else {
                 let curr_count = *count;
-                domain_indices.insert(entry.id().clone(), curr_count);
-                rev_domain_indices.insert(curr_count, entry.id().clone());
+                domain_indices.insert(peer_id, curr_count);
+                rev_domain_indices.insert(curr_count, peer_id);
                 *count += 1;
                 curr_count
             };
@@ -397,12 +440,342 @@ impl BaseRunner {
             } else if !is_zero {
                 seed.insert(index, *entry.value());
             }
+            if is_zero {
+                zeros.insert(index);
+            } else {
+                zeros.remove(&index);
+            }
         }
         info!("ST_MAP_UPDATE, DOMAIN: {}", domain.to_hash(),);
 
         Ok(())
     }
 
+    /// Removes the given peers from the domain, together with their outbound trust,
+    /// any trust they receive and their seed trust.
+    ///
+    /// The remaining peers are compacted afterwards, see [BaseRunner::compact_indices].
+    pub fn remove_peers(&mut self, domain: Domain, peer_ids: Vec<String>) -> Result<(), Error> {
+        let interner = &self.interner;
+        let domain_indices = self
+            .indices
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::IndicesNotFound(domain.to_hash()))?;
+        let rev_domain_indices = self
+            .rev_indices
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::ReverseIndicesNotFound(domain.to_hash()))?;
+        let lt = self
+            .local_trust
+            .get_mut(&domain.trust_namespace())
+            .ok_or::<Error>(Error::LocalTrustNotFound(domain.trust_namespace()))?;
+        let seed = self
+            .seed_trust
+            .get_mut(&domain.seed_namespace())
+            .ok_or::<Error>(Error::SeedTrustNotFound(domain.seed_namespace()))?;
+
+        let mut interned_ids = Vec::new();
+        for peer_id in peer_ids {
+            match interner.get(&peer_id) {
+                Some(x) if domain_indices.contains_key(&x) => interned_ids.push(x),
+                _ => return Err(Error::DomainIndexNotFound(peer_id)),
+            }
+        }
+        let mut removed = HashSet::new();
+        for peer_id in interned_ids {
+            if let Some(index) = domain_indices.remove(&peer_id) {
+                rev_domain_indices.remove(&index);
+                removed.insert(index);
+            }
+        }
+
+        lt.retain(|from, _| !removed.contains(from));
+        for from_map in lt.values_mut() {
+            let to_remove: Vec<u64> = from_map
+                .outbound_trust_scores()
+                .keys()
+                .filter(|to| removed.contains(*to))
+                .copied()
+                .collect();
+            for to in to_remove {
+                from_map.remove(&to);
+            }
+        }
+        seed.retain(|i, _| !removed.contains(i));
+        if let Some(zeros) = self.zero_trust.get_mut(&domain.trust_namespace()) {
+            zeros.retain(|(from, to)| !removed.contains(from) && !removed.contains(to));
+        }
+        if let Some(zeros) = self.zero_seed.get_mut(&domain.seed_namespace()) {
+            zeros.retain(|i| !removed.contains(i));
+        }
+        info!(
+            "PEERS_REMOVED, DOMAIN: {}, NUM_PEERS: {}",
+            domain.to_hash(),
+            removed.len()
+        );
+
+        self.compact_indices(domain)
+    }
+
+    /// Renumbers the peers of the domain so that their indices are dense (`0..count`),
+    /// keeping their relative order, and rebuilds the local trust and seed trust trees.
+    pub fn compact_indices(&mut self, domain: Domain) -> Result<(), Error> {
+        let rev_domain_indices = self
+            .rev_indices
+            .get(&domain.to_hash())
+            .ok_or::<Error>(Error::ReverseIndicesNotFound(domain.to_hash()))?;
+        let mut old_indices: Vec<u64> = rev_domain_indices.keys().copied().collect();
+        old_indices.sort_unstable();
+        let count = self.renumber_indices(&domain, old_indices)?;
+        info!(
+            "INDICES_COMPACTED, DOMAIN: {}, COUNT: {}",
+            domain.to_hash(),
+            count
+        );
+
+        Ok(())
+    }
+
+    /// Renumbers the peers of the domain in the order of their identifiers, so that the indices,
+    /// and therefore the commitments, don't depend on the order in which entries were loaded.
+    /// The indices are dense afterwards, as with [BaseRunner::compact_indices].
+    pub fn sort_indices(&mut self, domain: Domain) -> Result<(), Error> {
+        let peers = self.sorted_peers(&domain)?;
+        let old_indices = peers.into_iter().map(|(index, _)| index).collect();
+        let count = self.renumber_indices(&domain, old_indices)?;
+        info!(
+            "INDICES_SORTED, DOMAIN: {}, COUNT: {}",
+            domain.to_hash(),
+            count
+        );
+
+        Ok(())
+    }
+
+    /// Returns the indices of the peers of the domain with their identifiers, in the order of
+    /// the identifiers.
+    fn sorted_peers(&self, domain: &Domain) -> Result<Vec<(u64, &str)>, Error> {
+        let rev_domain_indices = self
+            .rev_indices
+            .get(&domain.to_hash())
+            .ok_or::<Error>(Error::ReverseIndicesNotFound(domain.to_hash()))?;
+        let mut peers = rev_domain_indices
+            .iter()
+            .map(|(index, peer_id)| {
+                let name = self
+                    .interner
+                    .resolve(*peer_id)
+                    .ok_or(Error::PeerIdNotFound(*peer_id))?;
+                Ok((*index, name))
+            })
+            .collect::<Result<Vec<(u64, &str)>, Error>>()?;
+        peers.sort_unstable_by_key(|(_, name)| *name);
+        Ok(peers)
+    }
+
+    /// Returns the [trust_id](crate::tx::canonical::trust_id) of the trust loaded for the
+    /// domain, so that the data can be checked without hashing a second copy of it. Peers
+    /// removed from the domain are not part of it anymore.
+    pub fn trust_id(&self, domain: &Domain) -> Result<Hash, Error> {
+        let peers = self.sorted_peers(domain)?;
+        let lt = self
+            .local_trust
+            .get(&domain.trust_namespace())
+            .ok_or::<Error>(Error::LocalTrustNotFound(domain.trust_namespace()))?;
+        let no_zeros = BTreeSet::new();
+        let zeros = self
+            .zero_trust
+            .get(&domain.trust_namespace())
+            .unwrap_or(&no_zeros);
+        let ranks: HashMap<u64, usize> = peers
+            .iter()
+            .enumerate()
+            .map(|(rank, (index, _))| (*index, rank))
+            .collect();
+        let rank = |index: u64| {
+            ranks
+                .get(&index)
+                .copied()
+                .ok_or(Error::DomainIndexNotFound(index.to_string()))
+        };
+
+        let edges: usize = lt.values().map(|x| x.outbound_trust_scores.len()).sum();
+        let mut hasher = SortedSetHasher::new(TRUST_SET_TAG, (edges + zeros.len()) as u64);
+        for (from, from_name) in &peers {
+            let outbound = lt.get(from).into_iter().flat_map(|x| {
+                x.outbound_trust_scores
+                    .iter()
+                    .map(|(to, value)| (*to, *value))
+            });
+            let zero = zeros
+                .range((*from, 0)..=(*from, u64::MAX))
+                .map(|(_, to)| (*to, 0.0));
+            // Only the entries of one peer are sorted at a time.
+            let mut entries = outbound
+                .chain(zero)
+                .map(|(to, value)| Ok((rank(to)?, value)))
+                .collect::<Result<Vec<(usize, f32)>, Error>>()?;
+            entries.sort_unstable_by_key(|(rank, _)| *rank);
+            for (to, value) in entries {
+                hasher.trust(from_name, peers[to].1, value);
+            }
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Returns the [scores_id](crate::tx::canonical::scores_id) of the seed loaded for the
+    /// domain. See [BaseRunner::trust_id].
+    pub fn seed_id(&self, domain: &Domain) -> Result<Hash, Error> {
+        let peers = self.sorted_peers(domain)?;
+        let seed = self
+            .seed_trust
+            .get(&domain.seed_namespace())
+            .ok_or::<Error>(Error::SeedTrustNotFound(domain.seed_namespace()))?;
+        let no_zeros = BTreeSet::new();
+        let zeros = self
+            .zero_seed
+            .get(&domain.seed_namespace())
+            .unwrap_or(&no_zeros);
+        let mut hasher = SortedSetHasher::new(SCORE_SET_TAG, (seed.len() + zeros.len()) as u64);
+        for (index, name) in peers {
+            if let Some(value) = seed.get(&index) {
+                hasher.score(name, *value);
+            } else if zeros.contains(&index) {
+                hasher.score(name, 0.0);
+            }
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Gives the new index `i` to the peer at `old_indices[i]`, dropping any other peer, and
+    /// rebuilds the trees. Returns the new peer count.
+    fn renumber_indices(&mut self, domain: &Domain, old_indices: Vec<u64>) -> Result<u64, Error> {
+        let domain_indices = self
+            .indices
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::IndicesNotFound(domain.to_hash()))?;
+        let rev_domain_indices = self
+            .rev_indices
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::ReverseIndicesNotFound(domain.to_hash()))?;
+        let count = self
+            .count
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::CountNotFound(domain.to_hash()))?;
+        let lt_sub_trees = self
+            .lt_sub_trees
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::LocalTrustSubTreesNotFoundWithDomain(
+                domain.to_hash(),
+            ))?;
+        let lt_master_tree = self
+            .lt_master_tree
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::LocalTrustMasterTreeNotFound(domain.to_hash()))?;
+        let st_master_tree = self
+            .st_master_tree
+            .get_mut(&domain.to_hash())
+            .ok_or::<Error>(Error::SeedTrustMasterTreeNotFound(domain.to_hash()))?;
+        let lt = self
+            .local_trust
+            .get_mut(&domain.trust_namespace())
+            .ok_or::<Error>(Error::LocalTrustNotFound(domain.trust_namespace()))?;
+        let seed = self
+            .seed_trust
+            .get_mut(&domain.seed_namespace())
+            .ok_or::<Error>(Error::SeedTrustNotFound(domain.seed_namespace()))?;
+
+        let remap: HashMap<u64, u64> = old_indices
+            .iter()
+            .enumerate()
+            .map(|(new, old)| (*old, new as u64))
+            .collect();
+
+        let mut new_indices = HashMap::new();
+        let mut new_rev_indices = HashMap::new();
+        for (old, new) in &remap {
+            let peer_id = rev_domain_indices
+                .remove(old)
+                .ok_or(Error::DomainIndexNotFound(old.to_string()))?;
+            new_indices.insert(peer_id, *new);
+            new_rev_indices.insert(*new, peer_id);
+        }
+        *domain_indices = new_indices;
+        *rev_domain_indices = new_rev_indices;
+        *count = old_indices.len() as u64;
+
+        *lt = std::mem::take(lt)
+            .into_iter()
+            .filter_map(|(from, from_map)| {
+                let from = remap.get(&from)?;
+                let outbound_trust_scores = from_map
+                    .outbound_trust_scores()
+                    .iter()
+                    .filter_map(|(to, value)| Some((*remap.get(to)?, *value)))
+                    .collect();
+                let mut new_from_map = OutboundLocalTrust::new();
+                new_from_map.set_outbound_trust_scores(outbound_trust_scores);
+                Some((*from, new_from_map))
+            })
+            .collect();
+        *seed = std::mem::take(seed)
+            .into_iter()
+            .filter_map(|(i, value)| Some((*remap.get(&i)?, value)))
+            .collect();
+        if let Some(zeros) = self.zero_trust.get_mut(&domain.trust_namespace()) {
+            *zeros = std::mem::take(zeros)
+                .into_iter()
+                .filter_map(|(from, to)| Some((*remap.get(&from)?, *remap.get(&to)?)))
+                .collect();
+        }
+        if let Some(zeros) = self.zero_seed.get_mut(&domain.seed_namespace()) {
+            *zeros = std::mem::take(zeros)
+                .into_iter()
+                .filter_map(|i| remap.get(&i).copied())
+                .collect();
+        }
+
+        // Trees are only rebuilt if they were in use, since `update_trust_map` and
+        // `update_seed_map` do not maintain them.
+        let default_tree = DenseIncrementalMerkleTree::<Keccak256>::new(32);
+        if !lt_sub_trees.is_empty() {
+            lt_sub_trees.clear();
+            *lt_master_tree = default_tree.clone();
+            for (from, from_map) in lt.iter() {
+                let mut sub_tree = default_tree.clone();
+                for (to, value) in from_map.outbound_trust_scores() {
+                    let leaf = hash_leaf::<Keccak256>(value.to_be_bytes().to_vec());
+                    sub_tree.insert_leaf(*to, leaf);
+                }
+                let sub_tree_root = sub_tree.root().map_err(Error::Merkle)?;
+                let leaf = hash_leaf::<Keccak256>(sub_tree_root.inner().to_vec());
+                lt_master_tree.insert_leaf(*from, leaf);
+                lt_sub_trees.insert(*from, sub_tree);
+            }
+        }
+        let default_root = default_tree.root().map_err(Error::Merkle)?;
+        if st_master_tree.root().map_err(Error::Merkle)? != default_root {
+            *st_master_tree = default_tree;
+            for (i, value) in seed.iter() {
+                let leaf = hash_leaf::<Keccak256>(value.to_be_bytes().to_vec());
+                st_master_tree.insert_leaf(*i, leaf);
+            }
+        }
+        Ok(*count)
+    }
+
+    /// Drops the local trust, the seed trust and the local trust sub-trees of the domain,
+    /// keeping its indices and tree roots.
+    ///
+    /// The domain can't be updated or computed afterwards.
+    pub fn release_inputs(&mut self, domain: &Domain) {
+        self.local_trust.remove(&domain.trust_namespace());
+        self.seed_trust.remove(&domain.seed_namespace());
+        self.zero_trust.remove(&domain.trust_namespace());
+        self.zero_seed.remove(&domain.seed_namespace());
+        self.lt_sub_trees.remove(&domain.to_hash());
+    }
+
     pub fn get_base_root_hashes(&self, domain: &Domain) -> Result<Hash, Error> {
         let lt_tree = self
             .lt_master_tree
@@ -419,6 +792,18 @@ impl BaseRunner {
     }
 }
 
+/// Records the number of peers and edges of the trust graph of the domain.
+fn record_graph_size(domain: &Domain, peers: u64, lt: &BTreeMap<u64, OutboundLocalTrust>) {
+    let edges: usize = lt.values().map(|x| x.outbound_trust_scores.len()).sum();
+    let domain = domain.to_hash().to_hex();
+    metrics::GRAPH_PEERS
+        .with_label_values(&[&domain])
+        .set(peers as i64);
+    metrics::GRAPH_EDGES
+        .with_label_values(&[&domain])
+        .set(edges as i64);
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("'indices' not found for domain: {0}")]
@@ -441,8 +826,214 @@ pub enum Error {
     SeedTrustNotFound(OwnedNamespace),
     #[error("'domain_index' not found for address: {0}")]
     DomainIndexNotFound(String),
+    #[error("'peer_id' not found in the interner: {0}")]
+    PeerIdNotFound(PeerId),
     #[error("Merkle Error: {0}")]
     Merkle(merkle::Error),
+    #[error("Interner Error: {0}")]
+    Interner(InternerError),
     #[error("Misc Error: {0}")]
     Misc(String),
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        runners::BaseRunner,
+        tx::canonical::{scores_id, trust_id},
+        tx::trust::{ScoreEntry, TrustEntry},
+        Domain,
+    };
+    use alloy_primitives::Address;
+    use std::collections::HashMap;
+
+    fn trust_entry(from: &str, to: &str, value: f32) -> TrustEntry {
+        TrustEntry::new(from.to_string(), to.to_string(), value)
+    }
+
+    #[test]
+    fn should_remove_peers_and_compact_indices() {
+        let domain = Domain::default();
+        let mut runner = BaseRunner::new(&[domain.clone()]);
+        runner
+            .update_trust(
+                domain.clone(),
+                vec![
+                    trust_entry("a", "b", 1.0),
+                    trust_entry("a", "c", 1.0),
+                    trust_entry("c", "d", 1.0),
+                    trust_entry("d", "a", 1.0),
+                ],
+            )
+            .unwrap();
+        runner
+            .update_seed(
+                domain.clone(),
+                vec![
+                    ScoreEntry::new("a".to_string(), 1.0),
+                    ScoreEntry::new("b".to_string(), 1.0),
+                ],
+            )
+            .unwrap();
+        runner
+            .remove_peers(domain.clone(), vec!["b".to_string()])
+            .unwrap();
+
+        let mut expected = BaseRunner::new(&[domain.clone()]);
+        expected
+            .update_trust(
+                domain.clone(),
+                vec![
+                    trust_entry("a", "c", 1.0),
+                    trust_entry("c", "d", 1.0),
+                    trust_entry("d", "a", 1.0),
+                ],
+            )
+            .unwrap();
+        expected
+            .update_seed(domain.clone(), vec![ScoreEntry::new("a".to_string(), 1.0)])
+            .unwrap();
+
+        let domain_hash = domain.to_hash();
+        assert_eq!(runner.count()[&domain_hash], 3);
+        let resolve = |runner: &BaseRunner| -> HashMap<String, u64> {
+            runner.indices()[&domain_hash]
+                .iter()
+                .map(|(peer_id, i)| (runner.interner().resolve(*peer_id).unwrap().to_string(), *i))
+                .collect()
+        };
+        assert_eq!(resolve(&runner), resolve(&expected));
+        assert_eq!(
+            runner.get_base_root_hashes(&domain).unwrap(),
+            expected.get_base_root_hashes(&domain).unwrap()
+        );
+        let lt = &runner.local_trust()[&domain.trust_namespace()];
+        assert!(lt
+            .values()
+            .all(|x| x.outbound_trust_scores().keys().all(|i| *i < 3)));
+    }
+
+    #[test]
+    fn should_sort_indices_independently_of_load_order() {
+        let domain = Domain::default();
+        let entries = vec![
+            trust_entry("d", "a", 1.0),
+            trust_entry("a", "c", 0.5),
+            trust_entry("c", "b", 1.0),
+            trust_entry("a", "b", 0.0),
+        ];
+        let load = |entries: Vec<TrustEntry>| {
+            let mut runner = BaseRunner::new(&[domain.clone()]);
+            runner.update_trust(domain.clone(), entries).unwrap();
+            runner
+                .update_seed_map(domain.clone(), vec![ScoreEntry::new("e".to_string(), 1.0)])
+                .unwrap();
+            runner.sort_indices(domain.clone()).unwrap();
+            runner
+        };
+        let runner = load(entries.clone());
+        let reversed = load(entries.into_iter().rev().collect());
+
+        let domain_hash = domain.to_hash();
+        let names: Vec<&str> = (0..5)
+            .map(|i| {
+                let peer_id = runner.rev_indices()[&domain_hash][&i];
+                runner.interner().resolve(peer_id).unwrap()
+            })
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c", "d", "e"]);
+
+        let lt = &runner.local_trust()[&domain.trust_namespace()];
+        let reversed_lt = &reversed.local_trust()[&domain.trust_namespace()];
+        assert_eq!(lt.keys().collect::<Vec<_>>(), vec![&0, &2, &3]);
+        for (from, from_map) in lt {
+            assert_eq!(
+                from_map.outbound_trust_scores(),
+                reversed_lt[from].outbound_trust_scores()
+            );
+        }
+        assert_eq!(runner.seed_trust()[&domain.seed_namespace()][&4], 1.0);
+        assert_eq!(
+            runner.get_base_root_hashes(&domain).unwrap(),
+            reversed.get_base_root_hashes(&domain).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_compute_content_ids_of_loaded_data() {
+        let domain = Domain::default();
+        let trust = vec![
+            trust_entry("b", "a", 0.5),
+            trust_entry("a", "c", 1.0),
+            trust_entry("a", "c", 0.0),
+            trust_entry("ab", "a", -0.0),
+            trust_entry("c", "a", 0.0),
+            trust_entry("c", "a", 0.25),
+            trust_entry("b", "a", 0.75),
+        ];
+        let seed = vec![
+            ScoreEntry::new("c".to_string(), 1.0),
+            ScoreEntry::new("a".to_string(), 0.0),
+            ScoreEntry::new("c".to_string(), 0.5),
+        ];
+        let mut runner = BaseRunner::new(&[domain.clone()]);
+        runner
+            .update_trust_map(domain.clone(), trust.clone())
+            .unwrap();
+        runner
+            .update_seed_map(domain.clone(), seed.clone())
+            .unwrap();
+        assert_eq!(runner.trust_id(&domain).unwrap(), trust_id(&trust));
+        assert_eq!(runner.seed_id(&domain).unwrap(), scores_id(&seed));
+
+        runner.sort_indices(domain.clone()).unwrap();
+        assert_eq!(runner.trust_id(&domain).unwrap(), trust_id(&trust));
+        assert_eq!(runner.seed_id(&domain).unwrap(), scores_id(&seed));
+    }
+
+    #[test]
+    fn should_drop_peers_left_out_of_the_renumbering() {
+        let domain = Domain::default();
+        let mut runner = BaseRunner::new(&[domain.clone()]);
+        runner
+            .update_trust_map(
+                domain.clone(),
+                vec![trust_entry("a", "b", 1.0), trust_entry("b", "c", 1.0)],
+            )
+            .unwrap();
+        assert_eq!(runner.renumber_indices(&domain, vec![1, 0]).unwrap(), 2);
+
+        let domain_hash = domain.to_hash();
+        let c = runner.interner().get("c").unwrap();
+        assert_eq!(runner.indices()[&domain_hash].len(), 2);
+        assert!(!runner.indices()[&domain_hash].contains_key(&c));
+        let lt = &runner.local_trust()[&domain.trust_namespace()];
+        assert_eq!(
+            lt[&1].outbound_trust_scores().keys().collect::<Vec<_>>(),
+            vec![&0]
+        );
+        assert!(lt[&0].outbound_trust_scores().is_empty());
+    }
+
+    #[test]
+    fn should_share_peer_ids_between_domains() {
+        let domain_a = Domain::new(Address::default(), 0, Address::default(), 0, 0);
+        let domain_b = Domain::new(Address::default(), 1, Address::default(), 1, 0);
+        let mut runner = BaseRunner::new(&[domain_a.clone(), domain_b.clone()]);
+        runner
+            .update_trust_map(domain_a, vec![trust_entry("a", "b", 1.0)])
+            .unwrap();
+        runner
+            .update_trust_map(domain_b, vec![trust_entry("b", "c", 1.0)])
+            .unwrap();
+        assert_eq!(runner.interner().len(), 3);
+    }
+
+    #[test]
+    fn should_fail_removing_unknown_peer() {
+        let domain = Domain::default();
+        let mut runner = BaseRunner::new(&[domain.clone()]);
+        let res = runner.remove_peers(domain, vec!["a".to_string()]);
+        assert!(res.is_err());
+    }
+}
@@ -1,17 +1,47 @@
 use crate::{
-    algos::et::convergence_check,
+    algos::et::{convergence_check, scored_peers},
     merkle::{self, fixed::DenseMerkleTree, hash_leaf, Hash},
+    metrics,
     tx::trust::{ScoreEntry, TrustEntry},
     Domain, DomainHash,
 };
 use getset::Getters;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use sha3::Keccak256;
-use std::collections::{BTreeMap, HashMap};
-use tracing::info;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
+use tracing::{info, warn};
 
 use super::{BaseRunner, Error as BaseError};
 
+/// The default tolerance for the sum of submitted scores, which should add up to 1.
+const DEFAULT_SUM_TOLERANCE: f64 = 0.001;
+
+#[derive(Clone, Debug, Getters)]
+#[getset(get = "pub")]
+/// Tolerances used while validating the submitted score vectors.
+pub struct ScoreValidationConfig {
+    /// Maximum absolute difference between the sum of the scores and 1.
+    sum_tolerance: f64,
+    /// Whether peers that should have a score are allowed to be missing from the submission.
+    allow_missing_peers: bool,
+}
+
+impl ScoreValidationConfig {
+    pub fn new(sum_tolerance: f64, allow_missing_peers: bool) -> Self {
+        Self {
+            sum_tolerance,
+            allow_missing_peers,
+        }
+    }
+}
+
+impl Default for ScoreValidationConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUM_TOLERANCE, false)
+    }
+}
+
 #[derive(Getters)]
 #[getset(get = "pub")]
 /// Struct containing the state of the verification runner
@@ -20,6 +50,7 @@ pub struct VerificationRunner {
     compute_scores: HashMap<DomainHash, HashMap<Hash, Vec<ScoreEntry>>>,
     compute_tree: HashMap<DomainHash, HashMap<Hash, DenseMerkleTree<Keccak256>>>,
     commitments: HashMap<Hash, Hash>,
+    validation_config: ScoreValidationConfig,
 }
 
 impl VerificationRunner {
@@ -37,14 +68,20 @@ impl VerificationRunner {
             compute_scores,
             compute_tree,
             commitments: HashMap::new(),
+            validation_config: ScoreValidationConfig::default(),
         }
     }
 
+    /// Set the tolerances used while validating submitted scores
+    pub fn set_validation_config(&mut self, validation_config: ScoreValidationConfig) {
+        self.validation_config = validation_config;
+    }
+
     /// Update the state of trees for certain domain, with the given trust entries
     pub fn update_trust(
         &mut self,
         domain: Domain,
-        trust_entries: Vec<TrustEntry>,
+        trust_entries: impl IntoIterator<Item = TrustEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_trust(domain, trust_entries)
@@ -54,18 +91,45 @@ impl VerificationRunner {
     pub fn update_trust_map(
         &mut self,
         domain: Domain,
-        trust_entries: Vec<TrustEntry>,
+        trust_entries: impl IntoIterator<Item = TrustEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_trust_map(domain, trust_entries)
             .map_err(Error::Base)
     }
 
+    /// Remove the given peers from certain domain, and compact the remaining indices
+    pub fn remove_peers(&mut self, domain: Domain, peer_ids: Vec<String>) -> Result<(), Error> {
+        self.base
+            .remove_peers(domain, peer_ids)
+            .map_err(Error::Base)
+    }
+
+    /// Compact the peer indices of certain domain
+    pub fn compact_indices(&mut self, domain: Domain) -> Result<(), Error> {
+        self.base.compact_indices(domain).map_err(Error::Base)
+    }
+
+    /// Sort the peer indices of certain domain by peer id, so that they don't depend on the load order
+    pub fn sort_indices(&mut self, domain: Domain) -> Result<(), Error> {
+        self.base.sort_indices(domain).map_err(Error::Base)
+    }
+
+    /// Content id of the trust loaded for certain domain. See [BaseRunner::trust_id]
+    pub fn trust_id(&self, domain: &Domain) -> Result<Hash, Error> {
+        self.base.trust_id(domain).map_err(Error::Base)
+    }
+
+    /// Content id of the seed loaded for certain domain. See [BaseRunner::seed_id]
+    pub fn seed_id(&self, domain: &Domain) -> Result<Hash, Error> {
+        self.base.seed_id(domain).map_err(Error::Base)
+    }
+
     /// Update the state of trees for certain domain, with the given seed entries
     pub fn update_seed(
         &mut self,
         domain: Domain,
-        seed_entries: Vec<ScoreEntry>,
+        seed_entries: impl IntoIterator<Item = ScoreEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_seed(domain, seed_entries)
@@ -75,7 +139,7 @@ impl VerificationRunner {
     pub fn update_seed_map(
         &mut self,
         domain: Domain,
-        seed_entries: Vec<ScoreEntry>,
+        seed_entries: impl IntoIterator<Item = ScoreEntry>,
     ) -> Result<(), Error> {
         self.base
             .update_seed_map(domain, seed_entries)
@@ -105,7 +169,11 @@ impl VerificationRunner {
     /// Get the list of completed assignments for certain domain
     pub fn verify_job(&mut self, domain: Domain, compute_id: Hash) -> Result<bool, Error> {
         info!("COMPLETED_ASSIGNMENT_SEARCH: {}", domain.to_hash());
-        let commitment = self.commitments.get(&compute_id.clone()).unwrap();
+        let start = Instant::now();
+        let commitment = self
+            .commitments
+            .get(&compute_id)
+            .ok_or(Error::CommitmentNotFound)?;
         let cp_root = commitment.clone();
 
         self.create_compute_tree(domain.clone(), compute_id.clone())?;
@@ -124,12 +192,15 @@ impl VerificationRunner {
             is_converged,
         );
 
-        return Ok(is_root_equal && is_converged);
+        let is_valid = is_root_equal && is_converged;
+        record_verification(start, is_valid);
+        Ok(is_valid)
     }
 
     /// Get the list of completed assignments for certain domain
     pub fn verify_scores(&mut self, domain: Domain, compute_id: Hash) -> Result<bool, Error> {
         info!("COMPLETED_ASSIGNMENT_SEARCH: {}", domain.to_hash());
+        let start = Instant::now();
 
         self.create_compute_tree(domain.clone(), compute_id.clone())?;
         let (res_lt_root, res_compute_root) =
@@ -145,7 +216,8 @@ impl VerificationRunner {
             is_converged,
         );
 
-        return Ok(is_converged);
+        record_verification(start, is_converged);
+        Ok(is_converged)
     }
 
     /// Build the compute tree of certain assignment, for certain domain.
@@ -159,7 +231,9 @@ impl VerificationRunner {
             .compute_scores
             .get(&domain.to_hash())
             .ok_or(Error::ComputeScoresNotFoundWithDomain(domain.to_hash()))?;
-        let scores = compute_scores.get(&compute_id).unwrap();
+        let scores = compute_scores
+            .get(&compute_id)
+            .ok_or(Error::ComputeScoresNotFound(compute_id.clone()))?;
         let score_entries: Vec<f32> = scores.iter().map(|x| *x.value()).collect();
         let score_hashes: Vec<Hash> = score_entries
             .par_iter()
@@ -176,8 +250,17 @@ impl VerificationRunner {
         Ok(())
     }
 
-    /// Get the verification result(True or False) of certain assignment, for certain domain
-    fn compute_verification(&mut self, domain: Domain, compute_id: Hash) -> Result<bool, Error> {
+    /// Validate the submitted scores of certain assignment, for certain domain.
+    ///
+    /// Rejects duplicate, unknown, non-finite and negative entries, scores that do not sum
+    /// up to 1, and missing scores of peers reachable from the seed, with
+    /// [Error::InvalidScores].
+    /// Returns the scores keyed by the domain index of each peer.
+    fn validate_scores(
+        &self,
+        domain: &Domain,
+        compute_id: &Hash,
+    ) -> Result<BTreeMap<u64, f32>, Error> {
         let compute_scores = self
             .compute_scores
             .get(&domain.to_hash())
@@ -187,6 +270,85 @@ impl VerificationRunner {
             .indices
             .get(&domain.to_hash())
             .ok_or::<Error>(BaseError::IndicesNotFound(domain.to_hash()).into())?;
+        let scores = compute_scores
+            .get(compute_id)
+            .ok_or(Error::ComputeScoresNotFound(compute_id.clone()))?;
+
+        let mut score_entries = BTreeMap::new();
+        let mut sum = 0.0f64;
+        for entry in scores {
+            let value = *entry.value();
+            if !value.is_finite() {
+                return Err(InvalidScores::NonFinite(entry.id().clone()).into());
+            }
+            if value < 0.0 {
+                return Err(InvalidScores::Negative(entry.id().clone(), value).into());
+            }
+            let i = self
+                .base
+                .interner
+                .get(entry.id())
+                .and_then(|peer_id| domain_indices.get(&peer_id))
+                .ok_or(InvalidScores::UnknownPeer(entry.id().clone()))?;
+            if score_entries.insert(*i, value).is_some() {
+                return Err(InvalidScores::Duplicate(entry.id().clone()).into());
+            }
+            sum += value as f64;
+        }
+
+        if (sum - 1.0).abs() > *self.validation_config.sum_tolerance() {
+            return Err(InvalidScores::SumOutOfTolerance(sum).into());
+        }
+
+        if !self.validation_config.allow_missing_peers() {
+            let rev_domain_indices = self
+                .base
+                .rev_indices
+                .get(&domain.to_hash())
+                .ok_or::<Error>(BaseError::ReverseIndicesNotFound(domain.to_hash()).into())?;
+            let lt = self
+                .base
+                .local_trust
+                .get(&domain.trust_namespace())
+                .ok_or::<Error>(BaseError::LocalTrustNotFound(domain.trust_namespace()).into())?;
+            let seed = self
+                .base
+                .seed_trust
+                .get(&domain.seed_namespace())
+                .ok_or::<Error>(BaseError::SeedTrustNotFound(domain.seed_namespace()).into())?;
+            let count = self
+                .base
+                .count
+                .get(&domain.to_hash())
+                .ok_or::<Error>(BaseError::CountNotFound(domain.to_hash()).into())?;
+            let submitted: HashSet<&u64> = score_entries.keys().collect();
+            let missing = scored_peers(lt, seed, *count)
+                .into_iter()
+                .find(|i| !submitted.contains(i));
+            if let Some(i) = missing {
+                let peer_id = rev_domain_indices
+                    .get(&i)
+                    .and_then(|peer_id| self.base.interner.resolve(*peer_id))
+                    .ok_or::<Error>(BaseError::DomainIndexNotFound(i.to_string()).into())?;
+                return Err(InvalidScores::Missing(peer_id.to_string()).into());
+            }
+        }
+
+        Ok(score_entries)
+    }
+
+    /// Get the verification result(True or False) of certain assignment, for certain domain.
+    ///
+    /// Invalid scores are a `false` result, so that they can be challenged.
+    fn compute_verification(&mut self, domain: Domain, compute_id: Hash) -> Result<bool, Error> {
+        let score_entries = match self.validate_scores(&domain, &compute_id) {
+            Ok(score_entries) => score_entries,
+            Err(Error::InvalidScores(e)) => {
+                warn!("Invalid scores for compute {}: {}", compute_id, e);
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        };
         let lt = self
             .base
             .local_trust
@@ -202,23 +364,7 @@ impl VerificationRunner {
             .seed_trust
             .get(&domain.seed_namespace())
             .ok_or::<Error>(BaseError::SeedTrustNotFound(domain.seed_namespace()).into())?;
-        let scores = compute_scores.get(&compute_id).unwrap();
-        let score_entries: BTreeMap<u64, f32> = {
-            let mut score_entries_map: BTreeMap<u64, f32> = BTreeMap::new();
-            for entry in scores {
-                let i = domain_indices
-                    .get(entry.id())
-                    .ok_or(Error::DomainIndexNotFound(entry.id().clone()))?;
-                score_entries_map.insert(*i, *entry.value());
-            }
-            score_entries_map
-        };
-        Ok(convergence_check(
-            lt.clone(),
-            seed.clone(),
-            &score_entries,
-            *count,
-        ))
+        Ok(convergence_check(lt, seed, &score_entries, *count))
     }
 
     /// Get the local trust tree root and compute tree root of certain assignment, for certain domain
@@ -233,7 +379,9 @@ impl VerificationRunner {
             .compute_tree
             .get(&domain.to_hash())
             .ok_or(Error::ComputeTreeNotFoundWithDomain(domain.to_hash()))?;
-        let compute_tree = compute_tree_map.get(&assignment_id).unwrap();
+        let compute_tree = compute_tree_map
+            .get(&assignment_id)
+            .ok_or(Error::ComputeTreeNotFound(assignment_id.clone()))?;
         let ct_tree_root = compute_tree.root().map_err(Error::Merkle)?;
 
         Ok((tree_roots, ct_tree_root))
@@ -248,10 +396,16 @@ pub enum Error {
     ComputeTreeNotFoundWithDomain(DomainHash),
     #[error("compute_scores not found for domain: {0}")]
     ComputeScoresNotFoundWithDomain(DomainHash),
+    #[error("commitment not found for compute")]
+    CommitmentNotFound,
+    #[error("compute_scores not found for compute: {0}")]
+    ComputeScoresNotFound(Hash),
+    #[error("compute_tree not found for compute: {0}")]
+    ComputeTreeNotFound(Hash),
     #[error("active_assignments not found for domain: {0}")]
     ActiveAssignmentsNotFound(DomainHash),
-    #[error("domain_indice not found for address: {0}")]
-    DomainIndexNotFound(String),
+    #[error("invalid scores: {0}")]
+    InvalidScores(InvalidScores),
     #[error("{0}")]
     Merkle(merkle::Error),
 }
@@ -261,3 +415,199 @@ impl From<BaseError> for Error {
         Self::Base(err)
     }
 }
+
+/// Reasons for rejecting a submitted score vector.
+#[derive(thiserror::Error, Debug)]
+pub enum InvalidScores {
+    #[error("unknown address: {0}")]
+    UnknownPeer(String),
+    #[error("duplicate score entry for address: {0}")]
+    Duplicate(String),
+    #[error("non-finite score for address: {0}")]
+    NonFinite(String),
+    #[error("negative score for address: {0}, value: {1}")]
+    Negative(String, f32),
+    #[error("scores sum out of tolerance: {0}")]
+    SumOutOfTolerance(f64),
+    #[error("missing score entry for address: {0}")]
+    Missing(String),
+}
+
+impl From<InvalidScores> for Error {
+    fn from(err: InvalidScores) -> Self {
+        Self::InvalidScores(err)
+    }
+}
+
+/// Records the duration and the result of a verification.
+fn record_verification(start: Instant, is_valid: bool) {
+    metrics::observe_since(&metrics::STAGE_DURATION, &["verify"], start);
+    let result = if is_valid { "valid" } else { "invalid" };
+    metrics::VERIFICATIONS.with_label_values(&[result]).inc();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        merkle::Hash,
+        runners::{
+            compute_runner::ComputeRunner,
+            verification_runner::{Error, InvalidScores, VerificationRunner},
+        },
+        tx::trust::{ScoreEntry, TrustEntry},
+        Domain,
+    };
+
+    fn trust_entries() -> Vec<TrustEntry> {
+        vec![
+            TrustEntry::new("a".to_string(), "b".to_string(), 1.0),
+            TrustEntry::new("b".to_string(), "c".to_string(), 1.0),
+            TrustEntry::new("c".to_string(), "a".to_string(), 1.0),
+            TrustEntry::new("a".to_string(), "c".to_string(), 0.5),
+        ]
+    }
+
+    fn seed_entries() -> Vec<ScoreEntry> {
+        vec![ScoreEntry::new("a".to_string(), 1.0)]
+    }
+
+    fn computed_scores() -> Vec<ScoreEntry> {
+        let domain = Domain::default();
+        let mut runner = ComputeRunner::new(&[domain.clone()]);
+        runner
+            .update_trust_map(domain.clone(), trust_entries())
+            .unwrap();
+        runner
+            .update_seed_map(domain.clone(), seed_entries())
+            .unwrap();
+        runner.compute(domain.clone()).unwrap();
+        runner.get_compute_scores(domain).unwrap()
+    }
+
+    fn runner_with(scores: Vec<ScoreEntry>) -> VerificationRunner {
+        let domain = Domain::default();
+        let mut runner = VerificationRunner::new(&[domain.clone()]);
+        runner
+            .update_trust_map(domain.clone(), trust_entries())
+            .unwrap();
+        runner
+            .update_seed_map(domain.clone(), seed_entries())
+            .unwrap();
+        runner
+            .update_scores(domain, Hash::default(), scores)
+            .unwrap();
+        runner
+    }
+
+    fn verify(scores: Vec<ScoreEntry>) -> Result<bool, Error> {
+        runner_with(scores).verify_scores(Domain::default(), Hash::default())
+    }
+
+    /// Asserts that the scores are rejected, and returns the reason.
+    fn rejection(scores: Vec<ScoreEntry>) -> InvalidScores {
+        let runner = runner_with(scores.clone());
+        assert!(!verify(scores).unwrap());
+        match runner.validate_scores(&Domain::default(), &Hash::default()) {
+            Err(Error::InvalidScores(e)) => e,
+            res => panic!("unexpected validation result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn should_verify_computed_scores() {
+        assert!(verify(computed_scores()).unwrap());
+    }
+
+    #[test]
+    fn should_reject_duplicate_scores() {
+        let mut scores = computed_scores();
+        scores.push(scores[0].clone());
+        assert!(matches!(rejection(scores), InvalidScores::Duplicate(_)));
+    }
+
+    #[test]
+    fn should_reject_non_finite_scores() {
+        let mut scores = computed_scores();
+        scores[0] = ScoreEntry::new(scores[0].id().clone(), f32::NAN);
+        assert!(matches!(rejection(scores), InvalidScores::NonFinite(_)));
+    }
+
+    #[test]
+    fn should_reject_negative_scores() {
+        let mut scores = computed_scores();
+        scores[0] = ScoreEntry::new(scores[0].id().clone(), -0.1);
+        assert!(matches!(rejection(scores), InvalidScores::Negative(_, _)));
+    }
+
+    #[test]
+    fn should_reject_scores_not_summing_to_one() {
+        let scores = computed_scores()
+            .into_iter()
+            .map(|x| ScoreEntry::new(x.id().clone(), x.value() * 2.0))
+            .collect();
+        assert!(matches!(
+            rejection(scores),
+            InvalidScores::SumOutOfTolerance(_)
+        ));
+    }
+
+    #[test]
+    fn should_reject_missing_scores() {
+        let mut scores = computed_scores();
+        let removed = scores.pop().unwrap();
+        let first = scores[0].clone();
+        scores[0] = ScoreEntry::new(first.id().clone(), first.value() + removed.value());
+        let res = rejection(scores);
+        assert!(matches!(res, InvalidScores::Missing(id) if &id == removed.id()));
+    }
+
+    #[test]
+    fn should_reject_unknown_peers() {
+        let mut scores = computed_scores();
+        scores[0] = ScoreEntry::new("z".to_string(), *scores[0].value());
+        assert!(matches!(rejection(scores), InvalidScores::UnknownPeer(id) if id == "z"));
+    }
+
+    #[test]
+    fn should_fail_verify_job_with_invalid_scores() {
+        for value in [f32::NAN, -0.1] {
+            let mut scores = computed_scores();
+            scores[0] = ScoreEntry::new(scores[0].id().clone(), value);
+            let domain = Domain::default();
+            let mut runner = runner_with(scores);
+            // The commitment matches the submitted scores, so only their validation fails.
+            runner
+                .create_compute_tree(domain.clone(), Hash::default())
+                .unwrap();
+            let (_, root) = runner
+                .get_root_hashes(domain.clone(), Hash::default())
+                .unwrap();
+            runner.update_commitment(Hash::default(), root);
+            assert!(!runner.verify_job(domain, Hash::default()).unwrap());
+        }
+    }
+
+    #[test]
+    fn should_fail_verify_job_without_commitment() {
+        let domain = Domain::default();
+        let mut runner = VerificationRunner::new(&[domain.clone()]);
+        let res = runner.verify_job(domain, Hash::default());
+        assert!(matches!(res, Err(Error::CommitmentNotFound)));
+    }
+
+    #[test]
+    fn should_fail_verify_scores_without_scores() {
+        let domain = Domain::default();
+        let mut runner = VerificationRunner::new(&[domain.clone()]);
+        let res = runner.verify_scores(domain, Hash::default());
+        assert!(matches!(res, Err(Error::ComputeScoresNotFound(_))));
+    }
+
+    #[test]
+    fn should_fail_get_root_hashes_without_compute_tree() {
+        let domain = Domain::default();
+        let runner = VerificationRunner::new(&[domain.clone()]);
+        let res = runner.get_root_hashes(domain, Hash::default());
+        assert!(matches!(res, Err(Error::ComputeTreeNotFound(_))));
+    }
+}
@@ -0,0 +1,488 @@
+//! Canonical binary encoding of trust, seed and score sets.
+//!
+//! Entries are sorted by their ids, the last of duplicate entries wins, and values are written
+//! as fixed-width big-endian `f32`s. Identical data therefore always has the same encoding,
+//! whatever the source format, and content ids are defined as the keccak hash of it.
+//!
+//! Ids identify the data rather than the graph built from it: entries with a zero value are
+//! kept, while the runners drop them, so `{a→b: 1, a→b: 0}` and `{}` have different ids.
+
+use crate::merkle::{hash_leaf, Hash};
+use crate::runners::interner::{Error as InternerError, PeerId, PeerInterner};
+use crate::tx::trust::{ScoreEntry, TrustEntry};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+
+/// Tag at the start of an encoded trust set.
+pub const TRUST_SET_TAG: [u8; 4] = *b"ORTS";
+/// Tag at the start of an encoded seed or score set.
+pub const SCORE_SET_TAG: [u8; 4] = *b"ORSS";
+/// Version of the encoding.
+pub const VERSION: u8 = 1;
+
+const CANONICAL_NAN: u32 = 0x7fc0_0000;
+
+/// Bits of the value with `-0.0` and all NaNs collapsed into one representation.
+fn canonical_bits(value: f32) -> u32 {
+    if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        CANONICAL_NAN
+    } else {
+        value.to_bits()
+    }
+}
+
+/// Sorts the trust entries by `(from, to)`, keeping the last value of duplicates.
+pub fn canonical_trust(entries: Vec<TrustEntry>) -> Vec<TrustEntry> {
+    let mut map = BTreeMap::new();
+    for entry in entries {
+        let value = f32::from_bits(canonical_bits(*entry.value()));
+        map.insert((entry.from().clone(), entry.to().clone()), value);
+    }
+    map.into_iter()
+        .map(|((from, to), value)| TrustEntry::new(from, to, value))
+        .collect()
+}
+
+/// Sorts the seed or score entries by id, keeping the last value of duplicates.
+pub fn canonical_scores(entries: Vec<ScoreEntry>) -> Vec<ScoreEntry> {
+    let mut map = BTreeMap::new();
+    for entry in entries {
+        let value = f32::from_bits(canonical_bits(*entry.value()));
+        map.insert(entry.id().clone(), value);
+    }
+    map.into_iter()
+        .map(|(id, value)| ScoreEntry::new(id, value))
+        .collect()
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Encodes the trust set. The entries don't need to be sorted.
+pub fn encode_trust(entries: &[TrustEntry]) -> Vec<u8> {
+    let mut map = BTreeMap::new();
+    for entry in entries {
+        map.insert(
+            (entry.from().as_str(), entry.to().as_str()),
+            canonical_bits(*entry.value()),
+        );
+    }
+    let mut out = Vec::new();
+    out.extend_from_slice(&TRUST_SET_TAG);
+    out.push(VERSION);
+    out.extend_from_slice(&(map.len() as u64).to_be_bytes());
+    for ((from, to), bits) in map {
+        put_str(&mut out, from);
+        put_str(&mut out, to);
+        out.extend_from_slice(&bits.to_be_bytes());
+    }
+    out
+}
+
+/// Encodes the seed or score set. The entries don't need to be sorted.
+pub fn encode_scores(entries: &[ScoreEntry]) -> Vec<u8> {
+    let mut map = BTreeMap::new();
+    for entry in entries {
+        map.insert(entry.id().as_str(), canonical_bits(*entry.value()));
+    }
+    let mut out = Vec::new();
+    out.extend_from_slice(&SCORE_SET_TAG);
+    out.push(VERSION);
+    out.extend_from_slice(&(map.len() as u64).to_be_bytes());
+    for (id, bits) in map {
+        put_str(&mut out, id);
+        out.extend_from_slice(&bits.to_be_bytes());
+    }
+    out
+}
+
+/// Content id of the trust set.
+pub fn trust_id(entries: &[TrustEntry]) -> Hash {
+    hash_leaf::<Keccak256>(encode_trust(entries))
+}
+
+/// Content id of the seed or score set.
+pub fn scores_id(entries: &[ScoreEntry]) -> Hash {
+    hash_leaf::<Keccak256>(encode_scores(entries))
+}
+
+/// Incremental computation of a content id, from entries in any order.
+pub trait IdHasher {
+    type Entry;
+
+    /// Adds the entry. Later entries replace earlier ones with the same ids.
+    fn update(&mut self, entry: &Self::Entry) -> Result<(), InternerError>;
+
+    /// Returns the content id of the set.
+    fn finalize(self) -> Hash;
+}
+
+/// Rank of each interned peer id in the sorted order of the identifiers.
+fn sorted_ranks(interner: &PeerInterner) -> Vec<u32> {
+    let mut names: Vec<(PeerId, &str)> = interner.iter().collect();
+    names.sort_unstable_by_key(|(_, name)| *name);
+    let mut ranks = vec![0; names.len()];
+    for (rank, (id, _)) in names.into_iter().enumerate() {
+        ranks[id.inner() as usize] = rank as u32;
+    }
+    ranks
+}
+
+/// Hashes the encoding of a set whose entries are written in canonical order, without
+/// buffering them.
+pub(crate) struct SortedSetHasher(Keccak256);
+
+impl SortedSetHasher {
+    /// Starts a set of `len` entries, with the tag of its kind.
+    pub(crate) fn new(tag: [u8; 4], len: u64) -> Self {
+        let mut hasher = Keccak256::new();
+        hasher.update(tag);
+        hasher.update([VERSION]);
+        hasher.update(len.to_be_bytes());
+        Self(hasher)
+    }
+
+    fn put_str(&mut self, s: &str) {
+        self.0.update((s.len() as u32).to_be_bytes());
+        self.0.update(s.as_bytes());
+    }
+
+    /// Adds the next entry of a trust set.
+    pub(crate) fn trust(&mut self, from: &str, to: &str, value: f32) {
+        self.put_str(from);
+        self.put_str(to);
+        self.0.update(canonical_bits(value).to_be_bytes());
+    }
+
+    /// Adds the next entry of a seed or score set.
+    pub(crate) fn score(&mut self, id: &str, value: f32) {
+        self.put_str(id);
+        self.0.update(canonical_bits(value).to_be_bytes());
+    }
+
+    pub(crate) fn finalize(self) -> Hash {
+        Hash::from_slice(&self.0.finalize())
+    }
+}
+
+#[derive(Default)]
+/// Computes the [`trust_id`] of a stream of entries that is not loaded anywhere else, e.g.
+/// before an upload. Runners compute the id of their loaded data themselves, see
+/// [`BaseRunner::trust_id`](crate::runners::BaseRunner::trust_id).
+///
+/// Identifiers are interned and only their handles are kept together with the value bits,
+/// so memory stays well below the size of the entries themselves.
+pub struct TrustIdHasher {
+    interner: PeerInterner,
+    entries: Vec<(PeerId, PeerId, u32)>,
+}
+
+impl TrustIdHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdHasher for TrustIdHasher {
+    type Entry = TrustEntry;
+
+    fn update(&mut self, entry: &TrustEntry) -> Result<(), InternerError> {
+        let from = self.interner.intern(entry.from())?;
+        let to = self.interner.intern(entry.to())?;
+        self.entries
+            .push((from, to, canonical_bits(*entry.value())));
+        Ok(())
+    }
+
+    fn finalize(self) -> Hash {
+        let ranks = sorted_ranks(&self.interner);
+        let rank = |id: PeerId| ranks[id.inner() as usize];
+        let mut entries = self.entries;
+        // The sort is stable, so duplicates stay in their original order.
+        entries.sort_by_key(|(from, to, _)| (rank(*from), rank(*to)));
+        entries.dedup_by(|next, prev| {
+            let duplicate = (next.0, next.1) == (prev.0, prev.1);
+            if duplicate {
+                prev.2 = next.2;
+            }
+            duplicate
+        });
+
+        let mut hasher = SortedSetHasher::new(TRUST_SET_TAG, entries.len() as u64);
+        for (from, to, bits) in entries {
+            let from = self.interner.resolve(from).unwrap();
+            let to = self.interner.resolve(to).unwrap();
+            hasher.trust(from, to, f32::from_bits(bits));
+        }
+        hasher.finalize()
+    }
+}
+
+#[derive(Default)]
+/// Computes the [`scores_id`] of a stream of entries. See [`TrustIdHasher`], and
+/// [`BaseRunner::seed_id`](crate::runners::BaseRunner::seed_id) for loaded seed data.
+pub struct ScoresIdHasher {
+    interner: PeerInterner,
+    entries: Vec<(PeerId, u32)>,
+}
+
+impl ScoresIdHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdHasher for ScoresIdHasher {
+    type Entry = ScoreEntry;
+
+    fn update(&mut self, entry: &ScoreEntry) -> Result<(), InternerError> {
+        let id = self.interner.intern(entry.id())?;
+        self.entries.push((id, canonical_bits(*entry.value())));
+        Ok(())
+    }
+
+    fn finalize(self) -> Hash {
+        let ranks = sorted_ranks(&self.interner);
+        let mut entries = self.entries;
+        entries.sort_by_key(|(id, _)| ranks[id.inner() as usize]);
+        entries.dedup_by(|next, prev| {
+            let duplicate = next.0 == prev.0;
+            if duplicate {
+                prev.1 = next.1;
+            }
+            duplicate
+        });
+
+        let mut hasher = SortedSetHasher::new(SCORE_SET_TAG, entries.len() as u64);
+        for (id, bits) in entries {
+            hasher.score(self.interner.resolve(id).unwrap(), f32::from_bits(bits));
+        }
+        hasher.finalize()
+    }
+}
+
+/// Reads values from an encoded set, tracking the position for errors.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() - self.pos < len {
+            return Err(Error::UnexpectedEnd(self.pos));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn header(&mut self, tag: [u8; 4]) -> Result<u64, Error> {
+        if self.take(4)? != tag {
+            return Err(Error::InvalidTag);
+        }
+        let version = self.take(1)?[0];
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().unwrap());
+        let pos = self.pos;
+        let bytes = self.take(len as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidUtf8(pos))
+    }
+
+    fn value(&mut self) -> Result<f32, Error> {
+        let pos = self.pos;
+        let bits = u32::from_be_bytes(self.take(4)?.try_into().unwrap());
+        let value = f32::from_bits(bits);
+        if canonical_bits(value) != bits {
+            return Err(Error::NotCanonical(pos));
+        }
+        Ok(value)
+    }
+
+    fn finish(&self) -> Result<(), Error> {
+        if self.pos != self.bytes.len() {
+            return Err(Error::TrailingBytes(self.pos));
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a trust set, rejecting encodings that are not canonical.
+pub fn decode_trust(bytes: &[u8]) -> Result<Vec<TrustEntry>, Error> {
+    let mut cursor = Cursor { bytes, pos: 0 };
+    let count = cursor.header(TRUST_SET_TAG)?;
+    let mut entries: Vec<TrustEntry> = Vec::new();
+    for _ in 0..count {
+        let pos = cursor.pos;
+        let entry = TrustEntry::new(cursor.string()?, cursor.string()?, cursor.value()?);
+        if let Some(prev) = entries.last() {
+            if (prev.from(), prev.to()) >= (entry.from(), entry.to()) {
+                return Err(Error::NotCanonical(pos));
+            }
+        }
+        entries.push(entry);
+    }
+    cursor.finish()?;
+    Ok(entries)
+}
+
+/// Decodes a seed or score set, rejecting encodings that are not canonical.
+pub fn decode_scores(bytes: &[u8]) -> Result<Vec<ScoreEntry>, Error> {
+    let mut cursor = Cursor { bytes, pos: 0 };
+    let count = cursor.header(SCORE_SET_TAG)?;
+    let mut entries: Vec<ScoreEntry> = Vec::new();
+    for _ in 0..count {
+        let pos = cursor.pos;
+        let entry = ScoreEntry::new(cursor.string()?, cursor.value()?);
+        if let Some(prev) = entries.last() {
+            if prev.id() >= entry.id() {
+                return Err(Error::NotCanonical(pos));
+            }
+        }
+        entries.push(entry);
+    }
+    cursor.finish()?;
+    Ok(entries)
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+/// Errors that can arise while decoding a canonical set.
+/// Positions are byte offsets into the encoding.
+pub enum Error {
+    #[error("Invalid set tag")]
+    InvalidTag,
+    #[error("Unsupported encoding version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Unexpected end of data at byte {0}")]
+    UnexpectedEnd(usize),
+    #[error("Invalid UTF-8 id at byte {0}")]
+    InvalidUtf8(usize),
+    #[error("Entry at byte {0} is not in canonical form")]
+    NotCanonical(usize),
+    #[error("Trailing bytes at byte {0}")]
+    TrailingBytes(usize),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tx::canonical::{
+        canonical_trust, decode_scores, decode_trust, encode_scores, encode_trust, scores_id,
+        trust_id, Error, IdHasher, ScoresIdHasher, TrustIdHasher,
+    };
+    use crate::tx::trust::{ScoreEntry, TrustEntry};
+
+    fn trust(from: &str, to: &str, value: f32) -> TrustEntry {
+        TrustEntry::new(from.to_string(), to.to_string(), value)
+    }
+
+    #[test]
+    fn should_not_depend_on_order_or_float_formatting() {
+        let a = vec![
+            trust("a", "b", 0.1),
+            trust("b", "c", 0.0),
+            trust("a", "c", 1.0),
+        ];
+        let b = vec![
+            trust("a", "c", "1.000".parse().unwrap()),
+            trust("b", "c", -0.0),
+            trust("a", "b", "1e-1".parse().unwrap()),
+        ];
+        assert_eq!(trust_id(&a), trust_id(&b));
+        assert_ne!(trust_id(&a), trust_id(&a[..2]));
+
+        let scores = vec![
+            ScoreEntry::new("b".to_string(), 0.25),
+            ScoreEntry::new("a".to_string(), 0.75),
+        ];
+        let reversed: Vec<ScoreEntry> = scores.iter().rev().cloned().collect();
+        assert_eq!(scores_id(&scores), scores_id(&reversed));
+    }
+
+    #[test]
+    fn should_keep_last_duplicate() {
+        let entries = vec![trust("a", "b", 0.5), trust("a", "b", 0.25)];
+        assert_eq!(
+            canonical_trust(entries.clone()),
+            vec![trust("a", "b", 0.25)]
+        );
+        assert_eq!(
+            decode_trust(&encode_trust(&entries)).unwrap(),
+            vec![trust("a", "b", 0.25)]
+        );
+    }
+
+    #[test]
+    fn should_keep_zero_values() {
+        let entries = vec![trust("a", "b", 1.0), trust("a", "b", 0.0)];
+        assert_eq!(canonical_trust(entries.clone()), vec![trust("a", "b", 0.0)]);
+        assert_ne!(trust_id(&entries), trust_id(&[]));
+
+        let scores = vec![ScoreEntry::new("a".to_string(), 0.0)];
+        assert_ne!(scores_id(&scores), scores_id(&[]));
+    }
+
+    #[test]
+    fn should_round_trip_and_reject_non_canonical() {
+        let entries = vec![trust("b", "a", 0.5), trust("a", "b", 0.25)];
+        let bytes = encode_trust(&entries);
+        assert_eq!(decode_trust(&bytes).unwrap(), canonical_trust(entries));
+
+        let scores = vec![ScoreEntry::new("a".to_string(), 0.75)];
+        let bytes = encode_scores(&scores);
+        assert_eq!(decode_scores(&bytes).unwrap(), scores);
+        assert_eq!(decode_trust(&bytes), Err(Error::InvalidTag));
+
+        // -0.0 is not canonical.
+        let mut negative_zero = encode_scores(&[ScoreEntry::new("a".to_string(), 0.0)]);
+        let len = negative_zero.len();
+        negative_zero[len - 4] = 0x80;
+        assert!(matches!(
+            decode_scores(&negative_zero),
+            Err(Error::NotCanonical(_))
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            decode_scores(&trailing),
+            Err(Error::TrailingBytes(bytes.len()))
+        );
+        assert!(matches!(
+            decode_scores(&bytes[..bytes.len() - 1]),
+            Err(Error::UnexpectedEnd(_))
+        ));
+    }
+
+    #[test]
+    fn should_hash_streams_like_the_encoding() {
+        let entries = vec![
+            trust("b", "a", 0.5),
+            trust("a", "b", 0.25),
+            trust("ab", "a", -0.0),
+            trust("b", "a", 0.75),
+            trust("a", "c", f32::NAN),
+        ];
+        let mut hasher = TrustIdHasher::new();
+        entries.iter().for_each(|x| hasher.update(x).unwrap());
+        assert_eq!(hasher.finalize(), trust_id(&entries));
+
+        let scores = vec![
+            ScoreEntry::new("b".to_string(), 0.25),
+            ScoreEntry::new("a".to_string(), 0.75),
+            ScoreEntry::new("b".to_string(), 0.5),
+        ];
+        let mut hasher = ScoresIdHasher::new();
+        scores.iter().for_each(|x| hasher.update(x).unwrap());
+        assert_eq!(hasher.finalize(), scores_id(&scores));
+    }
+}
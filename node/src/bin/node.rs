@@ -1,26 +1,29 @@
-use alloy::hex::FromHex;
-use alloy::primitives::Address;
-use alloy::providers::{ProviderBuilder, WsConnect};
+use alloy::providers::ProviderBuilder;
 use alloy::rpc::client::RpcClient;
-use alloy::signers::local::coins_bip39::English;
-use alloy::signers::local::MnemonicBuilder;
-use alloy::transports::http::reqwest::Url;
-use aws_config::from_env;
-use aws_sdk_s3::Client;
 use clap::Parser;
 use dotenv::dotenv;
+use openrank_common::blob::DatasetCache;
+use openrank_common::chain::{IngestStatus, OpenRankClient, WsLogSource};
+use openrank_common::config::ConfigArgs;
 use openrank_common::eigenda::EigenDAProxyClient;
+use openrank_common::health::{
+    self, BlobStoreCheck, EigenDaCheck, Health, LagCheck, QueueCheck, RpcCheck, WsCheck,
+};
+use openrank_common::jobs::JobStore;
 use openrank_common::logs::setup_tracing;
-use openrank_node::sol::{OpenRankManager, ReexecutionEndpoint};
+use openrank_node::config::NodeConfig;
 use openrank_node::{challenger, computer};
-
-const BUCKET_NAME: &str = "openrank-data-dev";
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     #[arg(long)]
     challenger: bool,
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[tokio::main]
@@ -31,51 +34,107 @@ async fn main() {
 
     let cli = Args::parse();
 
-    let eigenda_url = std::env::var("EIGEN_DA_PROXY_URL").expect("EIGEN_DA_PROXY_URL must be set.");
-    let rpc_url = std::env::var("CHAIN_RPC_URL").expect("CHAIN_RPC_URL must be set.");
-    let wss_url = std::env::var("CHAIN_WSS_URL").expect("CHAIN_WSS_URL must be set.");
-    let manager_address =
-        std::env::var("OPENRANK_MANAGER_ADDRESS").expect("OPENRANK_MANAGER_ADDRESS must be set.");
-    let rxp_address = std::env::var("REEXECUTION_ENDPOINT_ADDRESS")
-        .expect("REEXECUTION_ENDPOINT_ADDRESS must be set.");
-    let mnemonic = std::env::var("MNEMONIC").expect("MNEMONIC must be set.");
-    let config = from_env().region("us-west-2").load().await;
-    let client = Client::new(&config);
+    // Each role can use its own key, see SignerConfig.
+    let role = if cli.challenger {
+        "challenger"
+    } else {
+        "computer"
+    };
+    let config = cli
+        .config
+        .load_or_exit(|x| NodeConfig::from_settings(x, role));
+
+    let store = config.blob_store().build().await;
+    let cache = Arc::new(
+        DatasetCache::new(config.cache().clone()).expect("Failed to open the data cache."),
+    );
+    let tx_config = config.tx().clone();
 
-    let wallet = MnemonicBuilder::<English>::default()
-        .phrase(mnemonic)
-        .index(0)
-        .unwrap()
-        .build()
-        .unwrap();
+    let wallet = config.signer().build().expect("Failed to load the signer.");
+    let address = wallet.default_signer().address();
 
     let provider_http = ProviderBuilder::new()
         .wallet(wallet.clone())
-        .on_client(RpcClient::new_http(Url::parse(&rpc_url).unwrap()));
+        .on_client(RpcClient::new_http(config.chain().rpc_url().clone()));
 
-    let ws = WsConnect::new(wss_url);
-    let provider_wss = ProviderBuilder::new().on_ws(ws).await.unwrap();
+    // The log subscription is opened again by the source when it closes.
+    let source = WsLogSource::connect(config.wss_url())
+        .await
+        .expect("Failed to connect to the WebSocket endpoint.");
+    let status = Arc::new(IngestStatus::default());
 
-    let manager_address = Address::from_hex(manager_address).unwrap();
-    let manager_contract = OpenRankManager::new(manager_address, provider_http.clone());
-    let manager_contract_ws = OpenRankManager::new(manager_address, provider_wss.clone());
+    let manager_address = *config.chain().manager_address();
+    // Transactions of the role are tracked in their own file, so that roles with different
+    // keys can share a host.
+    let tracker = tx_config
+        .tracker(role, address)
+        .expect("Failed to open the pending transactions.");
+    let client = OpenRankClient::new(manager_address, provider_http.clone(), tx_config)
+        .with_tracker(Arc::new(tracker));
+    client
+        .reconcile_pending()
+        .await
+        .expect("Failed to reconcile the pending transactions.");
 
-    let rxp_address = Address::from_hex(rxp_address).unwrap();
-    let rxp_contract = ReexecutionEndpoint::new(rxp_address, provider_wss);
+    let eigenda_client = EigenDAProxyClient::new(config.eigenda_url().clone());
 
-    let eigenda_client = EigenDAProxyClient::new(eigenda_url);
+    let health_config = config.health();
+    let mut health = Health::new()
+        .with_live(WsCheck::new(
+            status.clone(),
+            *health_config.max_disconnected(),
+        ))
+        .with_ready(RpcCheck::new(provider_http.clone()))
+        .with_ready(WsCheck::new(status.clone(), Duration::ZERO))
+        .with_ready(LagCheck::new(
+            provider_http,
+            status.clone(),
+            *health_config.max_lag_blocks(),
+        ))
+        .with_ready(BlobStoreCheck::new(store.clone()))
+        .with_ready(EigenDaCheck::new(eigenda_client.clone()));
+    let jobs = if cli.challenger {
+        None
+    } else {
+        let jobs =
+            Arc::new(JobStore::open(config.jobs().clone()).expect("Failed to open the job store."));
+        health = health.with_ready(QueueCheck::new(jobs.clone()));
+        Some(jobs)
+    };
+    let metrics_addr = *config.metrics_addr();
+    let health = Arc::new(health);
+    tokio::spawn(async move {
+        if let Err(e) = health::serve(metrics_addr, health).await {
+            error!("Metrics and health endpoints stopped: {}", e);
+        }
+    });
 
-    if cli.challenger {
+    if let Some(jobs) = jobs {
+        computer::run(
+            client,
+            address,
+            source,
+            status,
+            store,
+            cache,
+            jobs,
+            config.batch().clone(),
+            *config.compression(),
+            config.ingest().clone(),
+        )
+        .await;
+    } else {
         challenger::run(
-            manager_contract,
-            rxp_contract,
-            provider_http,
             client,
+            *config.rxp_address(),
+            source,
+            status,
+            store,
+            cache,
             eigenda_client,
-            BUCKET_NAME,
+            *config.compression(),
+            config.ingest().clone(),
         )
         .await;
-    } else {
-        computer::run(manager_contract, manager_contract_ws, client, BUCKET_NAME).await;
     }
 }
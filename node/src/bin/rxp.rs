@@ -1,27 +1,33 @@
-use alloy::hex::{self, FromHex};
+use alloy::hex::{FromHex, ToHexExt};
+use alloy::network::EthereumWallet;
 use alloy::primitives::{Address, FixedBytes, Uint};
 use alloy::providers::{Provider, ProviderBuilder};
 use alloy::rpc::client::RpcClient;
-use alloy::signers::local::coins_bip39::English;
-use alloy::signers::local::{MnemonicBuilder, PrivateKeySigner};
 use alloy::sol_types::sol_data::Uint as SolUint;
 use alloy::sol_types::SolType;
-use alloy::transports::http::reqwest::Url;
 use alloy_rlp::{Encodable, RlpEncodable};
-use csv::StringRecord;
+use clap::Parser;
 use dotenv::dotenv;
+use openrank_common::blob::{get_verified, verify_content, BlobKey, BlobStore, Error as BlobError};
+use openrank_common::chain::{Error as ChainError, ManagerError, OpenRankClient, TxConfig};
+use openrank_common::config::ConfigArgs;
 use openrank_common::eigenda::EigenDAProxyClient;
+use openrank_common::io::compression::decompress;
+use openrank_common::io::{read_scores_bytes, read_trust_bytes};
 use openrank_common::logs::setup_tracing;
+use openrank_common::manifest::{
+    ChallengeData, Error as ManifestError, JobManifest, ResultManifest,
+};
 use openrank_common::merkle::fixed::DenseMerkleTree;
 use openrank_common::merkle::Hash;
-use openrank_common::runners::verification_runner::{self, VerificationRunner};
-use openrank_common::tx::trust::{ScoreEntry, TrustEntry};
-use openrank_common::Domain;
-use openrank_node::sol::OpenRankManager;
-use openrank_node::{error::Error as NodeError, sol::OpenRankManager::OpenRankManagerInstance};
-use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
+use openrank_common::runners::verification_runner::{
+    self, Error as VerificationRunnerError, VerificationRunner,
+};
+use openrank_common::tx::canonical::scores_id;
+use openrank_node::config::RxpConfig;
+use openrank_node::error::Error as NodeError;
 use sha3::Keccak256;
+use std::sync::Arc;
 use tonic::transport::Server;
 use tonic::{Request, Response, Status};
 use tracing::info;
@@ -35,20 +41,11 @@ mod proto {
         tonic::include_file_descriptor_set!("rxp_descriptor");
 }
 
-#[macro_use]
-extern crate dotenv_codegen;
-
-#[derive(Serialize, Deserialize, Clone)]
-struct JobDescription {
-    alpha: f32,
-    trust_id: String,
-    seed_id: String,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct JobResult {
-    scores_id: String,
-    commitment: String,
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[derive(Debug, Default)]
@@ -70,90 +67,133 @@ pub struct OpenRankExeResult {
     sub_job_commitment: FixedBytes<32>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct EigenDaJobDescription {
-    neighbour_commitments: Vec<String>,
-    trust_data: Vec<u8>,
-    seed_data: Vec<u8>,
-    scores_data: Vec<u8>,
+/// Downloads a job or result manifest, checking its content id.
+async fn download_meta(store: &dyn BlobStore, meta_id: String) -> Result<Vec<u8>, NodeError> {
+    get_verified(store, &BlobKey::meta(meta_id))
+        .await
+        .map_err(NodeError::BlobStoreError)
 }
 
-pub async fn download_meta<T: DeserializeOwned>(
+/// Downloads the challenge data posted by the challenger, checking its version.
+pub async fn download_challenge(
     eigenda_client: &EigenDAProxyClient,
     certificate: Vec<u8>,
-) -> Result<T, NodeError> {
+) -> Result<ChallengeData, NodeError> {
     let res_bytes = eigenda_client.get_meta(certificate).await;
-    let meta: T = serde_json::from_slice(res_bytes.as_slice()).map_err(NodeError::SerdeError)?;
-    Ok(meta)
+    let res_bytes = decompress(res_bytes).map_err(NodeError::DatasetError)?;
+    ChallengeData::from_json(&res_bytes).map_err(NodeError::ManifestError)
 }
 
 pub async fn run<P: Provider>(
-    contract: OpenRankManagerInstance<(), P>,
+    client: OpenRankClient<P>,
+    store: &dyn BlobStore,
     eigenda_client: EigenDAProxyClient,
     input: OpenRankExeInput,
 ) -> Result<OpenRankExeResult, NodeError> {
-    let challenge = contract
-        .metaChallenges(input.compute_id)
-        .call()
+    let challenge = client
+        .meta_challenge(input.compute_id)
         .await
-        .map_err(|e| NodeError::TxError(format!("{e:}")))?;
-    let meta_result: EigenDaJobDescription =
-        download_meta(&eigenda_client, challenge.certificate.to_vec()).await?;
-
-    let mut trust_rdr = csv::Reader::from_reader(meta_result.trust_data.as_slice());
-    let mut seed_rdr = csv::Reader::from_reader(meta_result.seed_data.as_slice());
-    let mut scores_rdr = csv::Reader::from_reader(meta_result.scores_data.as_slice());
-
-    let mut trust_entries = Vec::new();
-    for result in trust_rdr.records() {
-        let record: StringRecord = result.map_err(NodeError::CsvError)?;
-        let (from, to, value): (String, String, f32) =
-            record.deserialize(None).map_err(NodeError::CsvError)?;
-        let trust_entry = TrustEntry::new(from, to, value);
-        trust_entries.push(trust_entry);
-    }
-
-    let mut seed_entries = Vec::new();
-    for result in seed_rdr.records() {
-        let record: StringRecord = result.map_err(NodeError::CsvError)?;
-        let (id, value): (String, f32) = record.deserialize(None).map_err(NodeError::CsvError)?;
-        let seed_entry = ScoreEntry::new(id, value);
-        seed_entries.push(seed_entry);
-    }
-
-    let mut scores_entries = Vec::new();
-    for result in scores_rdr.records() {
-        let record: StringRecord = result.map_err(NodeError::CsvError)?;
-        let (id, value): (String, f32) = record.deserialize(None).map_err(NodeError::CsvError)?;
-        let score_entry = ScoreEntry::new(id, value);
-        scores_entries.push(score_entry);
-    }
+        .map_err(NodeError::ChainError)?
+        .ok_or(NodeError::ChainError(ChainError::Revert(
+            ManagerError::ChallengeNotFound,
+        )))?;
+    let request = client
+        .meta_compute_request(input.compute_id)
+        .await
+        .map_err(NodeError::ChainError)?
+        .ok_or(NodeError::ChainError(ChainError::Revert(
+            ManagerError::ComputeRequestNotFound,
+        )))?;
+    let compute_result = client
+        .meta_compute_result(input.compute_id)
+        .await
+        .map_err(NodeError::ChainError)?
+        .ok_or(NodeError::ChainError(ChainError::Revert(
+            ManagerError::ComputeResultNotFound,
+        )))?;
+    let meta_result = download_challenge(&eigenda_client, challenge.certificate.to_vec()).await?;
+
+    // The challenged datasets must be the ones of the sub-job, as referenced on chain.
+    let sub_job = challenge.subJobId as usize;
+    let job_bytes = download_meta(store, request.jobDescriptionId.encode_hex()).await?;
+    let job_manifest = JobManifest::from_jsonl(&job_bytes).map_err(NodeError::ManifestError)?;
+    let job_description = job_manifest
+        .jobs()
+        .get(sub_job)
+        .ok_or(NodeError::ManifestError(ManifestError::SubJobNotFound(
+            sub_job,
+        )))?;
+    let results_bytes = download_meta(store, compute_result.resultsId.encode_hex()).await?;
+    let results_manifest =
+        ResultManifest::from_jsonl(&results_bytes).map_err(NodeError::ManifestError)?;
+    let job_result = results_manifest
+        .results()
+        .get(sub_job)
+        .ok_or(NodeError::ManifestError(ManifestError::SubJobNotFound(
+            sub_job,
+        )))?;
+
+    let format = *meta_result.format();
+    let csv_config = job_description.csv_config();
+    let mut commitments = meta_result.neighbour_commitments().clone();
+    let (trust_data, seed_data, scores_data) = meta_result.into_datasets();
+    let trust_entries =
+        read_trust_bytes(format, csv_config, trust_data).map_err(NodeError::DatasetError)?;
+    let seed_entries =
+        read_scores_bytes(format, csv_config, seed_data).map_err(NodeError::DatasetError)?;
+    let scores_entries =
+        read_scores_bytes(format, csv_config, scores_data).map_err(NodeError::DatasetError)?;
+
+    verify_content(
+        &BlobKey::scores(job_result.scores_id().clone().to_hex()),
+        &scores_id(&scores_entries),
+    )
+    .map_err(NodeError::BlobStoreError)?;
 
     info!("Starting core compute...");
-    let mock_domain = Domain::default();
-    let mut runner = VerificationRunner::new(&[mock_domain.clone()]);
+    let domain = job_manifest
+        .domains()
+        .map_err(NodeError::ManifestError)?
+        .swap_remove(sub_job);
+    let mut runner = VerificationRunner::new(&[domain.clone()]);
+    runner
+        .update_trust_map(domain.clone(), trust_entries)
+        .map_err(NodeError::VerificationRunnerError)?;
     runner
-        .update_trust_map(mock_domain.clone(), trust_entries.to_vec())
+        .update_seed_map(domain.clone(), seed_entries)
+        .map_err(NodeError::VerificationRunnerError)?;
+    let trust_id = runner
+        .trust_id(&domain)
+        .map_err(NodeError::VerificationRunnerError)?;
+    verify_content(
+        &BlobKey::trust(job_description.trust_id().clone().to_hex()),
+        &trust_id,
+    )
+    .map_err(NodeError::BlobStoreError)?;
+    let seed_id = runner
+        .seed_id(&domain)
         .map_err(NodeError::VerificationRunnerError)?;
+    verify_content(
+        &BlobKey::seed(job_description.seed_id().clone().to_hex()),
+        &seed_id,
+    )
+    .map_err(NodeError::BlobStoreError)?;
+
+    // Same canonical order as the computer and the challenger.
     runner
-        .update_seed_map(mock_domain.clone(), seed_entries.to_vec())
+        .sort_indices(domain.clone())
         .map_err(NodeError::VerificationRunnerError)?;
     runner
-        .update_scores(mock_domain.clone(), Hash::default(), scores_entries)
+        .update_scores(domain.clone(), Hash::default(), scores_entries)
         .map_err(NodeError::VerificationRunnerError)?;
     let result = runner
-        .verify_scores(mock_domain.clone(), Hash::default())
+        .verify_scores(domain.clone(), Hash::default())
         .map_err(NodeError::VerificationRunnerError)?;
     let (sub_job_commitment, _) = runner
-        .get_root_hashes(mock_domain, Hash::default())
+        .get_root_hashes(domain, Hash::default())
         .map_err(NodeError::VerificationRunnerError)?;
     info!("Core Compute verification completed. Result({})", result);
 
-    let mut commitments: Vec<Hash> = meta_result
-        .neighbour_commitments
-        .iter()
-        .map(|x| Hash::from_slice(hex::decode(x).unwrap().as_slice()))
-        .collect();
     commitments.insert(challenge.subJobId as usize, sub_job_commitment.clone());
 
     let commitment_tree = DenseMerkleTree::<Keccak256>::new(commitments)
@@ -171,23 +211,47 @@ pub async fn run<P: Provider>(
     Ok(exe_res)
 }
 
+/// Maps a [NodeError] to the gRPC [Status] returned to the caller.
+fn to_status(err: NodeError) -> Status {
+    match err {
+        NodeError::VerificationRunnerError(
+            e @ (VerificationRunnerError::CommitmentNotFound
+            | VerificationRunnerError::ComputeScoresNotFound(_)
+            | VerificationRunnerError::ComputeTreeNotFound(_)),
+        ) => Status::not_found(e.to_string()),
+        NodeError::ChainError(e @ ChainError::Revert(ManagerError::ChallengeNotFound)) => {
+            Status::not_found(e.to_string())
+        }
+        NodeError::SerdeError(e) => Status::invalid_argument(e.to_string()),
+        NodeError::ManifestError(e) => Status::invalid_argument(e.to_string()),
+        NodeError::DatasetError(e) => Status::invalid_argument(e.to_string()),
+        NodeError::BlobStoreError(e @ BlobError::ContentHashMismatch { .. }) => {
+            Status::invalid_argument(e.to_string())
+        }
+        e => Status::internal(e.to_string()),
+    }
+}
+
 struct RxpService {
-    wallet: PrivateKeySigner,
+    wallet: EthereumWallet,
     rpc_client: RpcClient,
+    store: Arc<dyn BlobStore>,
     eigenda_client: EigenDAProxyClient,
     manager_address: Address,
 }
 
 impl RxpService {
     pub fn new(
-        wallet: PrivateKeySigner,
+        wallet: EthereumWallet,
         rpc_client: RpcClient,
+        store: Arc<dyn BlobStore>,
         eigenda_client: EigenDAProxyClient,
         manager_address: Address,
     ) -> Self {
         Self {
             wallet,
             rpc_client,
+            store,
             eigenda_client,
             manager_address,
         }
@@ -221,17 +285,18 @@ impl PerformerService for RxpService {
         let provider_http = ProviderBuilder::new()
             .wallet(self.wallet.clone())
             .on_client(self.rpc_client.clone());
-        let manager_contract = OpenRankManager::new(self.manager_address, provider_http.clone());
+        let client = OpenRankClient::new(self.manager_address, provider_http, TxConfig::default());
         type Input = (SolUint<256>, SolUint<32>);
-        let (compute_id, job_id) =
-            Input::abi_decode(task_request.payload.as_slice(), true).unwrap();
+        let (compute_id, job_id) = Input::abi_decode(task_request.payload.as_slice(), true)
+            .map_err(|e| Status::invalid_argument(format!("Invalid task payload: {e:}")))?;
         let res = run(
-            manager_contract,
+            client,
+            self.store.as_ref(),
             self.eigenda_client.clone(),
             OpenRankExeInput::new(compute_id, job_id),
         )
         .await
-        .unwrap();
+        .map_err(to_status)?;
 
         let mut encoded_res = Vec::new();
         res.encode(&mut encoded_res);
@@ -249,22 +314,16 @@ async fn main() {
     dotenv().ok();
     setup_tracing();
 
-    let eigenda_url = std::env::var("DA_PROXY_URL").expect("DA_PROXY_URL must be set.");
-    let service_port = dotenv!("SERVICE_PORT");
-    let rpc_url = dotenv!("ETH_RPC_URL");
-    let manager_address = dotenv!("OPENRANK_MANAGER_ADDRESS");
-    let mnemonic = dotenv!("MNEMONIC");
+    let cli = Args::parse();
+    let config = cli.config.load_or_exit(RxpConfig::from_settings);
 
-    let wallet = MnemonicBuilder::<English>::default()
-        .phrase(mnemonic)
-        .index(0)
-        .unwrap()
-        .build()
-        .unwrap();
+    let wallet = config.signer().build().expect("Failed to load the signer.");
 
-    let rpc_client = RpcClient::new_http(Url::parse(&rpc_url).unwrap());
-    let manager_address = Address::from_hex(manager_address).unwrap();
-    let eigenda_client = EigenDAProxyClient::new(eigenda_url);
+    let rpc_client = RpcClient::new_http(config.chain().rpc_url().clone());
+    let store = config.blob_store().build().await;
+    let manager_address = *config.chain().manager_address();
+    let eigenda_client = EigenDAProxyClient::new(config.eigenda_url().clone());
+    let service_port = config.service_port();
 
     info!("Running the rxp node on port {}..", service_port);
 
@@ -273,7 +332,7 @@ async fn main() {
         .build()
         .unwrap();
 
-    let rxp_service = RxpService::new(wallet, rpc_client, eigenda_client, manager_address);
+    let rxp_service = RxpService::new(wallet, rpc_client, store, eigenda_client, manager_address);
     let addr = format!("0.0.0.0:{}", service_port).parse().unwrap();
     Server::builder()
         .add_service(reflection_service)
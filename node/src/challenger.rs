@@ -1,112 +1,165 @@
 use crate::error::Error as NodeError;
-use crate::sol::OpenRankManager::{
-    MetaChallengeEvent, MetaComputeRequestEvent, MetaComputeResultEvent, OpenRankManagerInstance,
-};
-use crate::sol::ReexecutionEndpoint::{
-    OperatorResponse, ReexecutionEndpointInstance, ReexecutionRequestCreated,
-};
+use crate::sol::ReexecutionEndpoint::{OperatorResponse, ReexecutionRequestCreated};
 use alloy::eips::{BlockId, BlockNumberOrTag};
-use alloy::hex::{self, ToHexExt};
-use alloy::primitives::{Bytes, Uint};
+use alloy::hex::ToHexExt;
+use alloy::primitives::{Address, Uint};
 use alloy::providers::Provider;
-use alloy::rpc::types::Log;
-use aws_sdk_s3::Client;
-use csv::StringRecord;
-use futures_util::StreamExt;
+use alloy::rpc::types::{Block, Filter, Log};
+use alloy::sol_types::SolEvent;
+use openrank_common::blob::{get_verified, BlobKey, BlobStore, DatasetCache};
+use openrank_common::chain::contract::IOpenRankManager::{
+    MetaChallengeEvent, MetaComputeRequestEvent, MetaComputeResultEvent,
+};
+use openrank_common::chain::{
+    decode_log, Error as ChainError, EventIngestor, IngestConfig, IngestStatus, LogSource,
+    OpenRankClient,
+};
 use openrank_common::eigenda::EigenDAProxyClient;
+use openrank_common::io::compression::{compress, Compression};
+use openrank_common::io::{read_scores_file, stream_scores_file, stream_trust_file, EntryReader};
+use openrank_common::manifest::{ChallengeData, JobDescription, JobManifest, ResultManifest};
 use openrank_common::merkle::fixed::DenseMerkleTree;
 use openrank_common::merkle::Hash;
+use openrank_common::metrics;
 use openrank_common::runners::verification_runner::{self, VerificationRunner};
-use openrank_common::tx::trust::{ScoreEntry, TrustEntry};
+use openrank_common::tx::canonical::scores_id;
 use openrank_common::Domain;
 use rand::Rng;
-use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
 use sha3::Keccak256;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Write;
-use tokio::fs::create_dir_all;
-use tokio::select;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::block_in_place;
+use tokio::time::sleep;
 use tracing::{debug, error, info};
 
-#[derive(Serialize, Deserialize)]
-struct JobDescription {
-    alpha: f32,
-    trust_id: String,
-    seed_id: String,
+/// Delay before reading the events again after an RPC error.
+const EVENTS_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Downloads a meta object, checking its content id.
+async fn download_meta(store: &dyn BlobStore, meta_id: String) -> Result<Vec<u8>, NodeError> {
+    get_verified(store, &BlobKey::meta(meta_id))
+        .await
+        .map_err(NodeError::BlobStoreError)
 }
 
-#[derive(Serialize, Deserialize)]
-struct JobResult {
-    scores_id: String,
-    commitment: String,
+/// Opens a dataset through the cache. The download is written to disk synchronously, so it runs
+/// outside of the async worker threads.
+fn open_cached(
+    store: &dyn BlobStore,
+    cache: &DatasetCache,
+    key: &BlobKey,
+) -> Result<File, NodeError> {
+    block_in_place(|| Handle::current().block_on(cache.open(store, key)))
+        .map_err(NodeError::BlobStoreError)
 }
 
-#[derive(Serialize, Deserialize)]
-struct EigenDaJobDescription {
-    neighbour_commitments: Vec<String>,
-    trust_data: Vec<u8>,
-    seed_data: Vec<u8>,
-    scores_data: Vec<u8>,
+/// Reads the whole dataset through the cache.
+fn read_cached(
+    store: &dyn BlobStore,
+    cache: &DatasetCache,
+    key: &BlobKey,
+) -> Result<Vec<u8>, NodeError> {
+    let mut file = open_cached(store, cache, key)?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| NodeError::FileError(format!("Failed to read file: {e:}")))?;
+    Ok(data)
 }
 
-impl EigenDaJobDescription {
-    pub fn new(
-        neighbour_commitments: Vec<String>,
-        trust_data: Vec<u8>,
-        seed_data: Vec<u8>,
-        scores_data: Vec<u8>,
-    ) -> Self {
-        Self {
-            neighbour_commitments,
-            trust_data,
-            seed_data,
-            scores_data,
-        }
-    }
+/// Streams the cached datasets of a sub-job into a runner, checking their content ids, and
+/// verifies the submitted scores against the commitment.
+fn verify_sub_job(
+    cache: &DatasetCache,
+    index: usize,
+    (domain, job_description): (&Domain, &JobDescription),
+    commitment: Hash,
+    (trust_key, trust_file): (BlobKey, File),
+    (seed_key, seed_file): (BlobKey, File),
+    (scores_key, scores_file): (BlobKey, File),
+) -> Result<bool, NodeError> {
+    let (format, csv_config) = (*job_description.format(), job_description.csv_config());
+    let scores_entries =
+        read_scores_file(format, csv_config, scores_file).map_err(NodeError::DatasetError)?;
+    cache
+        .verify(&scores_key, &scores_id(&scores_entries))
+        .map_err(NodeError::BlobStoreError)?;
+
+    let assignment_id = Hash::from_slice(index.to_be_bytes().as_slice());
+    let mut runner = VerificationRunner::new(&[domain.clone()]);
+    // Entries are streamed into the runner without collecting them, and the content ids are
+    // computed from the loaded data.
+    let trust_entries =
+        stream_trust_file(format, csv_config, trust_file).map_err(NodeError::DatasetError)?;
+    let mut trust_reader = EntryReader::new(trust_entries);
+    runner
+        .update_trust_map(domain.clone(), &mut trust_reader)
+        .map_err(NodeError::VerificationRunnerError)?;
+    trust_reader.finish().map_err(NodeError::DatasetError)?;
+    let actual_trust_id = runner
+        .trust_id(domain)
+        .map_err(NodeError::VerificationRunnerError)?;
+    cache
+        .verify(&trust_key, &actual_trust_id)
+        .map_err(NodeError::BlobStoreError)?;
+
+    let seed_entries =
+        stream_scores_file(format, csv_config, seed_file).map_err(NodeError::DatasetError)?;
+    let mut seed_reader = EntryReader::new(seed_entries);
+    runner
+        .update_seed_map(domain.clone(), &mut seed_reader)
+        .map_err(NodeError::VerificationRunnerError)?;
+    seed_reader.finish().map_err(NodeError::DatasetError)?;
+    let actual_seed_id = runner
+        .seed_id(domain)
+        .map_err(NodeError::VerificationRunnerError)?;
+    cache
+        .verify(&seed_key, &actual_seed_id)
+        .map_err(NodeError::BlobStoreError)?;
+    runner
+        .sort_indices(domain.clone())
+        .map_err(NodeError::VerificationRunnerError)?;
+    runner.update_commitment(assignment_id.clone(), commitment);
+    runner
+        .update_scores(domain.clone(), assignment_id.clone(), scores_entries)
+        .map_err(NodeError::VerificationRunnerError)?;
+    runner
+        .verify_job(domain.clone(), assignment_id)
+        .map_err(NodeError::VerificationRunnerError)
 }
 
-pub async fn download_meta<T: DeserializeOwned>(
-    client: &Client,
-    bucket_name: &str,
-    meta_id: String,
-) -> Result<T, NodeError> {
-    let res = client
-        .get_object()
-        .bucket(bucket_name)
-        .key(format!("meta/{}", meta_id))
-        .send()
-        .await
-        .map_err(|e| NodeError::AwsError(e.into()))?;
-    let res_bytes = res
-        .body
-        .collect()
+/// Returns the block, which the provider must know about.
+async fn get_block<PH: Provider>(
+    client: &OpenRankClient<PH>,
+    number: BlockNumberOrTag,
+) -> Result<Block, NodeError> {
+    client
+        .provider()
+        .get_block(BlockId::Number(number))
         .await
-        .map_err(NodeError::ByteStreamError)?;
-    let meta: T =
-        serde_json::from_slice(res_bytes.to_vec().as_slice()).map_err(NodeError::SerdeError)?;
-    Ok(meta)
+        .map_err(|e| NodeError::TxError(e.to_string()))?
+        .ok_or(NodeError::BlockNotFound(number))
 }
 
 async fn handle_meta_compute_result<PH: Provider>(
-    contract: &OpenRankManagerInstance<(), PH>,
-    provider: &PH,
-    s3_client: &Client,
+    client: &OpenRankClient<PH>,
+    store: &dyn BlobStore,
+    cache: &DatasetCache,
     eigenda_client: &EigenDAProxyClient,
-    bucket_name: &str,
+    compression: Compression,
     meta_compute_res: MetaComputeResultEvent,
     log: Log,
     meta_compute_request_map: &HashMap<Uint<256, 4>, MetaComputeRequestEvent>,
     meta_challanged_jobs_map: &HashMap<Uint<256, 4>, Log>,
     challenge_window: u64,
 ) -> Result<(), NodeError> {
-    let meta_result: Vec<JobResult> = download_meta(
-        s3_client,
-        bucket_name,
-        meta_compute_res.resultsId.encode_hex(),
-    )
-    .await?;
+    let results_bytes = download_meta(store, meta_compute_res.resultsId.encode_hex()).await?;
+    let results_manifest =
+        ResultManifest::from_jsonl(&results_bytes).map_err(NodeError::ManifestError)?;
+    let meta_result = results_manifest.results();
 
     info!(
         "ComputeResultEvent: ComputeId({}), Commitment({:#}), ResultsId({:#})",
@@ -116,18 +169,11 @@ async fn handle_meta_compute_result<PH: Provider>(
 
     let already_challenged = meta_challanged_jobs_map.contains_key(&meta_compute_res.computeId);
 
-    let block = provider
-        .get_block(BlockId::Number(BlockNumberOrTag::Latest))
-        .await
-        .map_err(|e| NodeError::TxError(format!("{e:}")))?
-        .unwrap();
-    let log_block = provider
-        .get_block(BlockId::Number(BlockNumberOrTag::Number(
-            log.block_number.unwrap(),
-        )))
-        .await
-        .map_err(|e| NodeError::TxError(format!("{e:}")))?
-        .unwrap();
+    let log_block_number = log.block_number.ok_or_else(|| {
+        NodeError::ChainError(ChainError::Decode("Log without a block number".to_string()))
+    })?;
+    let block = get_block(client, BlockNumberOrTag::Latest).await?;
+    let log_block = get_block(client, BlockNumberOrTag::Number(log_block_number)).await?;
     if already_challenged {
         return Ok(());
     }
@@ -139,134 +185,44 @@ async fn handle_meta_compute_result<PH: Provider>(
         .get(&meta_compute_res.computeId)
         .unwrap();
 
-    let job_description: Vec<JobDescription> = download_meta(
-        s3_client,
-        bucket_name,
-        compute_req.jobDescriptionId.encode_hex(),
-    )
-    .await?;
+    let job_bytes = download_meta(store, compute_req.jobDescriptionId.encode_hex()).await?;
+    let job_manifest = JobManifest::from_jsonl(&job_bytes).map_err(NodeError::ManifestError)?;
+    job_manifest
+        .algorithm()
+        .check_supported()
+        .map_err(NodeError::ManifestError)?;
+    let job_description = job_manifest.jobs();
+    let domains = job_manifest.domains().map_err(NodeError::ManifestError)?;
 
     let mut global_result = true;
     let mut sub_job_failed = 0;
     let mut commitments = Vec::new();
     for (i, compute_res) in meta_result.iter().enumerate() {
-        info!("Downloading data...");
+        let trust_key = BlobKey::trust(job_description[i].trust_id().clone().to_hex());
+        let seed_key = BlobKey::seed(job_description[i].seed_id().clone().to_hex());
+        let scores_key = BlobKey::scores(compute_res.scores_id().clone().to_hex());
 
-        create_dir_all(&format!("./trust/")).await.unwrap();
-        create_dir_all(&format!("./seed/")).await.unwrap();
-        create_dir_all(&format!("./scores/")).await.unwrap();
-        let mut trust_file = File::create(&format!("./trust/{}", job_description[i].trust_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to create file: {e:}")))?;
-        let mut seed_file = File::create(&format!("./seed/{}", job_description[i].seed_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to create file: {e:}")))?;
-        let mut scores_file = File::create(&format!("./scores/{}", compute_res.scores_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to create file: {e:}")))?;
-
-        let mut trust_res = s3_client
-            .get_object()
-            .bucket(bucket_name)
-            .key(format!("trust/{}", job_description[i].trust_id))
-            .send()
-            .await
-            .map_err(|e| NodeError::AwsError(e.into()))?;
-        let mut seed_res = s3_client
-            .get_object()
-            .bucket(bucket_name)
-            .key(format!("seed/{}", job_description[i].seed_id))
-            .send()
-            .await
-            .map_err(|e| NodeError::AwsError(e.into()))?;
-        let mut scores_res = s3_client
-            .get_object()
-            .bucket(bucket_name)
-            .key(format!("scores/{}", compute_res.scores_id))
-            .send()
-            .await
-            .map_err(|e| NodeError::AwsError(e.into()))?;
-
-        while let Some(bytes) = trust_res.body.next().await {
-            trust_file
-                .write(&bytes.unwrap())
-                .map_err(|e| NodeError::FileError(format!("Failed to write to file: {e:}")))?;
-        }
-        while let Some(bytes) = seed_res.body.next().await {
-            seed_file
-                .write(&bytes.unwrap())
-                .map_err(|e| NodeError::FileError(format!("Failed to write to file: {e:}")))?;
-        }
-        while let Some(bytes) = scores_res.body.next().await {
-            scores_file
-                .write(&bytes.unwrap())
-                .map_err(|e| NodeError::FileError(format!("Failed to write to file: {e:}")))?;
-        }
-    }
-
-    for (i, compute_res) in meta_result.iter().enumerate() {
-        let trust_file = File::open(&format!("./trust/{}", job_description[i].trust_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to open file: {e:}")))?;
-        let seed_file = File::open(&format!("./seed/{}", job_description[i].seed_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to open file: {e:}")))?;
-        let scores_file = File::open(&format!("./scores/{}", compute_res.scores_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to open file: {e:}")))?;
-
-        let mut trust_rdr = csv::Reader::from_reader(trust_file);
-        let mut seed_rdr = csv::Reader::from_reader(seed_file);
-        let mut scores_rdr = csv::Reader::from_reader(scores_file);
-
-        let mut trust_entries = Vec::new();
-        for result in trust_rdr.records() {
-            let record: StringRecord = result.map_err(NodeError::CsvError)?;
-            let (from, to, value): (String, String, f32) =
-                record.deserialize(None).map_err(NodeError::CsvError)?;
-            let trust_entry = TrustEntry::new(from, to, value);
-            trust_entries.push(trust_entry);
-        }
-
-        let mut seed_entries = Vec::new();
-        for result in seed_rdr.records() {
-            let record: StringRecord = result.map_err(NodeError::CsvError)?;
-            let (id, value): (String, f32) =
-                record.deserialize(None).map_err(NodeError::CsvError)?;
-            let seed_entry = ScoreEntry::new(id, value);
-            seed_entries.push(seed_entry);
-        }
-
-        let mut scores_entries = Vec::new();
-        for result in scores_rdr.records() {
-            let record: StringRecord = result.map_err(NodeError::CsvError)?;
-            let (id, value): (String, f32) =
-                record.deserialize(None).map_err(NodeError::CsvError)?;
-            let score_entry = ScoreEntry::new(id, value);
-            scores_entries.push(score_entry);
-        }
+        // The trust and seed data is usually still cached from computing the same job.
+        info!("Downloading data...");
+        let download_start = Instant::now();
+        let trust_file = open_cached(store, cache, &trust_key)?;
+        let seed_file = open_cached(store, cache, &seed_key)?;
+        let scores_file = open_cached(store, cache, &scores_key)?;
+        metrics::observe_since(&metrics::STAGE_DURATION, &["download"], download_start);
 
         info!("Starting core compute...");
-        let mock_domain = Domain::default();
-        let mut runner = VerificationRunner::new(&[mock_domain.clone()]);
-        runner
-            .update_trust_map(mock_domain.clone(), trust_entries.to_vec())
-            .map_err(NodeError::VerificationRunnerError)?;
-        runner
-            .update_seed_map(mock_domain.clone(), seed_entries.to_vec())
-            .map_err(NodeError::VerificationRunnerError)?;
-        runner.update_commitment(
-            Hash::from_slice(i.to_be_bytes().as_slice()),
-            Hash::from_slice(
-                hex::decode(compute_res.commitment.clone())
-                    .unwrap()
-                    .as_slice(),
-            ),
-        );
-        runner
-            .update_scores(
-                mock_domain.clone(),
-                Hash::from_slice(i.to_be_bytes().as_slice()),
-                scores_entries,
+        // Parsing, hashing and verifying the datasets is CPU bound.
+        let result = block_in_place(|| {
+            verify_sub_job(
+                cache,
+                i,
+                (&domains[i], &job_description[i]),
+                compute_res.commitment().clone(),
+                (trust_key, trust_file),
+                (seed_key, seed_file),
+                (scores_key, scores_file),
             )
-            .map_err(NodeError::VerificationRunnerError)?;
-        let result = runner
-            .verify_job(mock_domain, Hash::from_slice(i.to_be_bytes().as_slice()))
-            .map_err(NodeError::VerificationRunnerError)?;
+        })?;
         info!("Core Compute verification completed. Result({})", result);
 
         if !result {
@@ -274,16 +230,11 @@ async fn handle_meta_compute_result<PH: Provider>(
             sub_job_failed = i;
             break;
         }
-        commitments.push(compute_res.commitment.clone());
+        commitments.push(compute_res.commitment().clone());
     }
 
-    let commitment_tree = DenseMerkleTree::<Keccak256>::new(
-        commitments
-            .iter()
-            .map(|x| Hash::from_slice(hex::decode(x).unwrap().as_slice()))
-            .collect(),
-    )
-    .map_err(|e| NodeError::VerificationRunnerError(verification_runner::Error::Merkle(e)))?;
+    let commitment_tree = DenseMerkleTree::<Keccak256>::new(commitments.clone())
+        .map_err(|e| NodeError::VerificationRunnerError(verification_runner::Error::Merkle(e)))?;
     let meta_commitment = commitment_tree
         .root()
         .map_err(|e| NodeError::VerificationRunnerError(verification_runner::Error::Merkle(e)))?;
@@ -298,97 +249,146 @@ async fn handle_meta_compute_result<PH: Provider>(
         (block.header.timestamp - log_block.header.timestamp) < challenge_window;
     info!("Challenge window open: {}", challenge_window_open);
 
+    // The verification takes a while, during which the result might have been reorged out.
+    let current_block = get_block(client, BlockNumberOrTag::Number(log_block_number)).await?;
+    if Some(current_block.header.hash) != log.block_hash {
+        info!(
+            "MetaComputeResultEvent removed by a reorg, challenge skipped: ComputeId({})",
+            meta_compute_res.computeId
+        );
+        return Ok(());
+    }
+
     let mut rng = rand::rng();
     if challenge_window_open && rng.random_range(0.0..1.0) <= 1.0 {
         info!("Posting input data on EigenDA");
-        let trust_data = std::fs::read(&format!(
-            "./trust/{}",
-            job_description[sub_job_failed].trust_id
-        ))
-        .unwrap();
-        let seed_data = std::fs::read(&format!(
-            "./seed/{}",
-            job_description[sub_job_failed].seed_id
-        ))
-        .unwrap();
-        let scores_data = std::fs::read(&format!(
-            "./scores/{}",
-            meta_result[sub_job_failed].scores_id
-        ))
-        .unwrap();
-        let res = EigenDaJobDescription::new(commitments, trust_data, seed_data, scores_data);
-        let data = serde_json::to_vec(&res).unwrap();
+        let trust_data = read_cached(
+            store,
+            cache,
+            &BlobKey::trust(job_description[sub_job_failed].trust_id().clone().to_hex()),
+        )?;
+        let seed_data = read_cached(
+            store,
+            cache,
+            &BlobKey::seed(job_description[sub_job_failed].seed_id().clone().to_hex()),
+        )?;
+        let scores_data = read_cached(
+            store,
+            cache,
+            &BlobKey::scores(meta_result[sub_job_failed].scores_id().clone().to_hex()),
+        )?;
+        let res = ChallengeData::new(
+            commitments,
+            trust_data,
+            seed_data,
+            scores_data,
+            *job_description[sub_job_failed].format(),
+        );
+        let data = res.to_json().map_err(NodeError::ManifestError)?;
+        let data = compress(&data, compression).map_err(NodeError::DatasetError)?;
         let certificate = eigenda_client.put_meta(data).await;
 
         info!("Submitting challenge. Calling 'metaSubmitChallenge'");
-        let res = contract
-            .submitMetaChallenge(
+        let res = client
+            .send_meta_challenge(
                 meta_compute_res.computeId,
                 sub_job_failed as u32,
-                Bytes::from(certificate),
+                certificate,
             )
-            .send()
             .await;
-        if let Ok(res) = res {
-            let tx_res = res.watch().await.unwrap();
-            info!("'metaSubmitChallenge' completed. Tx Hash({:#})", tx_res);
-        } else {
-            let err = res.unwrap_err();
-            error!("'metaSubmitChallenge' failed. {}", err);
+        let res = match res {
+            Ok(tx_hash) => client.wait_for_receipt(tx_hash).await,
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(receipt) => {
+                metrics::CHALLENGES.with_label_values(&["submitted"]).inc();
+                info!(
+                    "'metaSubmitChallenge' completed. Tx Hash({:#})",
+                    receipt.transaction_hash
+                )
+            }
+            Err(e) => {
+                metrics::CHALLENGES.with_label_values(&["failed"]).inc();
+                error!("'metaSubmitChallenge' failed. {}", e)
+            }
         }
     }
 
     Ok(())
 }
 
-pub async fn run<P: Provider, PW: Provider>(
-    manager_contract: OpenRankManagerInstance<(), P>,
-    rxp_contract: ReexecutionEndpointInstance<(), PW>,
-    provider: P,
-    s3_client: Client,
-    eigenda_client: EigenDAProxyClient,
-    bucket_name: &str,
+/// Undoes the effects of a processed log that was removed by a chain reorg.
+fn handle_removed_log(
+    log: &Log,
+    meta_compute_request_map: &mut HashMap<Uint<256, 4>, MetaComputeRequestEvent>,
+    meta_challanged_jobs_map: &mut HashMap<Uint<256, 4>, Log>,
 ) {
-    let challenge_window = manager_contract.CHALLENGE_WINDOW().call().await.unwrap();
+    let topic = log.topic0().copied();
+    if topic == Some(MetaComputeRequestEvent::SIGNATURE_HASH) {
+        if let Ok(compute_req) = decode_log::<MetaComputeRequestEvent>(log) {
+            info!(
+                "MetaComputeRequestEvent removed by a reorg: ComputeId({})",
+                compute_req.computeId
+            );
+            meta_compute_request_map.remove(&compute_req.computeId);
+        }
+    } else if topic == Some(MetaComputeResultEvent::SIGNATURE_HASH) {
+        if let Ok(compute_res) = decode_log::<MetaComputeResultEvent>(log) {
+            // The result is checked to still be on chain before it is challenged, so a removed
+            // result is never challenged. A new result for the request is handled on its own.
+            info!(
+                "MetaComputeResultEvent removed by a reorg: ComputeId({})",
+                compute_res.computeId
+            );
+        }
+    } else if topic == Some(MetaChallengeEvent::SIGNATURE_HASH) {
+        if let Ok(challenge) = decode_log::<MetaChallengeEvent>(log) {
+            info!(
+                "MetaChallengeEvent removed by a reorg: ComputeId({:#})",
+                challenge.computeId
+            );
+            meta_challanged_jobs_map.remove(&challenge.computeId);
+        }
+    } else {
+        debug!("Log removed by a reorg: {:?}", log);
+    }
+}
 
-    // Meta jobs filters
-    let meta_compute_request_filter = manager_contract
-        .MetaComputeRequestEvent_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
-        .await
-        .unwrap();
-    let meta_compute_result_filter = manager_contract
-        .MetaComputeResultEvent_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
-        .await
-        .unwrap();
-    let meta_challenge_filter = manager_contract
-        .MetaChallengeEvent_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
+/// Runs the challenger node. Logs are read from the source, whose connection state and progress
+/// are reported to the status.
+pub async fn run<P: Provider, S: LogSource>(
+    client: OpenRankClient<P>,
+    rxp_address: Address,
+    source: S,
+    status: Arc<IngestStatus>,
+    store: Arc<dyn BlobStore>,
+    cache: Arc<DatasetCache>,
+    eigenda_client: EigenDAProxyClient,
+    compression: Compression,
+    ingest_config: IngestConfig,
+) {
+    let challenge_window = client
+        .challenge_window()
         .await
-        .unwrap();
-    let reexecution_request_filter = rxp_contract
-        .ReexecutionRequestCreated_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
+        .expect("Failed to read the challenge window.");
+
+    // Meta jobs and re-execution events, read from a single log stream so that they stay in
+    // chain order.
+    let filter = Filter::new()
+        .address(vec![*client.address(), rxp_address])
+        .event_signature(vec![
+            MetaComputeRequestEvent::SIGNATURE_HASH,
+            MetaComputeResultEvent::SIGNATURE_HASH,
+            MetaChallengeEvent::SIGNATURE_HASH,
+            ReexecutionRequestCreated::SIGNATURE_HASH,
+            OperatorResponse::SIGNATURE_HASH,
+        ]);
+    let cursor_store = ingest_config.cursor_store("challenger");
+    let mut events = EventIngestor::start(source, filter, ingest_config, cursor_store)
         .await
-        .unwrap();
-    let operator_response_filter = rxp_contract
-        .OperatorResponse_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
-        .await
-        .unwrap();
-
-    // Meta streams
-    let mut meta_compute_request_stream = meta_compute_request_filter.into_stream();
-    let mut meta_compute_result_stream = meta_compute_result_filter.into_stream();
-    let mut meta_challenge_stream = meta_challenge_filter.into_stream();
-    let mut reexecution_request_stream = reexecution_request_filter.into_stream();
-    let mut operator_response_stream = operator_response_filter.into_stream();
+        .expect("Failed to start the event ingestion.")
+        .with_status(status);
 
     let mut meta_compute_request_map = HashMap::new();
     let mut meta_challanged_jobs_map = HashMap::new();
@@ -396,72 +396,112 @@ pub async fn run<P: Provider, PW: Provider>(
     info!("Running the challenger node...");
 
     loop {
-        select! {
-            meta_compute_request_event = meta_compute_request_stream.next() => {
-                if let Some(res) = meta_compute_request_event {
-                    let (compute_req, log): (MetaComputeRequestEvent, Log) = res.unwrap();
-                    info!(
-                        "MetaComputeRequestEvent: ComputeId({}), JobDescriptionId({:#})",
-                        compute_req.computeId, compute_req.jobDescriptionId
-                    );
-                    debug!("{:?}", log);
-
-                    meta_compute_request_map.insert(compute_req.computeId, compute_req);
-                }
+        let log = match events.next().await {
+            Ok(log) => log,
+            Err(ChainError::SubscriptionClosed) => {
+                error!("Log subscription closed for good, stopping the challenger node.");
+                return;
             }
-            meta_compute_result_event = meta_compute_result_stream.next() => {
-                if let Some(res) = meta_compute_result_event {
-                    let (compute_res, log): (MetaComputeResultEvent, Log) = res.unwrap();
-                    handle_meta_compute_result(
-                        &manager_contract,
-                        &provider,
-                        &s3_client,
-                        &eigenda_client,
-                        bucket_name,
-                        compute_res,
-                        log,
-                        &meta_compute_request_map,
-                        &meta_challanged_jobs_map,
-                        challenge_window._0,
-                    ).await.unwrap();
-                }
+            Err(e) => {
+                error!("Failed to read events: {}", e);
+                sleep(EVENTS_RETRY_DELAY).await;
+                continue;
             }
-            meta_challenge_event = meta_challenge_stream.next() => {
-                if let Some(res) = meta_challenge_event {
-                    let (challenge, log): (MetaChallengeEvent, Log) = res.unwrap();
-                    info!(
-                        "MetaChallengeEvent: ComputeId({:#}) SubJobID({:#})",
-                        challenge.computeId,
-                        challenge.subJobId
-                    );
-                    debug!("{:?}", log);
-
-                    meta_challanged_jobs_map.insert(challenge.computeId, log);
+        };
+        if log.removed {
+            handle_removed_log(
+                &log,
+                &mut meta_compute_request_map,
+                &mut meta_challanged_jobs_map,
+            );
+            continue;
+        }
+        let topic = log.topic0().copied();
+        if topic == Some(MetaComputeRequestEvent::SIGNATURE_HASH) {
+            let compute_req = match decode_log::<MetaComputeRequestEvent>(&log) {
+                Ok(compute_req) => compute_req,
+                Err(e) => {
+                    error!("Failed to decode MetaComputeRequestEvent: {}", e);
+                    continue;
                 }
-            }
-            reexecution_request_event = reexecution_request_stream.next() => {
-                if let Some(res) = reexecution_request_event {
-                    let (request, log): (ReexecutionRequestCreated, Log) = res.unwrap();
-                    info!(
-                        "ReexecutionRequestCreated: requestIndex({:#}) avs({:#}), reservationID({:#})",
-                        request.requestIndex,
-                        request.avs,
-                        request.reservationID,
-                    );
-                    debug!("{:?}", log);
+            };
+            info!(
+                "MetaComputeRequestEvent: ComputeId({}), JobDescriptionId({:#})",
+                compute_req.computeId, compute_req.jobDescriptionId
+            );
+            debug!("{:?}", log);
+
+            meta_compute_request_map.insert(compute_req.computeId, compute_req);
+        } else if topic == Some(MetaComputeResultEvent::SIGNATURE_HASH) {
+            let compute_res = match decode_log::<MetaComputeResultEvent>(&log) {
+                Ok(compute_res) => compute_res,
+                Err(e) => {
+                    error!("Failed to decode MetaComputeResultEvent: {}", e);
+                    continue;
                 }
+            };
+            let res = handle_meta_compute_result(
+                &client,
+                store.as_ref(),
+                &cache,
+                &eigenda_client,
+                compression,
+                compute_res,
+                log,
+                &meta_compute_request_map,
+                &meta_challanged_jobs_map,
+                challenge_window,
+            )
+            .await;
+            if let Err(e) = res {
+                error!("Failed to handle MetaComputeResultEvent: {}", e);
             }
-            operator_response_event = operator_response_stream.next() => {
-                if let Some(res) = operator_response_event {
-                    let (response, log): (OperatorResponse, Log) = res.unwrap();
-                    info!(
-                        "OperatorResponse: operator({:#}) response({:#})",
-                        response.operator,
-                        response.response
-                    );
-                    debug!("{:?}", log);
+        } else if topic == Some(MetaChallengeEvent::SIGNATURE_HASH) {
+            let challenge = match decode_log::<MetaChallengeEvent>(&log) {
+                Ok(challenge) => challenge,
+                Err(e) => {
+                    error!("Failed to decode MetaChallengeEvent: {}", e);
+                    continue;
                 }
-            }
+            };
+            info!(
+                "MetaChallengeEvent: ComputeId({:#}) SubJobID({:#})",
+                challenge.computeId, challenge.subJobId
+            );
+            debug!("{:?}", log);
+            metrics::CHALLENGES.with_label_values(&["observed"]).inc();
+
+            meta_challanged_jobs_map.insert(challenge.computeId, log);
+        } else if topic == Some(ReexecutionRequestCreated::SIGNATURE_HASH) {
+            let request = match decode_log::<ReexecutionRequestCreated>(&log) {
+                Ok(request) => request,
+                Err(e) => {
+                    error!("Failed to decode ReexecutionRequestCreated: {}", e);
+                    continue;
+                }
+            };
+            info!(
+                "ReexecutionRequestCreated: requestIndex({:#}) avs({:#}), reservationID({:#})",
+                request.requestIndex, request.avs, request.reservationID,
+            );
+            debug!("{:?}", log);
+            metrics::CHALLENGES
+                .with_label_values(&["reexecution_requested"])
+                .inc();
+        } else if topic == Some(OperatorResponse::SIGNATURE_HASH) {
+            let response = match decode_log::<OperatorResponse>(&log) {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Failed to decode OperatorResponse: {}", e);
+                    continue;
+                }
+            };
+            info!(
+                "OperatorResponse: operator({:#}) response({:#})",
+                response.operator, response.response
+            );
+            debug!("{:?}", log);
+            metrics::CHALLENGES.with_label_values(&["responded"]).inc();
         }
     }
 }
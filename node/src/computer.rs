@@ -1,249 +1,176 @@
 use crate::error::Error as NodeError;
-use crate::sol::OpenRankManager::{
-    MetaChallengeEvent, MetaComputeRequestEvent, MetaComputeResultEvent, OpenRankManagerInstance,
-};
-use alloy::eips::BlockNumberOrTag;
-use alloy::hex::{self, ToHexExt};
-use alloy::primitives::FixedBytes;
+use alloy::primitives::{Address, FixedBytes, Uint, U256};
 use alloy::providers::Provider;
-use alloy::rpc::types::Log;
-use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::Client;
-use csv::StringRecord;
-use futures_util::StreamExt;
+use alloy::rpc::types::{Log, TransactionReceipt};
+use alloy::sol_types::SolEvent;
+use openrank_common::blob::{content_hash, get_verified, BlobKey, BlobStore, DatasetCache};
+use openrank_common::chain::contract::IOpenRankManager::{
+    MetaChallengeEvent, MetaComputeRequestEvent, MetaComputeResultEvent,
+};
+use openrank_common::chain::{
+    decode_log, Error as ChainError, EventIngestor, IngestConfig, IngestStatus, LogSource,
+    ManagerError, OpenRankClient,
+};
+use openrank_common::io::compression::{compress, Compression};
+use openrank_common::io::{stream_scores_file, stream_trust_file, write_scores_bytes, EntryReader};
+use openrank_common::jobs::{unix_now, Error as JobStoreError, Job, JobState, JobStore};
+use openrank_common::manifest::{JobDescription, JobManifest, JobResult, ResultManifest};
 use openrank_common::merkle::fixed::DenseMerkleTree;
 use openrank_common::merkle::Hash;
-use openrank_common::runners::compute_runner::{self, ComputeRunner};
-use openrank_common::tx::trust::{ScoreEntry, TrustEntry};
+use openrank_common::metrics;
+use openrank_common::runners::compute_runner::{self, BatchConfig, ComputeRunner};
+use openrank_common::tx::canonical::scores_id;
+use openrank_common::tx::trust::ScoreEntry;
 use openrank_common::Domain;
-use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
-use sha3::{Digest, Keccak256};
+use sha3::Keccak256;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Write;
-use std::time::Instant;
-use tokio::fs::create_dir_all;
-use tokio::select;
-use tracing::{debug, info};
-
-#[derive(Serialize, Deserialize, Clone)]
-struct JobDescription {
-    alpha: f32,
-    trust_id: String,
-    seed_id: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct JobResult {
-    scores_id: String,
-    commitment: String,
-}
-
-impl JobResult {
-    pub fn new(scores_id: String, commitment: String) -> Self {
-        Self {
-            scores_id,
-            commitment,
-        }
-    }
-}
-
-pub async fn upload_meta<T: Serialize>(
-    client: &Client,
-    bucket_name: &str,
-    meta: T,
-) -> Result<String, NodeError> {
-    let mut bytes = serde_json::to_vec(&meta).map_err(NodeError::SerdeError)?;
-    let body = ByteStream::from(bytes.clone());
-
-    let mut hasher = Keccak256::new();
-    hasher.write_all(&mut bytes).unwrap();
-    let hash = hasher.finalize().to_vec();
-    client
-        .put_object()
-        .bucket(bucket_name)
-        .key(format!("meta/{}", hex::encode(hash.clone())))
-        .body(body)
-        .send()
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::block_in_place;
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+/// Delay before reading the events again after an RPC error.
+const EVENTS_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Delay between two looks for a claimable job, when no job was added or released meanwhile.
+const JOBS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Uploads the result manifest, returning its content id.
+pub async fn upload_results(
+    store: &dyn BlobStore,
+    manifest: &ResultManifest,
+) -> Result<Hash, NodeError> {
+    let bytes = manifest.to_jsonl().map_err(NodeError::ManifestError)?;
+    let hash = content_hash(&bytes);
+    store
+        .put(&BlobKey::meta(hash.clone().to_hex()), bytes)
         .await
-        .map_err(|e| NodeError::AwsError(e.into()))?;
-    Ok(hex::encode(hash))
+        .map_err(NodeError::BlobStoreError)?;
+    Ok(hash)
 }
 
-pub async fn download_meta<T: DeserializeOwned>(
-    client: &Client,
-    bucket_name: &str,
+/// Downloads the job manifest, checking its content id and version.
+pub async fn download_job(
+    store: &dyn BlobStore,
     meta_id: String,
-) -> Result<T, NodeError> {
-    let res = client
-        .get_object()
-        .bucket(bucket_name)
-        .key(format!("meta/{}", meta_id))
-        .send()
+) -> Result<JobManifest, NodeError> {
+    let res_bytes = get_verified(store, &BlobKey::meta(meta_id))
         .await
-        .map_err(|e| NodeError::AwsError(e.into()))?;
-    let res_bytes = res
-        .body
-        .collect()
-        .await
-        .map_err(NodeError::ByteStreamError)?;
-    let meta: T =
-        serde_json::from_slice(res_bytes.to_vec().as_slice()).map_err(NodeError::SerdeError)?;
-    Ok(meta)
+        .map_err(NodeError::BlobStoreError)?;
+    JobManifest::from_jsonl(&res_bytes).map_err(NodeError::ManifestError)
 }
 
-async fn handle_meta_compute_request<PH: Provider>(
-    contract: &OpenRankManagerInstance<(), PH>,
-    s3_client: &Client,
-    bucket_name: &str,
-    meta_compute_req: MetaComputeRequestEvent,
-    log: Log,
-) -> Result<(), NodeError> {
-    let start = Instant::now();
-    let meta_job: Vec<JobDescription> = download_meta(
-        s3_client,
-        bucket_name,
-        meta_compute_req.jobDescriptionId.encode_hex(),
-    )
-    .await?;
+/// Downloads the datasets of the meta job, computes the scores and uploads them along with
+/// the result manifest. Returns the result manifest id and the meta commitment.
+async fn compute_meta_job(
+    store: &dyn BlobStore,
+    cache: &DatasetCache,
+    batch_config: &BatchConfig,
+    compression: Compression,
+    jobs: &JobStore,
+    job: &mut Job,
+) -> Result<(Hash, Hash), NodeError> {
+    jobs.transition(job, JobState::Downloading)
+        .map_err(NodeError::JobStoreError)?;
+    let download_start = Instant::now();
+    let manifest = download_job(store, job.job_description_id().clone().to_hex()).await?;
     info!(
-        "MetaComputeRequestEvent: JobDescriptionId({})",
-        meta_compute_req.jobDescriptionId
+        "Job: ComputeId({}), JobDescriptionId({}), Label({})",
+        job.compute_id(),
+        job.job_description_id(),
+        manifest.label().as_deref().unwrap_or("-")
     );
-    debug!("Log: {:?}", log);
+    manifest
+        .algorithm()
+        .check_supported()
+        .map_err(NodeError::ManifestError)?;
+    let meta_job = manifest.jobs();
 
     let mut job_results = Vec::new();
     let mut commitments = Vec::new();
-    for compute_req in meta_job.clone() {
+    let domains = manifest.domains().map_err(NodeError::ManifestError)?;
+    // Datasets are only downloaded to the cache here, and loaded in memory when computed.
+    let mut files = Vec::new();
+    for compute_req in meta_job {
         info!(
             "SubJob: TrustId({}), SeedId({})",
-            compute_req.trust_id, compute_req.seed_id
+            compute_req.trust_id(),
+            compute_req.seed_id()
         );
-
-        create_dir_all(&format!("./trust/")).await.unwrap();
-        create_dir_all(&format!("./seed/")).await.unwrap();
-        let mut trust_file = File::create(&format!("./trust/{}", compute_req.trust_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to create file: {e:}")))?;
-        let mut seed_file = File::create(&format!("./seed/{}", compute_req.seed_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to create file: {e:}")))?;
+        let trust_key = BlobKey::trust(compute_req.trust_id().clone().to_hex());
+        let seed_key = BlobKey::seed(compute_req.seed_id().clone().to_hex());
 
         info!("Downloading data...");
-        let mut trust_res = s3_client
-            .get_object()
-            .bucket(bucket_name)
-            .key(format!("trust/{}", compute_req.trust_id))
-            .send()
-            .await
-            .map_err(|e| NodeError::AwsError(e.into()))?;
-        let mut seed_res = s3_client
-            .get_object()
-            .bucket(bucket_name)
-            .key(format!("seed/{}", compute_req.seed_id))
-            .send()
-            .await
-            .map_err(|e| NodeError::AwsError(e.into()))?;
-
-        while let Some(bytes) = trust_res.body.next().await {
-            trust_file
-                .write(&bytes.unwrap())
-                .map_err(|e| NodeError::FileError(format!("Failed to write to file: {e:}")))?;
-        }
-
-        while let Some(bytes) = seed_res.body.next().await {
-            seed_file
-                .write(&bytes.unwrap())
-                .map_err(|e| NodeError::FileError(format!("Failed to write to file: {e:}")))?;
-        }
+        let trust_file = open_cached(store, cache, &trust_key)?;
+        let seed_file = open_cached(store, cache, &seed_key)?;
+        files.push(((trust_key, trust_file), (seed_key, seed_file)));
     }
 
-    for compute_req in meta_job {
-        let trust_file = File::open(&format!("./trust/{}", compute_req.trust_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to open file: {e:}")))?;
-        let seed_file = File::open(&format!("./seed/{}", compute_req.seed_id))
-            .map_err(|e| NodeError::FileError(format!("Failed to open file: {e:}")))?;
-
-        let mut trust_rdr = csv::Reader::from_reader(trust_file);
-        let mut seed_rdr = csv::Reader::from_reader(seed_file);
-
-        let mut trust_entries = Vec::new();
-        for result in trust_rdr.records() {
-            let record: StringRecord = result.map_err(NodeError::CsvError)?;
-            let (from, to, value): (String, String, f32) =
-                record.deserialize(None).map_err(NodeError::CsvError)?;
-            let trust_entry = TrustEntry::new(from, to, value);
-            trust_entries.push(trust_entry);
-        }
-
-        let mut seed_entries = Vec::new();
-        for result in seed_rdr.records() {
-            let record: StringRecord = result.map_err(NodeError::CsvError)?;
-            let (id, value): (String, f32) =
-                record.deserialize(None).map_err(NodeError::CsvError)?;
-            let trust_entry = ScoreEntry::new(id, value);
-            seed_entries.push(trust_entry);
+    metrics::observe_since(&metrics::STAGE_DURATION, &["download"], download_start);
+
+    jobs.transition(job, JobState::Computing)
+        .map_err(NodeError::JobStoreError)?;
+    info!("Starting core compute...");
+    let compute_start = Instant::now();
+    let mut runner = ComputeRunner::new(&domains);
+    runner
+        .set_batch_config(batch_config.clone())
+        .map_err(NodeError::ComputeRunnerError)?;
+    let budget = batch_config.memory_budget_bytes().unwrap_or(u64::MAX);
+    let mut sub_job_results = Vec::new();
+    let mut wave = Vec::new();
+    let mut wave_bytes = 0u64;
+    for (i, (compute_req, (trust, seed))) in meta_job.iter().zip(files).enumerate() {
+        let domain = &domains[i];
+        // Parsing and hashing the datasets is CPU bound, like the compute itself.
+        block_in_place(|| load_sub_job(cache, &mut runner, domain, compute_req, trust, seed))?;
+        wave_bytes += runner.estimated_bytes(domain);
+        wave.push(domain.clone());
+        // The wave is computed and dropped as soon as it fills the memory budget, so that the
+        // loaded data never exceeds the budget by more than one sub-job.
+        if wave_bytes >= budget || i + 1 == meta_job.len() {
+            let first = i + 1 - wave.len();
+            sub_job_results.extend(compute_wave(&mut runner, first, &wave)?);
+            wave.clear();
+            wave_bytes = 0;
         }
+    }
+    info!(
+        "Core compute completed: Time({:?})",
+        compute_start.elapsed()
+    );
 
-        info!("Starting core compute...");
-        let mock_domain = Domain::default();
-        let mut runner = ComputeRunner::new(&[mock_domain.clone()]);
-        runner
-            .update_trust_map(mock_domain.clone(), trust_entries.to_vec())
-            .map_err(NodeError::ComputeRunnerError)?;
-        runner
-            .update_seed_map(mock_domain.clone(), seed_entries.to_vec())
-            .map_err(NodeError::ComputeRunnerError)?;
-        runner
-            .compute(mock_domain.clone())
-            .map_err(NodeError::ComputeRunnerError)?;
-        let scores = runner
-            .get_compute_scores(mock_domain.clone())
-            .map_err(NodeError::ComputeRunnerError)?;
-        runner
-            .create_compute_tree(mock_domain.clone())
-            .map_err(NodeError::ComputeRunnerError)?;
-        let (_, compute_root) = runner
-            .get_root_hashes(mock_domain.clone())
-            .map_err(NodeError::ComputeRunnerError)?;
-
-        let scores_vec = Vec::new();
-        let mut wtr = csv::Writer::from_writer(scores_vec);
-        wtr.write_record(&["i", "v"]).map_err(NodeError::CsvError)?;
-        for x in scores {
-            wtr.write_record(&[x.id(), x.value().to_string().as_str()])
-                .map_err(NodeError::CsvError)?;
-        }
-        let mut file_bytes = wtr.into_inner().unwrap();
-        let mut hasher = Keccak256::new();
-        hasher.write_all(&mut file_bytes).unwrap();
-        let scores_id = hasher.finalize().to_vec();
+    jobs.transition(job, JobState::Uploading)
+        .map_err(NodeError::JobStoreError)?;
+    let upload_start = Instant::now();
+    for (compute_req, (scores, compute_root)) in meta_job.iter().zip(sub_job_results) {
+        let file_bytes =
+            write_scores_bytes(*compute_req.format(), compute_req.csv_config(), &scores)
+                .map_err(NodeError::DatasetError)?;
+        let scores_id = scores_id(&scores);
 
         let commitment_bytes = FixedBytes::<32>::from_slice(compute_root.inner());
-        let scores_id_bytes = FixedBytes::<32>::from_slice(scores_id.as_slice());
-        let commitment = hex::encode(compute_root.inner());
-        let scores_id = hex::encode(scores_id.clone());
-        let job_result = JobResult::new(scores_id.clone(), commitment);
+        let scores_id_bytes = FixedBytes::<32>::from_slice(scores_id.inner());
+        let job_result = JobResult::new(scores_id.clone(), compute_root.clone());
 
         info!(
-            "Core compute completed: ScoresId({:#}), Commitment({:#})",
+            "SubJob result: ScoresId({:#}), Commitment({:#})",
             scores_id_bytes, commitment_bytes
         );
         info!("Uploading scores data...");
 
-        let body = ByteStream::from(file_bytes);
-        s3_client
-            .put_object()
-            .bucket(bucket_name)
-            .key(format!("scores/{}", scores_id))
-            .body(body)
-            .send()
+        let body = compress(&file_bytes, compression).map_err(NodeError::DatasetError)?;
+        store
+            .put(&BlobKey::scores(scores_id.to_hex()), body)
             .await
-            .map_err(|e| NodeError::AwsError(e.into()))?;
+            .map_err(NodeError::BlobStoreError)?;
 
         info!("Upload scores complete...");
 
         job_results.push(job_result);
-        commitments.push(Hash::from_slice(commitment_bytes.as_slice()));
+        commitments.push(compute_root);
     }
 
     let commitment_tree = DenseMerkleTree::<Keccak256>::new(commitments)
@@ -252,29 +179,243 @@ async fn handle_meta_compute_request<PH: Provider>(
         .root()
         .map_err(|e| NodeError::ComputeRunnerError(compute_runner::Error::Merkle(e)))?;
 
-    let meta_id = upload_meta(&s3_client, bucket_name, job_results).await?;
+    let mut results = ResultManifest::new(job_results).with_created_at(unix_now());
+    if let Some(label) = manifest.label() {
+        results = results.with_label(label.clone());
+    }
+    let meta_id = upload_results(store, &results).await?;
+    metrics::observe_since(&metrics::STAGE_DURATION, &["upload"], upload_start);
+
+    Ok((meta_id, meta_commitment))
+}
+
+/// Opens a dataset through the cache. The download is written to disk synchronously, so it runs
+/// outside of the async worker threads.
+fn open_cached(
+    store: &dyn BlobStore,
+    cache: &DatasetCache,
+    key: &BlobKey,
+) -> Result<File, NodeError> {
+    block_in_place(|| Handle::current().block_on(cache.open(store, key)))
+        .map_err(NodeError::BlobStoreError)
+}
+
+/// Streams the cached datasets of a sub-job into the runner, checking their content ids.
+fn load_sub_job(
+    cache: &DatasetCache,
+    runner: &mut ComputeRunner,
+    domain: &Domain,
+    compute_req: &JobDescription,
+    (trust_key, trust_file): (BlobKey, File),
+    (seed_key, seed_file): (BlobKey, File),
+) -> Result<(), NodeError> {
+    // Entries are streamed into the runner without collecting them, and the content ids are
+    // computed from the loaded data.
+    let (format, csv_config) = (*compute_req.format(), compute_req.csv_config());
+    let trust_entries =
+        stream_trust_file(format, csv_config, trust_file).map_err(NodeError::DatasetError)?;
+    let mut trust_reader = EntryReader::new(trust_entries);
+    runner
+        .update_trust_map(domain.clone(), &mut trust_reader)
+        .map_err(NodeError::ComputeRunnerError)?;
+    trust_reader.finish().map_err(NodeError::DatasetError)?;
+    let actual_trust_id = runner
+        .trust_id(domain)
+        .map_err(NodeError::ComputeRunnerError)?;
+    cache
+        .verify(&trust_key, &actual_trust_id)
+        .map_err(NodeError::BlobStoreError)?;
+
+    let seed_entries =
+        stream_scores_file(format, csv_config, seed_file).map_err(NodeError::DatasetError)?;
+    let mut seed_reader = EntryReader::new(seed_entries);
+    runner
+        .update_seed_map(domain.clone(), &mut seed_reader)
+        .map_err(NodeError::ComputeRunnerError)?;
+    seed_reader.finish().map_err(NodeError::DatasetError)?;
+    let actual_seed_id = runner
+        .seed_id(domain)
+        .map_err(NodeError::ComputeRunnerError)?;
+    cache
+        .verify(&seed_key, &actual_seed_id)
+        .map_err(NodeError::BlobStoreError)?;
+
+    // Canonical order, so that peer indices and commitments don't depend on the row order.
+    runner
+        .sort_indices(domain.clone())
+        .map_err(NodeError::ComputeRunnerError)
+}
+
+/// Computes the loaded domains of consecutive sub-jobs, starting at `first`, and drops their
+/// trust and seed data. Returns the scores and the compute root of each domain.
+fn compute_wave(
+    runner: &mut ComputeRunner,
+    first: usize,
+    domains: &[Domain],
+) -> Result<Vec<(Vec<ScoreEntry>, Hash)>, NodeError> {
+    // The compute is CPU bound, so it shouldn't hold up the other tasks of this thread.
+    let report =
+        block_in_place(|| runner.compute_all(domains)).map_err(NodeError::ComputeRunnerError)?;
+    let mut results = Vec::new();
+    for (i, (domain, domain_report)) in domains.iter().zip(report.domains()).enumerate() {
+        info!(
+            "SubJob({}) compute completed: NumScores({}), Time({:?})",
+            first + i,
+            domain_report.num_scores(),
+            domain_report.duration()
+        );
+        let scores = runner
+            .get_compute_scores(domain.clone())
+            .map_err(NodeError::ComputeRunnerError)?;
+        runner.release_inputs(domain);
+        results.push((scores, domain_report.compute_root().clone()));
+    }
+    Ok(results)
+}
+
+/// Checks that this computer is allowlisted, and returns the computer that submitted results
+/// for the request, if any.
+async fn preflight<PH: Provider>(
+    client: &OpenRankClient<PH>,
+    computer: Address,
+    compute_id: U256,
+) -> Result<Option<Address>, NodeError> {
+    let allowlisted = client
+        .is_allowlisted_computer(computer)
+        .await
+        .map_err(NodeError::ChainError)?;
+    if !allowlisted {
+        return Err(NodeError::ChainError(ChainError::Revert(
+            ManagerError::CallerNotWhitelisted,
+        )));
+    }
+    let result = client
+        .meta_compute_result(compute_id)
+        .await
+        .map_err(NodeError::ChainError)?;
+    Ok(result.map(|x| x.computer))
+}
+
+/// Runs the pre-flight checks, and completes the job if results were submitted for it
+/// already. Returns `true` if there is nothing left to do.
+async fn check_submitted<PH: Provider>(
+    client: &OpenRankClient<PH>,
+    computer: Address,
+    jobs: &JobStore,
+    job: &mut Job,
+) -> Result<bool, NodeError> {
+    match preflight(client, computer, *job.compute_id()).await? {
+        None => Ok(false),
+        Some(submitter) if submitter == computer => {
+            info!(
+                "Results already submitted by this computer: ComputeId({})",
+                job.compute_id()
+            );
+            jobs.transition(job, JobState::Confirmed)
+                .map_err(NodeError::JobStoreError)?;
+            Ok(true)
+        }
+        Some(submitter) => {
+            let reason = format!("Results already submitted by {:#}", submitter);
+            jobs.fail(job, &reason).map_err(NodeError::JobStoreError)?;
+            Ok(true)
+        }
+    }
+}
 
-    let meta_commitment_bytes = FixedBytes::from_slice(meta_commitment.inner());
-    let meta_id_bytes = FixedBytes::from_slice(hex::decode(meta_id).unwrap().as_slice());
+/// Sends the results of the job, and waits for the transaction to be included. The call is
+/// simulated first, so that a transaction bound to revert is not sent.
+async fn submit_results<PH: Provider>(
+    client: &OpenRankClient<PH>,
+    jobs: &JobStore,
+    job: &mut Job,
+) -> Result<(), NodeError> {
+    let (Some(results_id), Some(commitment)) = (job.results_id(), job.commitment()) else {
+        return Err(NodeError::TxError(
+            "Job has no results to submit".to_string(),
+        ));
+    };
 
     info!("Posting commitment on-chain. Calling: 'submitMetaComputeResult'");
-    let res = contract
-        .submitMetaComputeResult(
-            meta_compute_req.computeId,
-            meta_commitment_bytes,
-            meta_id_bytes,
-        )
-        .send()
+    let tx_hash = client
+        .send_meta_compute_result(*job.compute_id(), commitment, results_id)
         .await
-        .map_err(|e| NodeError::TxError(format!("{e:}")))?;
-    let tx_hash = res
-        .watch()
+        .map_err(NodeError::ChainError)?;
+    job.set_tx_hash(tx_hash);
+    jobs.transition(job, JobState::Submitted)
+        .map_err(NodeError::JobStoreError)?;
+    let receipt = client
+        .wait_for_receipt(tx_hash)
         .await
-        .map_err(|e| NodeError::TxError(format!("{e:}")))?;
+        .map_err(NodeError::ChainError)?;
+    confirm_results(jobs, job, &receipt)
+}
+
+/// Completes the job once its result transaction was included successfully.
+fn confirm_results(
+    jobs: &JobStore,
+    job: &mut Job,
+    receipt: &TransactionReceipt,
+) -> Result<(), NodeError> {
+    if !receipt.status() {
+        return Err(NodeError::TxError(format!(
+            "'submitMetaComputeResult' reverted: Tx Hash({:#})",
+            receipt.transaction_hash
+        )));
+    }
     info!(
         "'submitMetaComputeResult' completed: Tx Hash({:#})",
-        tx_hash
+        receipt.transaction_hash
     );
+    jobs.transition(job, JobState::Confirmed)
+        .map_err(NodeError::JobStoreError)
+}
+
+/// Takes the job from its current state to the confirmation of its results.
+async fn process_job<PH: Provider>(
+    client: &OpenRankClient<PH>,
+    computer: Address,
+    store: &dyn BlobStore,
+    cache: &DatasetCache,
+    batch_config: &BatchConfig,
+    compression: Compression,
+    jobs: &JobStore,
+    job: &mut Job,
+) -> Result<(), NodeError> {
+    let start = Instant::now();
+    if job.state() == &JobState::Submitted {
+        // The transaction sent by a previous attempt might have been included since.
+        if let Some(tx_hash) = job.tx_hash().filter(|x| client.is_pending(x)) {
+            match client.wait_for_receipt(tx_hash).await {
+                Ok(receipt) => return confirm_results(jobs, job, &receipt),
+                // Its nonce was taken by another transaction, so the results are sent again.
+                Err(ChainError::Dropped(_)) => {}
+                Err(e) => return Err(NodeError::ChainError(e)),
+            }
+        } else if let Some(tx_hash) = job.tx_hash() {
+            let receipt = client
+                .provider()
+                .get_transaction_receipt(*tx_hash)
+                .await
+                .map_err(|e| NodeError::TxError(e.to_string()))?;
+            if let Some(receipt) = receipt.filter(|x| x.status()) {
+                return confirm_results(jobs, job, &receipt);
+            }
+        }
+    } else {
+        // No compute time is spent on a request that already has results.
+        if check_submitted(client, computer, jobs, job).await? {
+            return Ok(());
+        }
+        let (results_id, commitment) =
+            compute_meta_job(store, cache, batch_config, compression, jobs, job).await?;
+        job.set_results(results_id, commitment);
+    }
+    // Results might have been submitted by another computer in the meantime.
+    if check_submitted(client, computer, jobs, job).await? {
+        return Ok(());
+    }
+    submit_results(client, jobs, job).await?;
 
     let elapsed = start.elapsed();
     info!("Total compute time: {:?}", elapsed);
@@ -282,72 +423,229 @@ async fn handle_meta_compute_request<PH: Provider>(
     Ok(())
 }
 
-pub async fn run<PH: Provider, PW: Provider>(
-    contract: OpenRankManagerInstance<(), PH>,
-    contract_ws: OpenRankManagerInstance<(), PW>,
-    s3_client: Client,
-    bucket_name: &str,
+/// Processes the jobs of the store, one at a time, until the node stops.
+async fn run_worker<PH: Provider>(
+    id: usize,
+    client: OpenRankClient<PH>,
+    computer: Address,
+    store: Arc<dyn BlobStore>,
+    cache: Arc<DatasetCache>,
+    jobs: Arc<JobStore>,
+    batch_config: BatchConfig,
+    compression: Compression,
 ) {
-    // Metaed jobs events
-    let meta_compute_request_filter = contract_ws
-        .MetaComputeRequestEvent_filter()
-        .watch()
-        .await
-        .unwrap();
-    let meta_compute_result_filter = contract_ws
-        .MetaComputeResultEvent_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
-        .await
-        .unwrap();
-    let meta_challenge_filter = contract_ws
-        .MetaChallengeEvent_filter()
-        .from_block(BlockNumberOrTag::Latest)
-        .watch()
-        .await
-        .unwrap();
+    loop {
+        let mut job = match jobs.claim(unix_now()) {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                jobs.wait(JOBS_POLL_INTERVAL).await;
+                continue;
+            }
+            Err(e) => {
+                error!("Failed to claim a job: {}", e);
+                sleep(JOBS_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        let compute_id = *job.compute_id();
+        info!(
+            "Worker({}) claimed job: ComputeId({}), State({}), Attempts({})",
+            id,
+            compute_id,
+            job.state(),
+            job.attempts()
+        );
+        let res = process_job(
+            &client,
+            computer,
+            store.as_ref(),
+            &cache,
+            &batch_config,
+            compression,
+            &jobs,
+            &mut job,
+        )
+        .await;
+        let res = match res {
+            Ok(()) => Ok(()),
+            // The request is gone, e.g. because it was reorged out, so retrying is pointless.
+            Err(NodeError::ChainError(ChainError::Revert(
+                e @ ManagerError::ComputeRequestNotFound,
+            ))) => jobs.fail(&mut job, &e.to_string()),
+            Err(NodeError::ChainError(e @ ChainError::Reverted(_))) => {
+                jobs.fail(&mut job, &e.to_string())
+            }
+            // The job belongs to another request now, which is worked on separately.
+            Err(NodeError::JobStoreError(e @ JobStoreError::Replaced(_))) => {
+                info!("Job attempt stopped: {}", e);
+                Ok(())
+            }
+            Err(e) => jobs.record_failure(&mut job, &e.to_string(), unix_now()),
+        };
+        if let Err(e) = res {
+            error!("Failed to record the job outcome: {}", e);
+        }
+        jobs.release(&compute_id);
+    }
+}
 
-    // Meta jobs event streams
-    let mut meta_compute_request_stream = meta_compute_request_filter.into_stream();
-    let mut meta_compute_result_stream = meta_compute_result_filter.into_stream();
-    let mut meta_challenge_stream = meta_challenge_filter.into_stream();
+/// Undoes the effects of a processed log that was removed by a chain reorg.
+fn handle_removed_log(
+    log: &Log,
+    jobs: &JobStore,
+    meta_compute_result_map: &mut HashMap<Uint<256, 4>, Log>,
+) {
+    let topic = log.topic0().copied();
+    if topic == Some(MetaComputeRequestEvent::SIGNATURE_HASH) {
+        if let Ok(compute_req) = decode_log::<MetaComputeRequestEvent>(log) {
+            match jobs.cancel(&compute_req.computeId, "Request removed by a reorg") {
+                Ok(true) => info!(
+                    "MetaComputeRequestEvent removed by a reorg, job cancelled: ComputeId({})",
+                    compute_req.computeId
+                ),
+                Ok(false) => warn!(
+                    "MetaComputeRequestEvent removed by a reorg, results were already submitted: ComputeId({})",
+                    compute_req.computeId
+                ),
+                Err(e) => error!("Failed to cancel the job: {}", e),
+            }
+        }
+    } else if topic == Some(MetaComputeResultEvent::SIGNATURE_HASH) {
+        if let Ok(meta_compute_res) = decode_log::<MetaComputeResultEvent>(log) {
+            info!(
+                "MetaComputeResultEvent removed by a reorg: ComputeId({})",
+                meta_compute_res.computeId
+            );
+            meta_compute_result_map.remove(&meta_compute_res.computeId);
+        }
+    } else if topic == Some(MetaChallengeEvent::SIGNATURE_HASH) {
+        if let Ok(meta_challenge) = decode_log::<MetaChallengeEvent>(log) {
+            info!(
+                "MetaChallengeEvent removed by a reorg: ComputeId({:#})",
+                meta_challenge.computeId
+            );
+        }
+    }
+}
+
+/// Runs the computer node. Logs are read from the source, whose connection state and progress
+/// are reported to the status.
+pub async fn run<PH: Provider + Clone + 'static, S: LogSource>(
+    client: OpenRankClient<PH>,
+    computer: Address,
+    source: S,
+    status: Arc<IngestStatus>,
+    store: Arc<dyn BlobStore>,
+    cache: Arc<DatasetCache>,
+    jobs: Arc<JobStore>,
+    batch_config: BatchConfig,
+    compression: Compression,
+    ingest_config: IngestConfig,
+) {
+    // Requests are queued by the event loop below, and processed by the workers.
+    for id in 0..*jobs.config().workers() {
+        tokio::spawn(run_worker(
+            id,
+            client.clone(),
+            computer,
+            store.clone(),
+            cache.clone(),
+            jobs.clone(),
+            batch_config.clone(),
+            compression,
+        ));
+    }
+
+    // Meta jobs events, read from a single log stream so that they stay in chain order.
+    let filter = client.event_filter();
+    let cursor_store = ingest_config.cursor_store("computer");
+    let mut events = EventIngestor::start(source, filter, ingest_config, cursor_store)
+        .await
+        .expect("Failed to start the event ingestion.")
+        .with_status(status);
 
     let mut meta_compute_result_map = HashMap::new();
     info!("Running the computer node...");
 
     loop {
-        select! {
-            meta_compute_request_event = meta_compute_request_stream.next() => {
-                if let Some(res) = meta_compute_request_event {
-                    let (compute_req, log): (MetaComputeRequestEvent, Log) = res.unwrap();
-                    handle_meta_compute_request(
-                        &contract,
-                        &s3_client,
-                        bucket_name,
-                        compute_req,
-                        log
-                    ).await.unwrap();
-                }
+        let log = match events.next().await {
+            Ok(log) => log,
+            Err(ChainError::SubscriptionClosed) => {
+                error!("Log subscription closed for good, stopping the computer node.");
+                return;
             }
-            meta_compute_result_event = meta_compute_result_stream.next() => {
-                if let Some(res) = meta_compute_result_event {
-                    let (meta_compute_res, log): (MetaComputeResultEvent, Log) = res.unwrap();
-                    info!(
-                        "MetaComputeResultEvent: ComputeId({}), Commitment({:#}), ResultsId({:#})",
-                        meta_compute_res.computeId, meta_compute_res.commitment, meta_compute_res.resultsId
-                    );
-                    debug!("Log: {:?}", log);
-
-                    meta_compute_result_map.insert(meta_compute_res.computeId, log);
-                }
+            Err(e) => {
+                error!("Failed to read events: {}", e);
+                sleep(EVENTS_RETRY_DELAY).await;
+                continue;
             }
-            meta_challenge_event = meta_challenge_stream.next() => {
-                if let Some(res) = meta_challenge_event {
-                    let (meta_challenge, log): (MetaChallengeEvent, Log) = res.unwrap();
-                    info!("MetaChallengeEvent: ComputeId({:#})", meta_challenge.computeId);
-                    debug!("{:?}", log);
+        };
+        if log.removed {
+            handle_removed_log(&log, &jobs, &mut meta_compute_result_map);
+            continue;
+        }
+        let topic = log.topic0().copied();
+        if topic == Some(MetaComputeRequestEvent::SIGNATURE_HASH) {
+            let compute_req = match decode_log::<MetaComputeRequestEvent>(&log) {
+                Ok(compute_req) => compute_req,
+                Err(e) => {
+                    error!("Failed to decode MetaComputeRequestEvent: {}", e);
+                    continue;
+                }
+            };
+            info!(
+                "MetaComputeRequestEvent: ComputeId({}), JobDescriptionId({:#})",
+                compute_req.computeId, compute_req.jobDescriptionId
+            );
+            debug!("Log: {:?}", log);
+
+            let job = Job::new(
+                compute_req.computeId,
+                Hash::from_slice(compute_req.jobDescriptionId.as_slice()),
+                log.block_number.unwrap_or_default(),
+            );
+            // The log counts as processed once the job is stored, so it is retried until then.
+            loop {
+                match jobs.insert(&job) {
+                    Ok(true) => break,
+                    Ok(false) => {
+                        debug!("Job already queued: ComputeId({})", compute_req.computeId);
+                        break;
+                    }
+                    Err(e) => {
+                        error!("Failed to queue the job: {}", e);
+                        sleep(EVENTS_RETRY_DELAY).await;
+                    }
                 }
             }
+        } else if topic == Some(MetaComputeResultEvent::SIGNATURE_HASH) {
+            let meta_compute_res = match decode_log::<MetaComputeResultEvent>(&log) {
+                Ok(meta_compute_res) => meta_compute_res,
+                Err(e) => {
+                    error!("Failed to decode MetaComputeResultEvent: {}", e);
+                    continue;
+                }
+            };
+            info!(
+                "MetaComputeResultEvent: ComputeId({}), Commitment({:#}), ResultsId({:#})",
+                meta_compute_res.computeId, meta_compute_res.commitment, meta_compute_res.resultsId
+            );
+            debug!("Log: {:?}", log);
+
+            meta_compute_result_map.insert(meta_compute_res.computeId, log);
+        } else if topic == Some(MetaChallengeEvent::SIGNATURE_HASH) {
+            let meta_challenge = match decode_log::<MetaChallengeEvent>(&log) {
+                Ok(meta_challenge) => meta_challenge,
+                Err(e) => {
+                    error!("Failed to decode MetaChallengeEvent: {}", e);
+                    continue;
+                }
+            };
+            info!(
+                "MetaChallengeEvent: ComputeId({:#})",
+                meta_challenge.computeId
+            );
+            debug!("{:?}", log);
         }
     }
 }
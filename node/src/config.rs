@@ -0,0 +1,126 @@
+use crate::error::Error as NodeError;
+use alloy::primitives::Address;
+use getset::Getters;
+use openrank_common::blob::{BlobStoreConfig, CacheConfig};
+use openrank_common::chain::{IngestConfig, SignerConfig, TxConfig};
+use openrank_common::config::{ChainConfig, Settings};
+use openrank_common::health::HealthConfig;
+use openrank_common::io::compression::Compression;
+use openrank_common::jobs::JobQueueConfig;
+use openrank_common::runners::compute_runner::BatchConfig;
+use std::net::SocketAddr;
+
+/// Address of the metrics and health endpoints used when none is configured.
+pub const DEFAULT_METRICS_ADDR: &str = "0.0.0.0:9100";
+
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+/// Configuration of the computer and challenger node.
+pub struct NodeConfig {
+    chain: ChainConfig,
+    signer: SignerConfig,
+    wss_url: String,
+    rxp_address: Address,
+    eigenda_url: String,
+    compression: Compression,
+    batch: BatchConfig,
+    blob_store: BlobStoreConfig,
+    cache: CacheConfig,
+    ingest: IngestConfig,
+    jobs: JobQueueConfig,
+    tx: TxConfig,
+    metrics_addr: SocketAddr,
+    health: HealthConfig,
+}
+
+impl NodeConfig {
+    /// Reads and validates the config of the role, `computer` or `challenger`.
+    /// `CHAIN_WSS_URL`, `REEXECUTION_ENDPOINT_ADDRESS` and `EIGEN_DA_PROXY_URL` must be set,
+    /// along with the [ChainConfig] and [SignerConfig] settings. Metrics and health checks are
+    /// served at `METRICS_ADDR`, [DEFAULT_METRICS_ADDR] by default.
+    pub fn from_settings(settings: &Settings, role: &str) -> Result<Self, NodeError> {
+        let chain = ChainConfig::from_settings(settings).map_err(NodeError::ConfigError)?;
+        let signer =
+            SignerConfig::from_settings(settings, Some(role)).map_err(NodeError::ChainError)?;
+        let wss_url = settings
+            .require("CHAIN_WSS_URL")
+            .map_err(NodeError::ConfigError)?;
+        let rxp_address = settings
+            .parse_required("REEXECUTION_ENDPOINT_ADDRESS")
+            .map_err(NodeError::ConfigError)?;
+        let eigenda_url = settings
+            .require("EIGEN_DA_PROXY_URL")
+            .map_err(NodeError::ConfigError)?;
+        let compression = settings
+            .parse("DATA_COMPRESSION")
+            .map_err(NodeError::ConfigError)?
+            .unwrap_or(Compression::Zstd);
+        let num_threads = settings
+            .parse("COMPUTE_NUM_THREADS")
+            .map_err(NodeError::ConfigError)?;
+        let memory_budget_bytes = settings
+            .parse("COMPUTE_MEMORY_BUDGET_BYTES")
+            .map_err(NodeError::ConfigError)?;
+        let metrics_addr = settings
+            .parse("METRICS_ADDR")
+            .map_err(NodeError::ConfigError)?
+            .unwrap_or_else(|| DEFAULT_METRICS_ADDR.parse().unwrap());
+        Ok(Self {
+            chain,
+            signer,
+            wss_url,
+            rxp_address,
+            eigenda_url,
+            compression,
+            batch: BatchConfig::new(num_threads, memory_budget_bytes),
+            blob_store: BlobStoreConfig::from_settings(settings)
+                .map_err(NodeError::BlobStoreError)?,
+            cache: CacheConfig::from_settings(settings).map_err(NodeError::BlobStoreError)?,
+            ingest: IngestConfig::from_settings(settings).map_err(NodeError::ChainError)?,
+            jobs: JobQueueConfig::from_settings(settings).map_err(NodeError::JobStoreError)?,
+            tx: TxConfig::from_settings(settings).map_err(NodeError::ChainError)?,
+            metrics_addr,
+            health: HealthConfig::from_settings(settings).map_err(NodeError::HealthError)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+/// Configuration of the re-execution service.
+pub struct RxpConfig {
+    chain: ChainConfig,
+    signer: SignerConfig,
+    service_port: u16,
+    eigenda_url: String,
+    blob_store: BlobStoreConfig,
+}
+
+impl RxpConfig {
+    /// Reads and validates the config. `SERVICE_PORT` and `EIGEN_DA_PROXY_URL` must be set,
+    /// along with the [ChainConfig] and [SignerConfig] settings, where the signer settings
+    /// prefixed by `RXP_` take precedence. The service also accepts the `ETH_RPC_URL` and
+    /// `DA_PROXY_URL` variables set up by the re-execution deployment. The job and result
+    /// manifests are read from the [BlobStoreConfig] store.
+    pub fn from_settings(settings: &mut Settings) -> Result<Self, NodeError> {
+        settings.alias("CHAIN_RPC_URL", "ETH_RPC_URL");
+        settings.alias("EIGEN_DA_PROXY_URL", "DA_PROXY_URL");
+        let chain = ChainConfig::from_settings(settings).map_err(NodeError::ConfigError)?;
+        let signer =
+            SignerConfig::from_settings(settings, Some("rxp")).map_err(NodeError::ChainError)?;
+        let service_port = settings
+            .parse_required("SERVICE_PORT")
+            .map_err(NodeError::ConfigError)?;
+        let eigenda_url = settings
+            .require("EIGEN_DA_PROXY_URL")
+            .map_err(NodeError::ConfigError)?;
+        Ok(Self {
+            chain,
+            signer,
+            service_port,
+            eigenda_url,
+            blob_store: BlobStoreConfig::from_settings(settings)
+                .map_err(NodeError::BlobStoreError)?,
+        })
+    }
+}
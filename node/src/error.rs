@@ -1,6 +1,12 @@
+use alloy::eips::BlockNumberOrTag;
 use alloy::{hex::FromHexError, signers::local::LocalSignerError, transports::TransportError};
-use aws_sdk_s3::{primitives::ByteStreamError, Error as AwsError};
-use csv::Error as CsvError;
+use openrank_common::blob::Error as BlobStoreError;
+use openrank_common::chain::Error as ChainError;
+use openrank_common::config::Error as ConfigError;
+use openrank_common::health::Error as HealthError;
+use openrank_common::io::Error as DatasetError;
+use openrank_common::jobs::Error as JobStoreError;
+use openrank_common::manifest::Error as ManifestError;
 use openrank_common::runners::compute_runner::Error as ComputeRunnerError;
 use openrank_common::runners::verification_runner::Error as VerificationRunnerError;
 use serde_json::Error as SerdeError;
@@ -15,18 +21,28 @@ pub enum Error {
     HexError(FromHexError),
     #[error("Serde error: {0}")]
     SerdeError(SerdeError),
-    #[error("Aws error: {0}")]
-    AwsError(AwsError),
+    #[error("Blob store error: {0}")]
+    BlobStoreError(BlobStoreError),
     #[error("File error: {0}")]
     FileError(String),
-    #[error("Csv error: {0}")]
-    CsvError(CsvError),
+    #[error("Dataset error: {0}")]
+    DatasetError(DatasetError),
+    #[error("Manifest error: {0}")]
+    ManifestError(ManifestError),
     #[error("ComputeRunnerError: {0}")]
     ComputeRunnerError(ComputeRunnerError),
     #[error("VerificationRunnerError: {0}")]
     VerificationRunnerError(VerificationRunnerError),
+    #[error("Job store error: {0}")]
+    JobStoreError(JobStoreError),
     #[error("Tx Error: {0}")]
     TxError(String),
-    #[error("ByteStreamError: {0}")]
-    ByteStreamError(ByteStreamError),
+    #[error("Block not found: {0}")]
+    BlockNotFound(BlockNumberOrTag),
+    #[error("Chain error: {0}")]
+    ChainError(ChainError),
+    #[error("Config error: {0}")]
+    ConfigError(ConfigError),
+    #[error("Health error: {0}")]
+    HealthError(HealthError),
 }
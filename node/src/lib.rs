@@ -1,4 +1,5 @@
 pub mod challenger;
 pub mod computer;
+pub mod config;
 pub mod error;
 pub mod sol;
@@ -1,161 +1,140 @@
-use crate::BUCKET_NAME;
-use alloy::hex::{self};
-use aws_sdk_s3::{primitives::ByteStream, Client, Error as AwsError};
 use openrank_common::{
+    blob::{content_hash, get_verified, BlobKey, BlobStore, Error as BlobError},
+    io::{
+        compression::{compress_reader, Compression, DecompressWriter},
+        stream_scores_file, stream_trust_file,
+        validate::{ValidationReport, Validator},
+        CsvConfig, DataFormat, HashingReader,
+    },
+    manifest::{JobManifest, ResultManifest},
     merkle::Hash,
     runners::{
         compute_runner::{self, ComputeRunner},
         verification_runner::{self, VerificationRunner},
     },
-    tx::trust::{ScoreEntry, TrustEntry},
+    tx::{
+        canonical::{ScoresIdHasher, TrustIdHasher},
+        trust::{ScoreEntry, TrustEntry},
+    },
     Domain,
 };
-use serde::{de::DeserializeOwned, Serialize};
-use sha3::{Digest, Keccak256};
-use std::{
-    fs::File,
-    io::{Read, Write},
-};
-
-pub async fn upload_trust(client: Client, path: String) -> Result<String, AwsError> {
-    let mut f = File::open(path.clone()).unwrap();
-    let mut file_bytes = Vec::new();
-    f.read_to_end(&mut file_bytes).unwrap();
-    let body = ByteStream::from(file_bytes.clone());
-
-    let mut hasher = Keccak256::new();
-    hasher.write_all(&mut file_bytes).unwrap();
-    let hash = hasher.finalize().to_vec();
-
-    let mut rdr = csv::Reader::from_reader(f);
-    for result in rdr.records() {
-        let record: csv::StringRecord = result.unwrap();
-        let (_, _, _): (String, String, f32) = record.deserialize(None).unwrap();
+use std::fs::File;
+
+/// Checks the trust dataset, and its seed if given, reporting every issue found.
+pub fn validate_datasets(
+    trust_path: &str,
+    seed_path: Option<&str>,
+    csv_config: &CsvConfig,
+) -> ValidationReport {
+    let mut validator = Validator::new().with_csv_config(csv_config.clone());
+    let file = File::open(trust_path).expect("Failed to open the trust dataset.");
+    validator.check_trust_file(DataFormat::from_path(trust_path), file);
+    if let Some(seed_path) = seed_path {
+        let file = File::open(seed_path).expect("Failed to open the seed dataset.");
+        validator.check_seed_file(DataFormat::from_path(seed_path), file);
     }
+    validator.finish()
+}
 
-    println!("Uploading trust data: {}", hex::encode(hash.clone()));
-
-    client
-        .put_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("trust/{}", hex::encode(hash.clone())))
-        .body(body)
-        .send()
+pub async fn upload_trust(
+    store: &dyn BlobStore,
+    path: String,
+    format: DataFormat,
+    csv_config: &CsvConfig,
+    compression: Compression,
+) -> Result<Hash, BlobError> {
+    // The file is read twice, but neither pass keeps the uncompressed data in memory.
+    let entries = stream_trust_file(format, csv_config, File::open(&path).unwrap()).unwrap();
+    let mut reader = HashingReader::new(entries, TrustIdHasher::new());
+    reader.by_ref().for_each(drop);
+    let hash = reader.finish().unwrap();
+    let body = compress_reader(File::open(&path).unwrap(), compression).unwrap();
+
+    println!("Uploading trust data: {}", hash);
+
+    store
+        .put(&BlobKey::trust(hash.clone().to_hex()), body)
         .await?;
 
-    Ok(hex::encode(hash))
+    Ok(hash)
 }
 
-pub async fn upload_seed(client: Client, path: String) -> Result<String, AwsError> {
-    let mut f = File::open(path.clone()).unwrap();
-    let mut file_bytes = Vec::new();
-    f.read_to_end(&mut file_bytes).unwrap();
-    let body = ByteStream::from(file_bytes.clone());
-
-    let mut hasher = Keccak256::new();
-    hasher.write_all(&mut file_bytes).unwrap();
-    let hash = hasher.finalize().to_vec();
-
-    let mut rdr = csv::Reader::from_reader(f);
-    for result in rdr.records() {
-        let record: csv::StringRecord = result.unwrap();
-        let (_, _): (String, f32) = record.deserialize(None).unwrap();
-    }
-
-    println!("Uploading seed data: {}", hex::encode(hash.clone()));
-
-    client
-        .put_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("seed/{}", hex::encode(hash.clone())))
-        .body(body)
-        .send()
+pub async fn upload_seed(
+    store: &dyn BlobStore,
+    path: String,
+    format: DataFormat,
+    csv_config: &CsvConfig,
+    compression: Compression,
+) -> Result<Hash, BlobError> {
+    // The file is read twice, but neither pass keeps the uncompressed data in memory.
+    let entries = stream_scores_file(format, csv_config, File::open(&path).unwrap()).unwrap();
+    let mut reader = HashingReader::new(entries, ScoresIdHasher::new());
+    reader.by_ref().for_each(drop);
+    let hash = reader.finish().unwrap();
+    let body = compress_reader(File::open(&path).unwrap(), compression).unwrap();
+
+    println!("Uploading seed data: {}", hash);
+
+    store
+        .put(&BlobKey::seed(hash.clone().to_hex()), body)
         .await?;
 
-    Ok(hex::encode(hash))
+    Ok(hash)
+}
+
+/// Downloads the object into the file at `path`, decompressing it if needed.
+async fn download_to_file(
+    store: &dyn BlobStore,
+    key: BlobKey,
+    path: String,
+) -> Result<(), BlobError> {
+    let file = File::create(path).unwrap();
+    let mut writer = DecompressWriter::new(file);
+    store.get_into(&key, &mut writer).await?;
+    writer.finish().unwrap();
+    Ok(())
 }
 
 pub async fn _download_trust(
-    client: Client,
+    store: &dyn BlobStore,
     trust_id: String,
     path: String,
-) -> Result<(), AwsError> {
-    let mut file = File::create(path).unwrap();
-    let mut res = client
-        .get_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("trust/{}", trust_id))
-        .send()
-        .await?;
-    while let Some(bytes) = res.body.next().await {
-        file.write(&bytes.unwrap()).unwrap();
-    }
-    Ok(())
+) -> Result<(), BlobError> {
+    download_to_file(store, BlobKey::trust(trust_id), path).await
 }
 
-pub async fn _download_seed(client: Client, seed_id: String, path: String) -> Result<(), AwsError> {
-    let mut file = File::create(path).unwrap();
-    let mut res = client
-        .get_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("seed/{}", seed_id))
-        .send()
-        .await?;
-    while let Some(bytes) = res.body.next().await {
-        file.write(&bytes.unwrap()).unwrap();
-    }
-    Ok(())
+pub async fn _download_seed(
+    store: &dyn BlobStore,
+    seed_id: String,
+    path: String,
+) -> Result<(), BlobError> {
+    download_to_file(store, BlobKey::seed(seed_id), path).await
 }
 
 pub async fn download_scores(
-    client: Client,
+    store: &dyn BlobStore,
     scores_id: String,
     path: String,
-) -> Result<(), AwsError> {
-    let mut file = File::create(path).unwrap();
-    let mut res = client
-        .get_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("scores/{}", scores_id))
-        .send()
-        .await?;
-    println!("{:?}", res);
-    while let Some(bytes) = res.body.next().await {
-        file.write(&bytes.unwrap()).unwrap();
-    }
-    Ok(())
+) -> Result<(), BlobError> {
+    download_to_file(store, BlobKey::scores(scores_id), path).await
 }
 
-pub async fn upload_meta<T: Serialize>(client: Client, meta: T) -> Result<String, AwsError> {
-    let mut bytes = serde_json::to_vec(&meta).unwrap();
-    let body = ByteStream::from(bytes.clone());
-
-    let mut hasher = Keccak256::new();
-    hasher.write_all(&mut bytes).unwrap();
-    let hash = hasher.finalize().to_vec();
-    client
-        .put_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("meta/{}", hex::encode(hash.clone())))
-        .body(body)
-        .send()
+pub async fn upload_job(store: &dyn BlobStore, manifest: &JobManifest) -> Result<Hash, BlobError> {
+    let bytes = manifest.to_jsonl().unwrap();
+    let hash = content_hash(&bytes);
+    store
+        .put(&BlobKey::meta(hash.clone().to_hex()), bytes)
         .await?;
-    Ok(hex::encode(hash))
+    Ok(hash)
 }
 
-pub async fn download_meta<T: DeserializeOwned>(
-    client: Client,
+pub async fn download_results(
+    store: &dyn BlobStore,
     meta_id: String,
-) -> Result<T, AwsError> {
-    let res = client
-        .get_object()
-        .bucket(BUCKET_NAME)
-        .key(format!("meta/{}", meta_id))
-        .send()
-        .await?;
-    let res_bytes = res.body.collect().await.unwrap();
-    let meta: T = serde_json::from_slice(res_bytes.to_vec().as_slice()).unwrap();
-    Ok(meta)
+) -> Result<ResultManifest, BlobError> {
+    let res_bytes = get_verified(store, &BlobKey::meta(meta_id)).await?;
+    let manifest = ResultManifest::from_jsonl(&res_bytes).unwrap();
+    Ok(manifest)
 }
 
 pub async fn compute_local(
@@ -164,8 +143,9 @@ pub async fn compute_local(
 ) -> Result<Vec<ScoreEntry>, compute_runner::Error> {
     let mock_domain = Domain::default();
     let mut runner = ComputeRunner::new(&[mock_domain.clone()]);
-    runner.update_trust(mock_domain.clone(), trust_entries.to_vec())?;
-    runner.update_seed(mock_domain.clone(), seed_entries.to_vec())?;
+    runner.update_trust(mock_domain.clone(), trust_entries.iter().cloned())?;
+    runner.update_seed(mock_domain.clone(), seed_entries.iter().cloned())?;
+    runner.sort_indices(mock_domain.clone())?;
     runner.compute(mock_domain.clone())?;
     let scores = runner.get_compute_scores(mock_domain.clone())?;
     Ok(scores)
@@ -178,8 +158,9 @@ pub async fn verify_local(
 ) -> Result<bool, verification_runner::Error> {
     let mock_domain = Domain::default();
     let mut runner = VerificationRunner::new(&[mock_domain.clone()]);
-    runner.update_trust_map(mock_domain.clone(), trust_entries.to_vec())?;
-    runner.update_seed_map(mock_domain.clone(), seed_entries.to_vec())?;
+    runner.update_trust_map(mock_domain.clone(), trust_entries.iter().cloned())?;
+    runner.update_seed_map(mock_domain.clone(), seed_entries.iter().cloned())?;
+    runner.sort_indices(mock_domain.clone())?;
     runner.update_scores(
         mock_domain.clone(),
         Hash::default(),
@@ -0,0 +1,47 @@
+use getset::Getters;
+use openrank_common::blob::{BlobStoreConfig, Error as BlobError};
+use openrank_common::chain::{Error as ChainError, SignerConfig, TxConfig};
+use openrank_common::config::{ChainConfig, Error as ConfigError, Settings};
+use openrank_common::io::CsvConfig;
+
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+/// Configuration of the SDK commands that use the chain, the blob store or EigenDA.
+pub struct SdkConfig {
+    chain: ChainConfig,
+    signer: SignerConfig,
+    eigenda_url: String,
+    blob_store: BlobStoreConfig,
+    tx: TxConfig,
+    csv_config: CsvConfig,
+}
+
+impl SdkConfig {
+    /// Reads and validates the config. `EIGEN_DA_PROXY_URL`, also read from `DA_PROXY_URL`,
+    /// must be set, along with the [ChainConfig] and [SignerConfig] settings, where the signer
+    /// settings prefixed by `SDK_` take precedence.
+    pub fn from_settings(settings: &mut Settings) -> Result<Self, Error> {
+        settings.alias("EIGEN_DA_PROXY_URL", "DA_PROXY_URL");
+        Ok(Self {
+            chain: ChainConfig::from_settings(settings).map_err(Error::Config)?,
+            signer: SignerConfig::from_settings(settings, Some("sdk")).map_err(Error::Chain)?,
+            eigenda_url: settings
+                .require("EIGEN_DA_PROXY_URL")
+                .map_err(Error::Config)?,
+            blob_store: BlobStoreConfig::from_settings(settings).map_err(Error::BlobStore)?,
+            tx: TxConfig::from_settings(settings).map_err(Error::Chain)?,
+            csv_config: CsvConfig::from_settings(settings).map_err(Error::Config)?,
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+/// Errors that can arise while reading the config.
+pub enum Error {
+    #[error("{0}")]
+    Config(ConfigError),
+    #[error("{0}")]
+    BlobStore(BlobError),
+    #[error("{0}")]
+    Chain(ChainError),
+}
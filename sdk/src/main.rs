@@ -1,29 +1,31 @@
 mod actions;
-mod sol;
+mod config;
 
 use actions::{
-    compute_local, download_meta, download_scores, upload_meta, upload_seed, upload_trust,
-    verify_local,
+    compute_local, download_results, download_scores, upload_job, upload_seed, upload_trust,
+    validate_datasets, verify_local,
 };
-use alloy::hex::FromHex;
-use alloy::primitives::{Address, FixedBytes};
 use alloy::providers::ProviderBuilder;
 use alloy::rpc::client::RpcClient;
-use alloy::signers::local::coins_bip39::English;
-use alloy::signers::local::MnemonicBuilder;
-use alloy::transports::http::reqwest::Url;
-use aws_config::from_env;
-use aws_sdk_s3::{Client, Error as AwsError};
 use clap::{Parser, Subcommand};
-use csv::StringRecord;
+use config::SdkConfig;
 use dotenv::dotenv;
+use openrank_common::blob::Error as BlobError;
+use openrank_common::chain::OpenRankClient;
+use openrank_common::config::ConfigArgs;
 use openrank_common::eigenda::EigenDAProxyClient;
-use openrank_common::tx::trust::{ScoreEntry, TrustEntry};
-use serde::{Deserialize, Serialize};
-use sol::OpenRankManager;
+use openrank_common::io::compression::Compression;
+use openrank_common::io::validate::{Severity, ValidationReport};
+use openrank_common::io::{
+    read_scores_file, read_trust_file, write_scores_bytes, CsvConfig, DataFormat,
+};
+use openrank_common::manifest::{AlgorithmParams, JobDescription, JobManifest};
+use openrank_common::Domain;
 use std::collections::HashMap;
 use std::fs::{read_dir, File};
 use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Subcommand)]
 /// The method to call.
@@ -35,6 +37,9 @@ enum Method {
     MetaComputeRequest {
         trust_folder_path: String,
         seed_folder_path: String,
+        /// Label stored in the job manifest.
+        #[arg(long)]
+        label: Option<String>,
     },
     ComputeLocal {
         trust_path: String,
@@ -54,6 +59,11 @@ enum Method {
         path: String,
         certs_path: String,
     },
+    /// Checks a trust dataset, and optionally its seed, and prints graph statistics.
+    Validate {
+        trust_path: String,
+        seed_path: Option<String>,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -61,62 +71,66 @@ enum Method {
 struct Args {
     #[command(subcommand)]
     method: Method,
+    /// Compression applied to uploaded datasets: none, zstd or gzip.
+    #[arg(long, global = true, default_value_t = Compression::Zstd)]
+    compression: Compression,
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
-const BUCKET_NAME: &str = "openrank-data-dev";
-
-#[derive(Serialize, Deserialize)]
-struct JobDescription {
-    alpha: f32,
-    trust_id: String,
-    seed_id: String,
-}
-
-impl JobDescription {
-    pub fn default_with(trust_id: String, seed_id: String) -> Self {
-        Self {
-            alpha: 0.5,
-            trust_id,
-            seed_id,
-        }
+/// Prints the issues and statistics of a validation run.
+fn print_report(report: &ValidationReport) {
+    for issue in report.issues() {
+        println!("{}", issue);
     }
+    println!("Graph: {}", report.stats());
+    println!(
+        "{} error(s), {} warning(s)",
+        report.count(Severity::Error),
+        report.count(Severity::Warning)
+    );
 }
 
-#[derive(Serialize, Deserialize)]
-struct JobResult {
-    scores_id: String,
-    commitment: String,
+/// Validates the datasets before they are uploaded, exiting if they have errors.
+fn ensure_valid(trust_path: &str, seed_path: Option<&str>, csv_config: &CsvConfig) {
+    let report = validate_datasets(trust_path, seed_path, csv_config);
+    if report.has_errors() {
+        print_report(&report);
+        std::process::exit(1);
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), AwsError> {
+async fn main() -> Result<(), BlobError> {
     dotenv().ok();
     let cli = Args::parse();
 
-    let eigen_da_url = std::env::var("DA_PROXY_URL").expect("DA_PROXY_URL must be set.");
-    let rpc_url = std::env::var("CHAIN_RPC_URL").expect("CHAIN_RPC_URL must be set.");
-    let manager_address =
-        std::env::var("OPENRANK_MANAGER_ADDRESS").expect("OPENRANK_MANAGER_ADDRESS must be set.");
-    let mnemonic = std::env::var("MNEMONIC").expect("MNEMONIC must be set.");
-    let config = from_env().region("us-west-2").load().await;
-    let client = Client::new(&config);
-
-    let wallet = MnemonicBuilder::<English>::default()
-        .phrase(mnemonic)
-        .index(0)
-        .unwrap()
-        .build()
-        .unwrap();
-
+    // Settings are only loaded by the methods that need them, so that validation and the local
+    // methods don't need the chain or the blob store.
+    let load_config = || cli.config.load_or_exit(SdkConfig::from_settings);
+    let load_csv_config = || cli.config.load_or_exit(|x| CsvConfig::from_settings(x));
     match cli.method {
+        Method::Validate {
+            trust_path,
+            seed_path,
+        } => {
+            let csv_config = load_csv_config();
+            let report = validate_datasets(&trust_path, seed_path.as_deref(), &csv_config);
+            print_report(&report);
+            if report.has_errors() {
+                std::process::exit(1);
+            }
+        }
         Method::MetaDownloadScores { results_id } => {
-            let job_results: Vec<JobResult> =
-                download_meta(client.clone(), results_id).await.unwrap();
-            for job_result in job_results {
+            let config = load_config();
+            let store = config.blob_store().build().await;
+            let manifest = download_results(store.as_ref(), results_id).await.unwrap();
+            for job_result in manifest.results() {
+                let scores_id = job_result.scores_id().clone().to_hex();
                 download_scores(
-                    client.clone(),
-                    job_result.scores_id.clone(),
-                    format!("./scores/{}", job_result.scores_id),
+                    store.as_ref(),
+                    scores_id.clone(),
+                    format!("./scores/{}", scores_id),
                 )
                 .await
                 .unwrap();
@@ -125,14 +139,35 @@ async fn main() -> Result<(), AwsError> {
         Method::MetaComputeRequest {
             trust_folder_path,
             seed_folder_path,
+            label,
         } => {
+            let config = load_config();
+            let csv_config = config.csv_config();
+            for path in read_dir(&trust_folder_path).unwrap() {
+                let path = path.unwrap().path();
+                let seed_path = Path::new(&seed_folder_path).join(path.file_name().unwrap());
+                ensure_valid(
+                    &path.display().to_string(),
+                    Some(&seed_path.display().to_string()),
+                    csv_config,
+                );
+            }
+
+            let store = config.blob_store().build().await;
+            let wallet = config.signer().build().expect("Failed to load the signer.");
+            let sender = wallet.default_signer().address();
+
             let trust_paths = read_dir(trust_folder_path).unwrap();
             let mut trust_map = HashMap::new();
             for path in trust_paths {
                 let path = path.unwrap().path();
                 let file_name = path.file_name().unwrap().to_str().unwrap();
                 let display = path.display().to_string();
-                let res = upload_trust(client.clone(), display).await.unwrap();
+                let format = DataFormat::from_path(&path);
+                let res =
+                    upload_trust(store.as_ref(), display, format, csv_config, cli.compression)
+                        .await
+                        .unwrap();
                 trust_map.insert(file_name.to_string(), res);
             }
 
@@ -142,80 +177,77 @@ async fn main() -> Result<(), AwsError> {
                 let path = path.unwrap().path();
                 let file_name = path.file_name().unwrap().to_str().unwrap();
                 let display = path.display().to_string();
-                let res = upload_seed(client.clone(), display).await.unwrap();
+                let format = DataFormat::from_path(&path);
+                let res = upload_seed(store.as_ref(), display, format, csv_config, cli.compression)
+                    .await
+                    .unwrap();
                 seed_map.insert(file_name.to_string(), res);
             }
 
+            // Every sub-job is loaded in namespaces of its own, owned by the sender.
             let mut jds = Vec::new();
-            for (trust_file, trust_id) in trust_map {
+            for (i, (trust_file, trust_id)) in trust_map.into_iter().enumerate() {
                 let seed_id = seed_map.get(&trust_file).unwrap();
-                let job_description = JobDescription::default_with(trust_id, seed_id.clone());
-                jds.push(job_description);
+                let format = DataFormat::from_path(&trust_file);
+                let domain = Domain::new(sender, i as u32, sender, i as u32, 0);
+                let jd = JobDescription::new(trust_id, seed_id.clone(), format)
+                    .with_csv_config(csv_config.clone())
+                    .with_domain(domain);
+                jds.push(jd);
             }
 
-            let meta_id = upload_meta(client, jds).await?;
+            let created_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default();
+            let mut manifest =
+                JobManifest::new(AlgorithmParams::default(), jds).with_created_at(created_at);
+            if let Some(label) = label {
+                manifest = manifest.with_label(label);
+            }
+            let meta_id = upload_job(store.as_ref(), &manifest).await?;
 
             let provider = ProviderBuilder::new()
                 .wallet(wallet)
-                .on_client(RpcClient::new_http(Url::parse(&rpc_url).unwrap()));
-            let contract =
-                OpenRankManager::new(Address::from_hex(manager_address).unwrap(), provider);
-
-            let meta_id_bytes = FixedBytes::from_hex(meta_id.clone()).unwrap();
+                .on_client(RpcClient::new_http(config.chain().rpc_url().clone()));
+            let client = OpenRankClient::new(
+                *config.chain().manager_address(),
+                provider,
+                config.tx().clone(),
+            )
+            .with_sender(sender);
 
-            let res = contract
-                .submitMetaComputeRequest(meta_id_bytes)
-                .send()
+            let (compute_id, receipt) = client
+                .submit_meta_compute_request(&meta_id)
                 .await
-                .unwrap();
-            println!("Meta Job ID: {}", meta_id);
-            println!("Tx Hash: {}", res.watch().await.unwrap());
+                .expect("Failed to submit the compute request.");
+            println!("Meta Job ID: {}", meta_id.to_hex());
+            println!("Compute ID: {}", compute_id);
+            println!("Tx Hash: {}", receipt.transaction_hash);
         }
         Method::ComputeLocal {
             trust_path,
             seed_path,
             output_path,
         } => {
+            let csv_config = load_csv_config();
+            let format = DataFormat::from_path(&trust_path);
             let f = File::open(trust_path).unwrap();
-            let mut rdr = csv::Reader::from_reader(f);
-            let mut trust_entries = Vec::new();
-            for result in rdr.records() {
-                let record: StringRecord = result.unwrap();
-                let (from, to, value): (String, String, f32) = record.deserialize(None).unwrap();
-                let trust_entry = TrustEntry::new(from, to, value);
-                trust_entries.push(trust_entry);
-            }
+            let trust_entries = read_trust_file(format, &csv_config, f).unwrap();
 
-            // Read CSV, to get a list of `ScoreEntry`
+            // Read the dataset, to get a list of `ScoreEntry`
+            let format = DataFormat::from_path(&seed_path);
             let f = File::open(seed_path).unwrap();
-            let mut rdr = csv::Reader::from_reader(f);
-            let mut seed_entries = Vec::new();
-            for result in rdr.records() {
-                let record: StringRecord = result.unwrap();
-                let (i, value): (String, f32) = record.deserialize(None).unwrap();
-                let score_entry = ScoreEntry::new(i, value);
-                seed_entries.push(score_entry);
-            }
+            let seed_entries = read_scores_file(format, &csv_config, f).unwrap();
 
             let scores_vec = compute_local(&trust_entries, &seed_entries).await.unwrap();
 
             if let Some(output_path) = output_path {
-                let scores_file = File::create(output_path).unwrap();
-                let mut wtr = csv::Writer::from_writer(scores_file);
-                wtr.write_record(&["i", "v"]).unwrap();
-                for x in scores_vec {
-                    wtr.write_record(&[x.id(), x.value().to_string().as_str()])
-                        .unwrap();
-                }
+                let format = DataFormat::from_path(&output_path);
+                let res = write_scores_bytes(format, &csv_config, &scores_vec).unwrap();
+                std::fs::write(output_path, res).unwrap();
             } else {
-                let scores_wrt = Vec::new();
-                let mut wtr = csv::Writer::from_writer(scores_wrt);
-                wtr.write_record(&["i", "v"]).unwrap();
-                for x in scores_vec {
-                    wtr.write_record(&[x.id(), x.value().to_string().as_str()])
-                        .unwrap();
-                }
-                let res = wtr.into_inner().unwrap();
+                let res = write_scores_bytes(DataFormat::Csv, &csv_config, &scores_vec).unwrap();
                 println!("{:?}", String::from_utf8(res));
             }
         }
@@ -224,37 +256,20 @@ async fn main() -> Result<(), AwsError> {
             seed_path,
             scores_path,
         } => {
+            let csv_config = load_csv_config();
+            let format = DataFormat::from_path(&trust_path);
             let f = File::open(trust_path).unwrap();
-            let mut rdr = csv::Reader::from_reader(f);
-            let mut trust_entries = Vec::new();
-            for result in rdr.records() {
-                let record: StringRecord = result.unwrap();
-                let (from, to, value): (String, String, f32) = record.deserialize(None).unwrap();
-                let trust_entry = TrustEntry::new(from, to, value);
-                trust_entries.push(trust_entry);
-            }
+            let trust_entries = read_trust_file(format, &csv_config, f).unwrap();
 
-            // Read CSV, to get a list of `ScoreEntry`
+            // Read the dataset, to get a list of `ScoreEntry`
+            let format = DataFormat::from_path(&seed_path);
             let f = File::open(seed_path).unwrap();
-            let mut rdr = csv::Reader::from_reader(f);
-            let mut seed_entries = Vec::new();
-            for result in rdr.records() {
-                let record: StringRecord = result.unwrap();
-                let (i, value): (String, f32) = record.deserialize(None).unwrap();
-                let score_entry = ScoreEntry::new(i, value);
-                seed_entries.push(score_entry);
-            }
+            let seed_entries = read_scores_file(format, &csv_config, f).unwrap();
 
-            // Read CSV, to get a list of `ScoreEntry`
+            // Read the dataset, to get a list of `ScoreEntry`
+            let format = DataFormat::from_path(&scores_path);
             let f = File::open(scores_path).unwrap();
-            let mut rdr = csv::Reader::from_reader(f);
-            let mut scores_entries = Vec::new();
-            for result in rdr.records() {
-                let record: StringRecord = result.unwrap();
-                let (i, value): (String, f32) = record.deserialize(None).unwrap();
-                let score_entry = ScoreEntry::new(i, value);
-                scores_entries.push(score_entry);
-            }
+            let scores_entries = read_scores_file(format, &csv_config, f).unwrap();
 
             let res = verify_local(&trust_entries, &seed_entries, &scores_entries)
                 .await
@@ -262,17 +277,11 @@ async fn main() -> Result<(), AwsError> {
             println!("Verification result: {}", res);
         }
         Method::UploadTrust { path, certs_path } => {
-            {
-                let f = File::open(path.clone()).unwrap();
-                let mut rdr = csv::Reader::from_reader(f);
-                for result in rdr.records() {
-                    let record: StringRecord = result.unwrap();
-                    let (_, _, _): (String, String, f32) = record.deserialize(None).unwrap();
-                }
-            }
+            let config = load_config();
+            ensure_valid(&path, None, config.csv_config());
             let data = std::fs::read(&path).unwrap(); // Read the contents of the file into a vector of bytes
 
-            let eigenda_client = EigenDAProxyClient::new(eigen_da_url);
+            let eigenda_client = EigenDAProxyClient::new(config.eigenda_url().clone());
             let res = eigenda_client.put_meta(data).await;
 
             let mut file = File::create(certs_path).unwrap();
@@ -281,7 +290,7 @@ async fn main() -> Result<(), AwsError> {
         Method::DownloadTrust { path, certs_path } => {
             let data = std::fs::read(&certs_path).unwrap();
 
-            let eigenda_client = EigenDAProxyClient::new(eigen_da_url);
+            let eigenda_client = EigenDAProxyClient::new(load_config().eigenda_url().clone());
 
             let res = eigenda_client.put_meta(data).await;
             let mut file = File::create(path).unwrap();
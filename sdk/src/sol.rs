@@ -1,8 +0,0 @@
-use alloy::sol;
-
-sol!(
-    #[allow(missing_docs)]
-    #[sol(rpc)]
-    OpenRankManager,
-    "../contracts/out/OpenRankManager.sol/OpenRankManager.json"
-);